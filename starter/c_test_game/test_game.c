@@ -3,7 +3,7 @@
 int main() {
 
     // Create the game window
-    create_game_window("C Test Game", 800, 600);
+    GLFWwindow* window = create_game_window("C Test Game", 800, 600);
 
     // Create a sprite
     Sprite* sprite = create_sprite(100.0f, 150.0f, 50, 50, 255, 0, 0); // Red sprite
@@ -12,18 +12,22 @@ int main() {
     Sprite* sprite2 = create_sprite(200.0f, 300.0f, 60, 60, 0, 255, 0); // Green sprite
 
     // Main loop
-    while (!window_should_close()) {
-        clear_screen();
+    while (!window_should_close(window)) {
+        clear_screen(window);
 
         // Render the sprite
-        render_sprite(sprite);
-        render_sprite(sprite2);
+        render_sprite(window, sprite);
+        render_sprite(window, sprite2);
 
-        renderText("Hello", 10.0f, 20.0f, 100,  255, 0, 0);
+        renderText(window, "Hello", 10.0f, 20.0f, 100,  255, 0, 0);
 
         // Update the game window
-        update_game_window();
+        update_game_window(window);
     }
 
+    destroy_sprite(sprite);
+    destroy_sprite(sprite2);
+    destroy_game_window(window);
+
     return 0;
 }
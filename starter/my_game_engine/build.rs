@@ -1,4 +1,9 @@
 fn main() {
+    // The headless backend doesn't need the C library nor GLFW / OpenGL
+    if std::env::var_os("CARGO_FEATURE_OPENGL").is_none() {
+        return;
+    }
+
     // Compile the C code itself
     cc::Build::new()
         .compiler("gcc") // make sure to us gcc instead of clang on macos
@@ -6,6 +11,10 @@ fn main() {
         .include("../opengl_wrapper_lib/")
         .compile("opengl_wrapper_lib");
 
+    // the C sources live outside of this crate, so cargo won't track them by itself
+    println!("cargo::rerun-if-changed=../opengl_wrapper_lib/opengl_wrapper_lib.c");
+    println!("cargo::rerun-if-changed=../opengl_wrapper_lib/opengl_wrapper_lib.h");
+
     println!("cargo::rustc-link-lib=glfw");
     println!("cargo::rustc-link-lib=GL");
     println!("cargo::rustc-link-lib=glut");
@@ -0,0 +1,295 @@
+//! animation.rs
+//!
+//! Sprite-sheet animations. A `SpriteSheet` cuts a texture into a grid of equally sized
+//! frames, and an `Animation` plays a sequence of those frames, each one shown for its own
+//! duration. Animations are advanced with the delta time of the game loop and applied to a
+//! sprite, which then draws the current frame.
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::sprite::SpriteHandle;
+use crate::texture::{SourceRect, Texture};
+
+/// What an animation does once its last frame was shown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlayMode {
+    /// Stop on the last frame
+    Once,
+    /// Start again from the first frame
+    #[default]
+    Loop,
+    /// Play backwards to the first frame, then forwards again
+    PingPong,
+}
+
+/// A texture made of a grid of equally sized frames, numbered row by row from the
+/// top-left corner
+#[derive(Clone, Debug)]
+pub struct SpriteSheet {
+    texture: Texture,
+    frame_width: i32,
+    frame_height: i32,
+    columns: i32,
+    rows: i32,
+}
+
+impl SpriteSheet {
+    /// Cut `texture` into frames of `frame_width` by `frame_height` pixels. Panics on an
+    /// empty frame size.
+    pub fn new(texture: &Texture, frame_width: i32, frame_height: i32) -> SpriteSheet {
+        assert!(
+            frame_width > 0 && frame_height > 0,
+            "sprite sheet frames can't be empty"
+        );
+        SpriteSheet {
+            texture: texture.clone(),
+            frame_width,
+            frame_height,
+            columns: texture.width() / frame_width,
+            rows: texture.height() / frame_height,
+        }
+    }
+
+    /// Load a sprite sheet from a PNG or BMP file
+    pub fn load(
+        path: impl AsRef<Path>,
+        frame_width: i32,
+        frame_height: i32,
+    ) -> io::Result<SpriteSheet> {
+        Ok(SpriteSheet::new(
+            &Texture::load(path)?,
+            frame_width,
+            frame_height,
+        ))
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Number of whole frames in the sheet
+    pub fn frame_count(&self) -> usize {
+        (self.columns * self.rows) as usize
+    }
+
+    /// Part of the texture showing frame `index`. Panics when the sheet has no such frame.
+    pub fn frame(&self, index: usize) -> SourceRect {
+        assert!(
+            index < self.frame_count(),
+            "frame {index} is out of a {} frames sprite sheet",
+            self.frame_count()
+        );
+        let index = index as i32;
+        SourceRect::new(
+            index % self.columns * self.frame_width,
+            index / self.columns * self.frame_height,
+            self.frame_width,
+            self.frame_height,
+        )
+    }
+
+    /// Animation showing each of `frames` for `frame_duration`
+    pub fn animation(
+        &self,
+        frames: impl IntoIterator<Item = usize>,
+        frame_duration: Duration,
+        mode: PlayMode,
+    ) -> Animation {
+        self.timed_animation(
+            frames.into_iter().map(|frame| (frame, frame_duration)),
+            mode,
+        )
+    }
+
+    /// Animation showing each frame for its own duration
+    pub fn timed_animation(
+        &self,
+        frames: impl IntoIterator<Item = (usize, Duration)>,
+        mode: PlayMode,
+    ) -> Animation {
+        let frames = frames
+            .into_iter()
+            .map(|(frame, duration)| (self.frame(frame), duration))
+            .collect();
+        Animation::new(&self.texture, frames, mode)
+    }
+}
+
+/// A sequence of texture parts, each shown for a duration
+#[derive(Clone, Debug)]
+pub struct Animation {
+    texture: Texture,
+    frames: Vec<(SourceRect, Duration)>,
+    mode: PlayMode,
+    /// Index in `frames` of the frame shown
+    frame: usize,
+    /// Whether a ping-pong animation is playing backwards
+    backwards: bool,
+    /// Time the current frame was shown for
+    frame_time: Duration,
+    finished: bool,
+}
+
+impl Animation {
+    /// Animation over parts of `texture`. Panics without frames or on a zero frame duration.
+    pub fn new(
+        texture: &Texture,
+        frames: Vec<(SourceRect, Duration)>,
+        mode: PlayMode,
+    ) -> Animation {
+        assert!(!frames.is_empty(), "an animation needs frames");
+        assert!(
+            frames.iter().all(|(_, duration)| !duration.is_zero()),
+            "animation frames can't last zero time"
+        );
+        Animation {
+            texture: texture.clone(),
+            frames,
+            mode,
+            frame: 0,
+            backwards: false,
+            frame_time: Duration::ZERO,
+            finished: false,
+        }
+    }
+
+    /// Play the animation for `dt` more seconds, the delta time of the game loop
+    pub fn update(&mut self, dt: f32) {
+        if self.finished {
+            return;
+        }
+        self.frame_time += Duration::from_secs_f32(dt.max(0.0));
+
+        while !self.finished && self.frame_time >= self.frames[self.frame].1 {
+            self.frame_time -= self.frames[self.frame].1;
+            self.next_frame();
+        }
+    }
+
+    /// Move to the frame following the current one according to the play mode
+    fn next_frame(&mut self) {
+        let last = self.frames.len() - 1;
+        match (self.mode, self.backwards) {
+            (_, false) if self.frame < last => self.frame += 1,
+            (PlayMode::PingPong, true) if self.frame > 0 => self.frame -= 1,
+            (PlayMode::Once, _) => {
+                self.finished = true;
+                self.frame_time = Duration::ZERO;
+            }
+            (PlayMode::Loop, _) => self.frame = 0,
+            // turn around, unless there is a single frame to show
+            (PlayMode::PingPong, backwards) => {
+                self.backwards = !backwards;
+                self.frame = match backwards {
+                    true => 1.min(last),
+                    false => last.saturating_sub(1),
+                };
+            }
+        }
+    }
+
+    /// Start again from the first frame
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.backwards = false;
+        self.frame_time = Duration::ZERO;
+        self.finished = false;
+    }
+
+    /// Index of the frame shown, in the frames the animation was created with
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    /// Part of the texture to draw now
+    pub fn source(&self) -> SourceRect {
+        self.frames[self.frame].0
+    }
+
+    pub fn mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    /// Whether a `PlayMode::Once` animation reached its end
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Make `sprite` draw the current frame
+    pub fn apply(&self, sprite: &mut SpriteHandle) {
+        sprite.set_texture(&self.texture, Some(self.source()));
+    }
+}
+
+/// Play an animation for `dt` seconds and make a sprite draw its current frame
+#[macro_export]
+macro_rules! ANIMATE_SPRITE {
+    ($sprite:expr, $animation:expr, $dt:expr) => {{
+        $animation.update($dt);
+        $animation.apply(&mut $sprite);
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{self, Backend};
+    use crate::texture::Image;
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    /// A 4 frames sprite sheet, 2 by 2
+    fn sheet() -> SpriteSheet {
+        renderer::select_backend(Backend::Headless);
+        let texture = Texture::new(&Image::new(4, 4, vec![[255; 4]; 16]));
+        SpriteSheet::new(&texture, 2, 2)
+    }
+
+    /// Frames shown after each of `steps` updates of `dt` seconds
+    fn play(animation: &mut Animation, dt: f32, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animation.update(dt);
+                animation.frame_index()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sheet_frames() {
+        let sheet = sheet();
+        assert_eq!(sheet.frame_count(), 4);
+        assert_eq!(sheet.frame(0), SourceRect::new(0, 0, 2, 2));
+        assert_eq!(sheet.frame(3), SourceRect::new(2, 2, 2, 2));
+    }
+
+    #[test]
+    fn play_modes() {
+        let sheet = sheet();
+
+        let mut looping = sheet.animation(0..3, FRAME, PlayMode::Loop);
+        assert_eq!(play(&mut looping, 0.1, 5), vec![1, 2, 0, 1, 2]);
+
+        let mut ping_pong = sheet.animation(0..3, FRAME, PlayMode::PingPong);
+        assert_eq!(play(&mut ping_pong, 0.1, 6), vec![1, 2, 1, 0, 1, 2]);
+
+        let mut once = sheet.animation(0..3, FRAME, PlayMode::Once);
+        assert_eq!(play(&mut once, 0.1, 4), vec![1, 2, 2, 2]);
+        assert!(once.is_finished());
+        once.restart();
+        assert_eq!((once.frame_index(), once.is_finished()), (0, false));
+    }
+
+    #[test]
+    fn frame_durations() {
+        let sheet = sheet();
+        let mut blink = sheet.timed_animation([(0, FRAME * 3), (3, FRAME)], PlayMode::Loop);
+
+        assert_eq!(play(&mut blink, 0.05, 8), vec![0, 0, 0, 0, 0, 1, 1, 0]);
+        // a long step can skip several frames
+        blink.update(0.35);
+        assert_eq!(blink.source(), sheet.frame(3));
+    }
+}
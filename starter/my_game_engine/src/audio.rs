@@ -0,0 +1,653 @@
+//! audio.rs
+//!
+//! Sound effects and music. Sounds are decoded from WAV or OGG Vorbis files, and a `Mixer`
+//! adds up the sounds playing, each at its own volume, into a stereo output. Effects play
+//! once, music loops until it is stopped or replaced.
+//!
+//! Like the renderer, the audio output is picked at startup with `GAME_ENGINE_AUDIO`:
+//! `device` plays on the sound device and needs the `audio-device` feature, `null` mixes
+//! without playing anything, and `wav` records to the file named by
+//! `GAME_ENGINE_AUDIO_FILE`. Outputs without a device follow the clock of the renderer, so
+//! a headless run records what would have been heard.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lewton::inside_ogg::OggStreamReader;
+use lewton::samples::InterleavedSamples;
+
+#[cfg(feature = "audio-device")]
+use crate::audio_device::DeviceOutput;
+use crate::renderer::try_with_renderer;
+
+/// Environment variable used to pick an audio output at startup, `device`, `null` or `wav`
+pub const AUDIO_BACKEND_ENV: &str = "GAME_ENGINE_AUDIO";
+
+/// Environment variable naming the file recorded by the `wav` output
+pub const AUDIO_FILE_ENV: &str = "GAME_ENGINE_AUDIO_FILE";
+
+/// File recorded by the `wav` output when `GAME_ENGINE_AUDIO_FILE` is not set
+const DEFAULT_AUDIO_FILE: &str = "audio.wav";
+
+/// Sample rate of the outputs without a device
+pub const OUTPUT_SAMPLE_RATE: u32 = 44_100;
+
+/// Decoded audio: interleaved samples between -1 and 1. Clones are cheap and share the
+/// samples.
+#[derive(Clone, Debug)]
+pub struct Sound {
+    channels: u16,
+    sample_rate: u32,
+    samples: Arc<[f32]>,
+}
+
+impl Sound {
+    /// Read a WAV or OGG Vorbis file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Sound> {
+        Sound::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Decode the contents of a WAV or OGG Vorbis file
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Sound> {
+        match bytes.get(..4) {
+            Some(b"RIFF") => decode_wav(bytes),
+            Some(b"OggS") => decode_ogg(bytes),
+            _ => Err(invalid_data("not a WAV or OGG file")),
+        }
+    }
+
+    /// A sound made of interleaved `samples` between -1 and 1, e.g. generated by the game
+    pub fn from_samples(channels: u16, sample_rate: u32, samples: Vec<f32>) -> Sound {
+        assert!(
+            channels > 0 && sample_rate > 0,
+            "a sound needs channels and a rate"
+        );
+        Sound {
+            channels,
+            sample_rate,
+            samples: samples.into(),
+        }
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Interleaved samples, a frame holds a sample per channel
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// Number of samples per channel
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames() as f64 / self.sample_rate as f64)
+    }
+
+    /// Left and right samples of a frame, mono sounds play on both sides
+    fn frame(&self, frame: usize) -> (f32, f32) {
+        let start = frame * self.channels as usize;
+        match self.channels {
+            1 => (self.samples[start], self.samples[start]),
+            _ => (self.samples[start], self.samples[start + 1]),
+        }
+    }
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn decode_wav(bytes: Vec<u8>) -> io::Result<Sound> {
+    let reader = hound::WavReader::new(Cursor::new(bytes)).map_err(invalid_data)?;
+    let spec = reader.spec();
+    let samples: Result<Vec<f32>, hound::Error> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect(),
+        hound::SampleFormat::Int => {
+            let full_scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / full_scale))
+                .collect()
+        }
+    };
+    if spec.channels == 0 || spec.sample_rate == 0 {
+        return Err(invalid_data("the WAV file has no channels"));
+    }
+    Ok(Sound::from_samples(
+        spec.channels,
+        spec.sample_rate,
+        samples.map_err(invalid_data)?,
+    ))
+}
+
+fn decode_ogg(bytes: Vec<u8>) -> io::Result<Sound> {
+    let mut reader = OggStreamReader::new(Cursor::new(bytes)).map_err(invalid_data)?;
+    let channels = reader.ident_hdr.audio_channels as u16;
+    let sample_rate = reader.ident_hdr.audio_sample_rate;
+
+    let mut samples = Vec::new();
+    while let Some(packet) = reader
+        .read_dec_packet_generic::<InterleavedSamples<f32>>()
+        .map_err(invalid_data)?
+    {
+        // lossy compression overshoots loud sounds a little
+        samples.extend(packet.samples.iter().map(|sample| sample.clamp(-1.0, 1.0)));
+    }
+    if channels == 0 || sample_rate == 0 {
+        return Err(invalid_data("the OGG file has no channels"));
+    }
+    Ok(Sound::from_samples(channels, sample_rate, samples))
+}
+
+/// Groups of sounds sharing a volume
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Effects,
+    Music,
+}
+
+/// A sound started by `play`, to change or stop it while it plays
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SoundId(u64);
+
+/// A sound being played
+#[derive(Debug)]
+struct Voice {
+    id: SoundId,
+    sound: Sound,
+    channel: Channel,
+    volume: f32,
+    looping: bool,
+    /// Frame of the sound played next, between two frames when resampling
+    position: f64,
+}
+
+impl Voice {
+    /// Add the next frames of the sound to `out`, resampled to `sample_rate`. Returns
+    /// whether the sound is still playing.
+    fn mix(&mut self, out: &mut [f32], sample_rate: u32, gain: f32) -> bool {
+        let frames = self.sound.frames();
+        if frames == 0 {
+            return false;
+        }
+        let step = self.sound.sample_rate as f64 / sample_rate as f64;
+        for frame in out.chunks_exact_mut(2) {
+            if self.position >= frames as f64 {
+                match self.looping {
+                    true => self.position %= frames as f64,
+                    false => return false,
+                }
+            }
+            let index = self.position as usize;
+            let next = match index + 1 < frames {
+                true => index + 1,
+                false if self.looping => 0,
+                false => index,
+            };
+            let t = (self.position - index as f64) as f32;
+            let (left, right) = self.sound.frame(index);
+            let (next_left, next_right) = self.sound.frame(next);
+            frame[0] += (left + (next_left - left) * t) * gain;
+            frame[1] += (right + (next_right - right) * t) * gain;
+            self.position += step;
+        }
+        true
+    }
+}
+
+/// Adds up the sounds playing into stereo frames
+#[derive(Debug)]
+pub struct Mixer {
+    sample_rate: u32,
+    voices: Vec<Voice>,
+    last_id: u64,
+    master_volume: f32,
+    effects_volume: f32,
+    music_volume: f32,
+}
+
+impl Mixer {
+    /// A mixer producing `sample_rate` frames per second
+    pub fn new(sample_rate: u32) -> Mixer {
+        Mixer {
+            sample_rate,
+            voices: Vec::new(),
+            last_id: 0,
+            master_volume: 1.0,
+            effects_volume: 1.0,
+            music_volume: 1.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Start playing `sound` at `volume`, 1 being the volume it was recorded at. Looping
+    /// sounds play until stopped.
+    pub fn play(&mut self, sound: &Sound, channel: Channel, volume: f32, looping: bool) -> SoundId {
+        self.last_id += 1;
+        let id = SoundId(self.last_id);
+        self.voices.push(Voice {
+            id,
+            sound: sound.clone(),
+            channel,
+            volume: volume.max(0.0),
+            looping,
+            position: 0.0,
+        });
+        id
+    }
+
+    pub fn stop(&mut self, id: SoundId) {
+        self.voices.retain(|voice| voice.id != id);
+    }
+
+    /// Stop all the sounds of a channel
+    pub fn stop_channel(&mut self, channel: Channel) {
+        self.voices.retain(|voice| voice.channel != channel);
+    }
+
+    pub fn is_playing(&self, id: SoundId) -> bool {
+        self.voices.iter().any(|voice| voice.id == id)
+    }
+
+    /// Change the volume of a sound while it plays
+    pub fn set_volume(&mut self, id: SoundId, volume: f32) {
+        if let Some(voice) = self.voices.iter_mut().find(|voice| voice.id == id) {
+            voice.volume = volume.max(0.0);
+        }
+    }
+
+    /// Volume applied to every sound, 1 by default
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.max(0.0);
+    }
+
+    /// Volume applied to the sounds of a channel, 1 by default
+    pub fn set_channel_volume(&mut self, channel: Channel, volume: f32) {
+        let volume = volume.max(0.0);
+        match channel {
+            Channel::Effects => self.effects_volume = volume,
+            Channel::Music => self.music_volume = volume,
+        }
+    }
+
+    /// Fill `out` with the next interleaved stereo frames, clipped between -1 and 1. Sounds
+    /// that ended are dropped.
+    pub fn mix(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        let (sample_rate, master_volume) = (self.sample_rate, self.master_volume);
+        let (effects_volume, music_volume) = (self.effects_volume, self.music_volume);
+        self.voices.retain_mut(|voice| {
+            let channel_volume = match voice.channel {
+                Channel::Effects => effects_volume,
+                Channel::Music => music_volume,
+            };
+            let gain = voice.volume * channel_volume * master_volume;
+            voice.mix(out, sample_rate, gain)
+        });
+        for sample in out {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// Where the mixed sound goes
+pub trait AudioOutput {
+    /// Called every frame with the clock of the renderer. Outputs without a device mix
+    /// the frames elapsed since the last call.
+    fn update(&mut self, mixer: &Mutex<Mixer>, now: Duration);
+}
+
+/// Mixes at the pace of the renderer clock, optionally recording to a WAV file
+struct OfflineOutput {
+    recording: Option<(PathBuf, hound::WavWriter<BufWriter<File>>)>,
+    /// Renderer clock at the first update
+    start: Option<Duration>,
+    /// Frames mixed since the first update
+    mixed: u64,
+    buffer: Vec<f32>,
+}
+
+impl OfflineOutput {
+    fn new(recording: Option<(PathBuf, hound::WavWriter<BufWriter<File>>)>) -> OfflineOutput {
+        OfflineOutput {
+            recording,
+            start: None,
+            mixed: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Record to a 16 bit stereo WAV file
+    fn recording(path: PathBuf) -> io::Result<OfflineOutput> {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: OUTPUT_SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = hound::WavWriter::create(&path, spec).map_err(io::Error::other)?;
+        Ok(OfflineOutput::new(Some((path, writer))))
+    }
+}
+
+impl AudioOutput for OfflineOutput {
+    fn update(&mut self, mixer: &Mutex<Mixer>, now: Duration) {
+        let start = *self.start.get_or_insert(now);
+        let elapsed = now.saturating_sub(start).as_secs_f64();
+        let frames = (elapsed * OUTPUT_SAMPLE_RATE as f64) as u64;
+        let new_frames = frames.saturating_sub(self.mixed);
+        self.mixed = self.mixed.max(frames);
+
+        self.buffer.resize(new_frames as usize * 2, 0.0);
+        mixer.lock().unwrap().mix(&mut self.buffer);
+
+        let Some((path, writer)) = &mut self.recording else {
+            return;
+        };
+        let written: Result<(), hound::Error> = self
+            .buffer
+            .iter()
+            .try_for_each(|sample| writer.write_sample((sample * i16::MAX as f32).round() as i16));
+        if let Err(error) = written {
+            println!("Can't record audio to {}: {error}", path.display());
+            self.recording = None;
+        }
+    }
+}
+
+impl Drop for OfflineOutput {
+    fn drop(&mut self) {
+        if let Some((path, writer)) = self.recording.take() {
+            if let Err(error) = writer.finalize() {
+                println!("Can't record audio to {}: {error}", path.display());
+            }
+        }
+    }
+}
+
+/// The audio outputs built into the engine
+#[derive(Clone, Debug, PartialEq)]
+pub enum AudioBackend {
+    /// The default sound device, only available with the `audio-device` feature
+    #[cfg(feature = "audio-device")]
+    Device,
+    /// Sounds are mixed but not played, e.g. in tests
+    Null,
+    /// Sounds are recorded to a WAV file
+    WaveFile(PathBuf),
+}
+
+impl AudioBackend {
+    /// Output named by the `GAME_ENGINE_AUDIO` environment variable, falling back to the
+    /// sound device when it is compiled in and to the null output otherwise
+    pub fn from_env() -> AudioBackend {
+        match std::env::var(AUDIO_BACKEND_ENV).as_deref() {
+            Ok("null") => AudioBackend::Null,
+            Ok("wav") => AudioBackend::WaveFile(
+                std::env::var_os(AUDIO_FILE_ENV)
+                    .unwrap_or_else(|| DEFAULT_AUDIO_FILE.into())
+                    .into(),
+            ),
+            #[cfg(feature = "audio-device")]
+            Ok("device") => AudioBackend::Device,
+            Ok(other) => {
+                println!("Unknown {AUDIO_BACKEND_ENV}={other}, using the default audio output");
+                AudioBackend::default()
+            }
+            Err(_) => AudioBackend::default(),
+        }
+    }
+
+    /// Open the output and a mixer at its sample rate, falling back to the null output
+    fn open(self) -> Audio {
+        let audio = match self {
+            #[cfg(feature = "audio-device")]
+            AudioBackend::Device => DeviceOutput::open().map(|(output, mixer)| Audio {
+                mixer,
+                output: Box::new(output),
+            }),
+            AudioBackend::Null => Ok(Audio::offline(OfflineOutput::new(None))),
+            AudioBackend::WaveFile(path) => OfflineOutput::recording(path).map(Audio::offline),
+        };
+        audio.unwrap_or_else(|error| {
+            println!("Can't open the audio output ({error}), sounds won't be heard");
+            Audio::offline(OfflineOutput::new(None))
+        })
+    }
+}
+
+impl Default for AudioBackend {
+    fn default() -> AudioBackend {
+        #[cfg(feature = "audio-device")]
+        return AudioBackend::Device;
+        #[cfg(not(feature = "audio-device"))]
+        return AudioBackend::Null;
+    }
+}
+
+/// The mixer of the thread and the output it plays on
+struct Audio {
+    mixer: Arc<Mutex<Mixer>>,
+    output: Box<dyn AudioOutput>,
+}
+
+impl Audio {
+    /// An output without a device, mixing at `OUTPUT_SAMPLE_RATE`
+    fn offline(output: OfflineOutput) -> Audio {
+        Audio {
+            mixer: Arc::new(Mutex::new(Mixer::new(OUTPUT_SAMPLE_RATE))),
+            output: Box::new(output),
+        }
+    }
+}
+
+thread_local! {
+    static AUDIO: RefCell<Option<Audio>> = const { RefCell::new(None) };
+}
+
+/// Select one of the built-in audio outputs, stopping the sounds playing
+pub fn select_backend(backend: AudioBackend) {
+    // the previous output, e.g. a recording, is closed first
+    AUDIO.with(|audio| audio.borrow_mut().take());
+    let opened = backend.open();
+    AUDIO.with(|audio| *audio.borrow_mut() = Some(opened));
+}
+
+/// Run a closure against the mixer. When no output was selected, the one from
+/// `AudioBackend::from_env` is opened.
+pub fn with_mixer<T>(f: impl FnOnce(&mut Mixer) -> T) -> T {
+    AUDIO.with(|audio| {
+        let mut audio = audio.borrow_mut();
+        let audio = audio.get_or_insert_with(|| AudioBackend::from_env().open());
+        let mut mixer = audio.mixer.lock().unwrap();
+        f(&mut mixer)
+    })
+}
+
+/// Play a sound effect once
+pub fn play(sound: &Sound) -> SoundId {
+    play_with_volume(sound, 1.0)
+}
+
+/// Play a sound effect once at `volume`, 1 being the volume it was recorded at
+pub fn play_with_volume(sound: &Sound, volume: f32) -> SoundId {
+    with_mixer(|mixer| mixer.play(sound, Channel::Effects, volume, false))
+}
+
+/// Loop `sound` as the music of the game, replacing the music playing
+pub fn play_music(sound: &Sound) -> SoundId {
+    with_mixer(|mixer| {
+        mixer.stop_channel(Channel::Music);
+        mixer.play(sound, Channel::Music, 1.0, true)
+    })
+}
+
+pub fn stop_music() {
+    with_mixer(|mixer| mixer.stop_channel(Channel::Music));
+}
+
+pub fn stop(id: SoundId) {
+    with_mixer(|mixer| mixer.stop(id));
+}
+
+pub fn is_playing(id: SoundId) -> bool {
+    with_mixer(|mixer| mixer.is_playing(id))
+}
+
+/// Change the volume of a sound while it plays
+pub fn set_volume(id: SoundId, volume: f32) {
+    with_mixer(|mixer| mixer.set_volume(id, volume));
+}
+
+/// Volume applied to every sound, 1 by default
+pub fn set_master_volume(volume: f32) {
+    with_mixer(|mixer| mixer.set_master_volume(volume));
+}
+
+/// Volume applied to the effects or the music, 1 by default
+pub fn set_channel_volume(channel: Channel, volume: f32) {
+    with_mixer(|mixer| mixer.set_channel_volume(channel, volume));
+}
+
+/// Let the output catch up with the renderer clock, once a frame. Does nothing until a
+/// sound was played.
+pub(crate) fn update() {
+    let Some(now) = try_with_renderer(|renderer| renderer.time()) else {
+        return;
+    };
+    let _ = AUDIO.try_with(|audio| {
+        if let Some(audio) = audio.borrow_mut().as_mut() {
+            audio.output.update(&audio.mixer, now);
+        }
+    });
+}
+
+/// Load a sound from a WAV or OGG file, returning an `io::Result<Sound>`
+#[macro_export]
+macro_rules! LOAD_SOUND {
+    ($path:expr) => {
+        $crate::audio::Sound::load($path)
+    };
+}
+
+/// Play a sound effect once, optionally at a volume
+#[macro_export]
+macro_rules! PLAY_SOUND {
+    ($sound:expr) => {
+        $crate::audio::play(&$sound)
+    };
+    ($sound:expr, $volume:expr) => {
+        $crate::audio::play_with_volume(&$sound, $volume)
+    };
+}
+
+/// Loop a sound as the music of the game, replacing the music playing
+#[macro_export]
+macro_rules! PLAY_MUSIC {
+    ($sound:expr) => {
+        $crate::audio::play_music(&$sound)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_sound_path() -> &'static str {
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/sounds/sine_440hz_stereo.ogg"
+        )
+    }
+
+    #[test]
+    fn decodes_wav_and_ogg() {
+        // a 16 bit mono WAV file, written in memory
+        let mut bytes = Cursor::new(Vec::new());
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for sample in [0, i16::MAX, i16::MIN, 16384] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let wav = Sound::from_bytes(bytes.into_inner()).unwrap();
+        assert_eq!(
+            (wav.channels(), wav.sample_rate(), wav.frames()),
+            (1, 8000, 4)
+        );
+        assert_eq!(wav.frame(2), (-1.0, -1.0));
+        assert_eq!(wav.frame(3), (0.5, 0.5));
+        assert_eq!(wav.duration(), Duration::from_micros(500));
+
+        let ogg = Sound::load(test_sound_path()).unwrap();
+        assert_eq!((ogg.channels(), ogg.sample_rate()), (2, 44100));
+        assert!(ogg.duration() > Duration::from_millis(900));
+        let peak = ogg
+            .samples
+            .iter()
+            .fold(0.0_f32, |peak, s| peak.max(s.abs()));
+        assert!(peak > 0.1 && peak <= 1.0);
+
+        let error = Sound::from_bytes(b"not a sound".to_vec()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn mixes_voices_with_their_volumes() {
+        let mut mixer = Mixer::new(4);
+        let one = Sound::from_samples(1, 4, vec![0.5; 4]);
+        let stereo = Sound::from_samples(2, 4, vec![0.25, -0.25, 0.25, -0.25]);
+
+        let effect = mixer.play(&one, Channel::Effects, 1.0, false);
+        mixer.play(&stereo, Channel::Music, 2.0, true);
+        mixer.set_channel_volume(Channel::Music, 0.5);
+        let mut out = [0.0; 6];
+        mixer.mix(&mut out);
+        assert_eq!(out, [0.75, 0.25, 0.75, 0.25, 0.75, 0.25]);
+
+        // the effect ends after its 4 frames, the music loops
+        mixer.set_master_volume(2.0);
+        mixer.mix(&mut out);
+        assert_eq!(out, [1.0, 0.5, 0.5, -0.5, 0.5, -0.5]);
+        assert!(!mixer.is_playing(effect));
+
+        mixer.stop_channel(Channel::Music);
+        mixer.mix(&mut out);
+        assert_eq!(out, [0.0; 6]);
+    }
+
+    #[test]
+    fn resamples_to_the_output_rate() {
+        let mut mixer = Mixer::new(4);
+        let sound = Sound::from_samples(1, 2, vec![0.0, 1.0]);
+        let id = mixer.play(&sound, Channel::Effects, 1.0, false);
+        let mut out = [0.0; 8];
+        mixer.mix(&mut out);
+        // halfway between two samples, then the last sample is held
+        assert_eq!(out, [0.0, 0.0, 0.5, 0.5, 1.0, 1.0, 1.0, 1.0]);
+        assert!(mixer.is_playing(id));
+        mixer.mix(&mut out);
+        assert!(!mixer.is_playing(id));
+    }
+}
@@ -0,0 +1,83 @@
+//! audio_device.rs
+//!
+//! The `AudioOutput` playing on the default sound device through `cpal`. The device asks
+//! for samples from its own thread, which locks the mixer to fill them in.
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+
+use crate::audio::{AudioOutput, Mixer};
+
+/// Keeps the stream of the sound device open
+pub(crate) struct DeviceOutput {
+    _stream: Stream,
+}
+
+impl DeviceOutput {
+    /// Start playing on the default output device, with a mixer at its sample rate
+    pub(crate) fn open() -> io::Result<(DeviceOutput, Arc<Mutex<Mixer>>)> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no sound device"))?;
+        let supported = device.default_output_config().map_err(io::Error::other)?;
+        let sample_format = supported.sample_format();
+        let config: StreamConfig = supported.into();
+        let mixer = Arc::new(Mutex::new(Mixer::new(config.sample_rate.0)));
+
+        let stream = match sample_format {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, mixer.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, mixer.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, mixer.clone()),
+            other => {
+                let error = format!("unsupported sample format {other}");
+                return Err(io::Error::new(io::ErrorKind::Unsupported, error));
+            }
+        }?;
+        stream.play().map_err(io::Error::other)?;
+        Ok((DeviceOutput { _stream: stream }, mixer))
+    }
+}
+
+impl AudioOutput for DeviceOutput {
+    /// The device pulls the samples it needs by itself
+    fn update(&mut self, _mixer: &Mutex<Mixer>, _now: Duration) {}
+}
+
+/// Open a stream of `T` samples mixing the sounds of `mixer`, in stereo or spread over
+/// the channels of the device
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mixer: Arc<Mutex<Mixer>>,
+) -> io::Result<Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let mut stereo = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                stereo.resize(data.len() / channels * 2, 0.0);
+                mixer.lock().unwrap().mix(&mut stereo);
+                for (frame, sides) in data.chunks_mut(channels).zip(stereo.chunks(2)) {
+                    for (channel, sample) in frame.iter_mut().enumerate() {
+                        let value = match (channels, channel) {
+                            (1, _) => (sides[0] + sides[1]) / 2.0,
+                            (_, 0 | 1) => sides[channel],
+                            _ => 0.0,
+                        };
+                        *sample = T::from_sample(value);
+                    }
+                }
+            },
+            |error| println!("Sound device error: {error}"),
+            None,
+        )
+        .map_err(io::Error::other)
+}
@@ -0,0 +1,298 @@
+//! camera.rs
+//!
+//! Cameras looking at a game world larger than the window. Once a camera is set on a window,
+//! sprites and shapes are positioned in world coordinates: the camera picks the part of the
+//! world shown, zoomed, in the logical resolution of the window. Text and the frame
+//! statistics stay in window coordinates, and so does anything drawn in `on_screen`.
+//!
+//! Mouse positions are reported in window coordinates, `to_world` converts them to world
+//! coordinates.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use crate::game_ffi::Sprite;
+use crate::window::{self, WindowId};
+
+/// A view of the world: the position it is centered on, how much it is zoomed, and an
+/// optional target it follows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    center: (f32, f32),
+    zoom: f32,
+    target: Option<(f32, f32)>,
+    /// Fraction of the distance to the target left after a second of following it
+    smoothing: f32,
+    /// Left, top, right and bottom of the world area the view stays in
+    bounds: Option<(f32, f32, f32, f32)>,
+}
+
+impl Camera {
+    /// A camera centered on world position `x`, `y`, unzoomed
+    pub fn new(x: f32, y: f32) -> Camera {
+        Camera {
+            center: (x, y),
+            zoom: 1.0,
+            target: None,
+            smoothing: 0.0,
+            bounds: None,
+        }
+    }
+
+    /// Show the world `zoom` times bigger, 0.5 shows twice as much of it
+    pub fn with_zoom(mut self, zoom: f32) -> Camera {
+        self.set_zoom(zoom);
+        self
+    }
+
+    /// Follow the target smoothly: `smoothing` is the fraction of the distance to the target
+    /// left after a second, 0 jumps on the target right away
+    pub fn with_smoothing(mut self, smoothing: f32) -> Camera {
+        self.smoothing = smoothing.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Never show anything outside of a world area, e.g. the size of the level. A view
+    /// larger than the area is centered on it.
+    pub fn with_bounds(mut self, x: f32, y: f32, width: f32, height: f32) -> Camera {
+        self.bounds = Some((x, y, x + width, y + height));
+        self
+    }
+
+    /// World position at the center of the view, before `bounds` are applied
+    pub fn center(&self) -> (f32, f32) {
+        self.center
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// World position followed by the camera
+    pub fn target(&self) -> Option<(f32, f32)> {
+        self.target
+    }
+
+    /// Center the view on world position `x`, `y` right away
+    pub fn set_center(&mut self, x: f32, y: f32) {
+        self.center = (x, y);
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.max(f32::EPSILON);
+    }
+
+    /// Move towards world position `x`, `y` in `update`, e.g. the position of the player
+    pub fn follow(&mut self, x: f32, y: f32) {
+        self.target = Some((x, y));
+    }
+
+    /// Stay where the camera is
+    pub fn stop_following(&mut self) {
+        self.target = None;
+    }
+
+    /// Move towards the target for `dt` seconds
+    pub fn update(&mut self, dt: f32) {
+        let Some((target_x, target_y)) = self.target else {
+            return;
+        };
+        let progress = 1.0 - self.smoothing.powf(dt.max(0.0));
+        self.center.0 += (target_x - self.center.0) * progress;
+        self.center.1 += (target_y - self.center.1) * progress;
+    }
+
+    /// World area shown in a view of `view_size` logical pixels: left, top, width and height
+    pub fn visible_area(&self, view_size: (i32, i32)) -> (f32, f32, f32, f32) {
+        let width = view_size.0 as f32 / self.zoom;
+        let height = view_size.1 as f32 / self.zoom;
+        let (center_x, center_y) = self.bounded_center(width, height);
+        (
+            center_x - width / 2.0,
+            center_y - height / 2.0,
+            width,
+            height,
+        )
+    }
+
+    /// Logical position in a view of `view_size` of world position `x`, `y`
+    pub fn to_screen(&self, view_size: (i32, i32), x: f32, y: f32) -> (f32, f32) {
+        let (left, top, _, _) = self.visible_area(view_size);
+        ((x - left) * self.zoom, (y - top) * self.zoom)
+    }
+
+    /// World position of logical position `x`, `y` in a view of `view_size`
+    pub fn to_world(&self, view_size: (i32, i32), x: f32, y: f32) -> (f32, f32) {
+        let (left, top, _, _) = self.visible_area(view_size);
+        (left + x / self.zoom, top + y / self.zoom)
+    }
+
+    /// Center of a `width` by `height` view kept inside the bounds
+    fn bounded_center(&self, width: f32, height: f32) -> (f32, f32) {
+        let Some((left, top, right, bottom)) = self.bounds else {
+            return self.center;
+        };
+        let clamp = |center: f32, size: f32, min: f32, max: f32| match max - min > size {
+            true => center.clamp(min + size / 2.0, max - size / 2.0),
+            false => (min + max) / 2.0,
+        };
+        (
+            clamp(self.center.0, width, left, right),
+            clamp(self.center.1, height, top, bottom),
+        )
+    }
+}
+
+thread_local! {
+    /// The cameras of the windows of the current thread
+    static CAMERAS: RefCell<HashMap<WindowId, Camera>> = RefCell::new(HashMap::new());
+    /// Whether draws bypass the camera, see `on_screen`
+    static ON_SCREEN: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The camera of the current window and its logical size
+fn current_view() -> Option<(Camera, (i32, i32))> {
+    let id = window::current()?;
+    let camera = CAMERAS.with(|cameras| cameras.borrow().get(&id).copied())?;
+    Some((camera, window::logical_size()))
+}
+
+/// Look at the world of the current window through `camera`
+pub fn set(camera: Camera) {
+    let id = window::current().expect("the game window must be created first");
+    CAMERAS.with(|cameras| cameras.borrow_mut().insert(id, camera));
+}
+
+/// Draw in window coordinates again
+pub fn reset() {
+    if let Some(id) = window::current() {
+        forget(id);
+    }
+}
+
+/// The camera of the current window, if any
+pub fn get() -> Option<Camera> {
+    current_view().map(|(camera, _)| camera)
+}
+
+/// Change the camera of the current window, e.g. to follow the player. Returns `None`
+/// when the window has no camera.
+pub fn with_camera<T>(f: impl FnOnce(&mut Camera) -> T) -> Option<T> {
+    let id = window::current()?;
+    CAMERAS.with(|cameras| cameras.borrow_mut().get_mut(&id).map(f))
+}
+
+/// World area shown in the current window: left, top, width and height
+pub fn visible_area() -> (f32, f32, f32, f32) {
+    match current_view() {
+        Some((camera, view_size)) => camera.visible_area(view_size),
+        None => {
+            let (width, height) = window::logical_size();
+            (0.0, 0.0, width as f32, height as f32)
+        }
+    }
+}
+
+/// World position of a logical position in the current window, e.g. of the mouse cursor
+pub fn to_world(x: f32, y: f32) -> (f32, f32) {
+    match current_view() {
+        Some((camera, view_size)) => camera.to_world(view_size, x, y),
+        None => (x, y),
+    }
+}
+
+/// Logical position in the current window of a world position
+pub fn to_screen(x: f32, y: f32) -> (f32, f32) {
+    match current_view() {
+        Some((camera, view_size)) => camera.to_screen(view_size, x, y),
+        None => (x, y),
+    }
+}
+
+/// Run `f` with sprites and shapes positioned in window coordinates, e.g. to draw a HUD
+pub fn on_screen<T>(f: impl FnOnce() -> T) -> T {
+    let previous = ON_SCREEN.with(|on_screen| on_screen.replace(true));
+    let result = f();
+    ON_SCREEN.with(|on_screen| on_screen.set(previous));
+    result
+}
+
+/// The camera transforming draws made now, if any
+fn drawing_view() -> Option<(Camera, (i32, i32))> {
+    match ON_SCREEN.with(Cell::get) {
+        true => None,
+        false => current_view(),
+    }
+}
+
+/// Place a sprite given in world coordinates in the window. Zooming scales the sprite
+/// around its origin, which keeps rotations right.
+pub(crate) fn project_sprite(sprite: &Sprite) -> Sprite {
+    let mut projected = *sprite;
+    let Some((camera, view_size)) = drawing_view() else {
+        return projected;
+    };
+
+    let origin_x = sprite.origin[0] * sprite.width as f32;
+    let origin_y = sprite.origin[1] * sprite.height as f32;
+    let (pivot_x, pivot_y) = camera.to_screen(view_size, sprite.x + origin_x, sprite.y + origin_y);
+    projected.x = pivot_x - origin_x;
+    projected.y = pivot_y - origin_y;
+    projected.scale = [sprite.scale[0] * camera.zoom, sprite.scale[1] * camera.zoom];
+    projected
+}
+
+/// Place points given in world coordinates in the window
+pub(crate) fn project_points(mut points: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+    if let Some((camera, view_size)) = drawing_view() {
+        for point in &mut points {
+            *point = camera.to_screen(view_size, point.0, point.1);
+        }
+    }
+    points
+}
+
+/// Drop the camera of a closed window
+pub(crate) fn forget(id: WindowId) {
+    CAMERAS.with(|cameras| cameras.borrow_mut().remove(&id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEW: (i32, i32) = (800, 600);
+
+    #[test]
+    fn converts_between_screen_and_world() {
+        let camera = Camera::new(1000.0, 500.0).with_zoom(2.0);
+        assert_eq!(camera.visible_area(VIEW), (800.0, 350.0, 400.0, 300.0));
+        assert_eq!(camera.to_screen(VIEW, 1000.0, 500.0), (400.0, 300.0));
+        assert_eq!(camera.to_screen(VIEW, 800.0, 350.0), (0.0, 0.0));
+        assert_eq!(camera.to_world(VIEW, 800.0, 600.0), (1200.0, 650.0));
+
+        // a camera centered on the window shows it unchanged
+        let identity = Camera::new(400.0, 300.0);
+        assert_eq!(identity.to_screen(VIEW, 12.0, 34.0), (12.0, 34.0));
+    }
+
+    #[test]
+    fn follows_its_target_within_bounds() {
+        let mut camera = Camera::new(0.0, 0.0).with_smoothing(0.25);
+        camera.follow(100.0, 40.0);
+        camera.update(1.0);
+        assert_eq!(camera.center(), (75.0, 30.0));
+        camera.update(0.5);
+        assert_eq!(camera.center(), (87.5, 35.0));
+        camera.stop_following();
+        camera.update(1.0);
+        assert_eq!(camera.center(), (87.5, 35.0));
+
+        // the view stays inside the world, and is centered on a world smaller than itself
+        let bounded = Camera::new(0.0, 0.0).with_bounds(0.0, 0.0, 2000.0, 500.0);
+        assert_eq!(bounded.visible_area(VIEW), (0.0, -50.0, 800.0, 600.0));
+        let mut bounded = bounded.with_zoom(2.0);
+        bounded.set_center(1900.0, 400.0);
+        assert_eq!(bounded.visible_area(VIEW), (1600.0, 200.0, 400.0, 300.0));
+    }
+}
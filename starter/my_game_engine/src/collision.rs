@@ -0,0 +1,299 @@
+//! collision.rs
+//!
+//! Collision tests between axis-aligned rectangles, circles and points, in logical or world
+//! coordinates. Sprites collide through their bounds, the rectangle they cover once rotated
+//! and scaled.
+//!
+//! Shapes touching along an edge don't overlap: sprites side by side on a grid don't
+//! collide. Penetration vectors tell how far to move a shape for it to only touch the other.
+
+use crate::game_ffi::Sprite;
+
+/// An axis-aligned rectangle with its top-left corner at `x`, `y`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Rectangle covered by a sprite, rotated and scaled around its origin
+    pub fn of_sprite(sprite: &Sprite) -> Rect {
+        let (width, height) = (sprite.width as f32, sprite.height as f32);
+        if sprite.rotation == 0.0 && sprite.scale == [1.0, 1.0] {
+            return Rect::new(sprite.x, sprite.y, width, height);
+        }
+
+        // same transform as the renderers
+        let pivot_x = sprite.x + sprite.origin[0] * width;
+        let pivot_y = sprite.y + sprite.origin[1] * height;
+        let (sin, cos) = sprite.rotation.to_radians().sin_cos();
+        let corners = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)].map(|(x, y)| {
+            let dx = (sprite.x + x - pivot_x) * sprite.scale[0];
+            let dy = (sprite.y + y - pivot_y) * sprite.scale[1];
+            (pivot_x + dx * cos - dy * sin, pivot_y + dx * sin + dy * cos)
+        });
+        let (mut left, mut top) = corners[0];
+        let (mut right, mut bottom) = corners[0];
+        for (x, y) in corners {
+            (left, top) = (left.min(x), top.min(y));
+            (right, bottom) = (right.max(x), bottom.max(y));
+        }
+        Rect::new(left, top, right - left, bottom - top)
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// Whether point `x`, `y` is inside, the right and bottom edges excluded like pixels
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// Whether the rectangles share some area, including when one contains the other
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+
+    /// Area shared by the rectangles
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let (left, top) = (self.x.max(other.x), self.y.max(other.y));
+        let (right, bottom) = (
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        );
+        Some(Rect::new(left, top, right - left, bottom - top))
+    }
+
+    /// Shortest move of this rectangle out of `other`, along the axis they overlap the
+    /// least on
+    pub fn penetration(&self, other: &Rect) -> Option<(f32, f32)> {
+        let overlap = self.intersection(other)?;
+        let (center_x, center_y) = self.center();
+        let (other_x, other_y) = other.center();
+        let away = |center: f32, other: f32| match center < other {
+            true => -1.0,
+            false => 1.0,
+        };
+        Some(match overlap.width < overlap.height {
+            true => (overlap.width * away(center_x, other_x), 0.0),
+            false => (0.0, overlap.height * away(center_y, other_y)),
+        })
+    }
+
+    /// Point of the rectangle closest to `x`, `y`
+    fn closest_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x.clamp(self.x, self.right()),
+            y.clamp(self.y, self.bottom()),
+        )
+    }
+}
+
+impl From<&Sprite> for Rect {
+    fn from(sprite: &Sprite) -> Rect {
+        Rect::of_sprite(sprite)
+    }
+}
+
+/// A circle of `radius` around `x`, `y`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Circle {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+impl Circle {
+    pub fn new(x: f32, y: f32, radius: f32) -> Circle {
+        Circle { x, y, radius }
+    }
+
+    /// Whether point `x`, `y` is inside, the edge excluded
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        distance_squared((self.x, self.y), (x, y)) < self.radius * self.radius
+    }
+
+    pub fn intersects(&self, other: &Circle) -> bool {
+        let radii = self.radius + other.radius;
+        distance_squared((self.x, self.y), (other.x, other.y)) < radii * radii
+    }
+
+    pub fn intersects_rect(&self, rect: &Rect) -> bool {
+        if rect.width <= 0.0 || rect.height <= 0.0 {
+            return false;
+        }
+        let (closest_x, closest_y) = rect.closest_point(self.x, self.y);
+        self.contains(closest_x, closest_y)
+    }
+
+    /// Shortest move of this circle out of `other`, away from its center
+    pub fn penetration(&self, other: &Circle) -> Option<(f32, f32)> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let (dx, dy) = (self.x - other.x, self.y - other.y);
+        let distance = (dx * dx + dy * dy).sqrt();
+        let depth = self.radius + other.radius - distance;
+        // concentric circles are pushed apart to the right
+        Some(match distance > 0.0 {
+            true => (dx / distance * depth, dy / distance * depth),
+            false => (depth, 0.0),
+        })
+    }
+
+    /// Shortest move of this circle out of `rect`
+    pub fn penetration_rect(&self, rect: &Rect) -> Option<(f32, f32)> {
+        if !self.intersects_rect(rect) {
+            return None;
+        }
+        let (closest_x, closest_y) = rect.closest_point(self.x, self.y);
+        let (dx, dy) = (self.x - closest_x, self.y - closest_y);
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance > 0.0 {
+            let depth = self.radius - distance;
+            return Some((dx / distance * depth, dy / distance * depth));
+        }
+
+        // the center is inside or on an edge, leave through the closest edge
+        let exits = [
+            (rect.x - self.x - self.radius, 0.0),
+            (rect.right() - self.x + self.radius, 0.0),
+            (0.0, rect.y - self.y - self.radius),
+            (0.0, rect.bottom() - self.y + self.radius),
+        ];
+        exits
+            .into_iter()
+            .min_by(|a, b| (a.0.abs() + a.1.abs()).total_cmp(&(b.0.abs() + b.1.abs())))
+    }
+}
+
+fn distance_squared(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)
+}
+
+/// Whether two sprites overlap, see `collision::Rect::of_sprite`
+#[macro_export]
+macro_rules! SPRITES_OVERLAP {
+    ($s1:expr, $s2:expr) => {{
+        $s1.bounds().intersects(&$s2.bounds())
+    }};
+}
+
+/// Whether a point is inside the bounds of a sprite
+#[macro_export]
+macro_rules! SPRITE_CONTAINS {
+    ($sprite:expr, $x:expr, $y:expr) => {{
+        $sprite.bounds().contains($x, $y)
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{self, Backend};
+    use crate::sprite::SpriteHandle;
+
+    #[test]
+    fn rectangles_intersect() {
+        let a = Rect::new(0.0, 0.0, 10.0, 10.0);
+        // one containing the other, a cross and a corner
+        assert!(a.intersects(&Rect::new(2.0, 2.0, 2.0, 2.0)));
+        assert!(Rect::new(2.0, 2.0, 2.0, 2.0).intersects(&a));
+        assert!(Rect::new(3.0, -5.0, 4.0, 20.0).intersects(&Rect::new(-5.0, 3.0, 20.0, 4.0)));
+        assert!(a.intersects(&Rect::new(9.0, 9.0, 5.0, 5.0)));
+        // touching edges
+        assert!(!a.intersects(&Rect::new(10.0, 0.0, 10.0, 10.0)));
+        assert!(!a.intersects(&Rect::new(0.0, 10.0, 10.0, 10.0)));
+
+        assert!(a.contains(0.0, 0.0) && a.contains(9.5, 9.5));
+        assert!(!a.contains(10.0, 5.0) && !a.contains(5.0, -0.1));
+        assert_eq!(
+            a.intersection(&Rect::new(6.0, -2.0, 10.0, 5.0)),
+            Some(Rect::new(6.0, 0.0, 4.0, 3.0))
+        );
+    }
+
+    #[test]
+    fn penetration_separates_shapes() {
+        let wall = Rect::new(0.0, 0.0, 100.0, 10.0);
+        let falling = Rect::new(20.0, -8.0, 10.0, 10.0);
+        assert_eq!(falling.penetration(&wall), Some((0.0, -2.0)));
+        let from_right = Rect::new(97.0, 2.0, 10.0, 6.0);
+        assert_eq!(from_right.penetration(&wall), Some((3.0, 0.0)));
+        assert_eq!(Rect::new(0.0, 20.0, 5.0, 5.0).penetration(&wall), None);
+
+        let ball = Circle::new(50.0, 14.0, 5.0);
+        assert!(ball.intersects_rect(&wall));
+        assert_eq!(ball.penetration_rect(&wall), Some((0.0, 1.0)));
+        // past the corner, the circle is pushed away from it
+        let corner = Circle::new(103.0, 14.0, 6.0);
+        assert!(corner.intersects_rect(&wall));
+        let (dx, dy) = corner.penetration_rect(&wall).unwrap();
+        assert!((dx - 0.6).abs() < 1e-4 && (dy - 0.8).abs() < 1e-4);
+        assert!(!Circle::new(105.0, 14.0, 6.0).intersects_rect(&wall));
+        // a center inside leaves through the closest edge
+        let inside = Circle::new(50.0, 8.0, 1.0);
+        assert_eq!(inside.penetration_rect(&wall), Some((0.0, 3.0)));
+        // as does a center on the right edge, outside of `Rect::contains`
+        let on_edge = Circle::new(100.0, 5.0, 2.0);
+        assert_eq!(on_edge.penetration_rect(&wall), Some((2.0, 0.0)));
+
+        let other = Circle::new(0.0, 0.0, 3.0);
+        assert_eq!(
+            Circle::new(4.0, 0.0, 2.0).penetration(&other),
+            Some((1.0, 0.0))
+        );
+        assert_eq!(Circle::new(0.0, 5.0, 2.0).penetration(&other), None);
+        assert!(other.contains(1.0, 1.0) && !other.contains(3.0, 0.0));
+    }
+
+    #[test]
+    fn sprites_collide_through_their_bounds() {
+        renderer::select_backend(Backend::Headless);
+        let head = SpriteHandle::new(100.0, 100.0, 25, 25, 0, 255, 0);
+        let food = SpriteHandle::new(105.0, 105.0, 10, 10, 255, 0, 0);
+        let next_cell = SpriteHandle::new(125.0, 100.0, 25, 25, 255, 0, 0);
+        assert!(SPRITES_OVERLAP!(head, food) && SPRITES_OVERLAP!(food, head));
+        assert!(!SPRITES_OVERLAP!(head, next_cell));
+        assert!(SPRITE_CONTAINS!(head, 124.0, 124.0));
+
+        // rotated by 45 degrees around its center, the sprite reaches the next cell
+        let mut rotated = head.duplicate_at(100.0, 100.0);
+        rotated.set_rotation(45.0);
+        let half_diagonal = 12.5 * std::f32::consts::SQRT_2;
+        let bounds = rotated.bounds();
+        assert!((bounds.right() - (112.5 + half_diagonal)).abs() < 1e-3);
+        assert!(SPRITES_OVERLAP!(rotated, next_cell));
+
+        let mut scaled = head.duplicate_at(100.0, 100.0);
+        scaled.set_scale(2.0, 0.5);
+        assert_eq!(scaled.bounds(), Rect::new(87.5, 106.25, 50.0, 12.5));
+    }
+}
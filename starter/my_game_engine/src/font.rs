@@ -0,0 +1,467 @@
+//! font.rs
+//!
+//! TrueType and OpenType fonts. Glyphs are rasterized on demand into an atlas texture, at
+//! the size the text covers in the window, and drawn as textured sprites: text stays sharp
+//! at any size and resolution, and any character of the font can be drawn.
+//!
+//! `TEXT_RENDER!` uses the default font, DejaVu Sans built into the engine unless another
+//! font is set with `set_default`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use ab_glyph::{Font as _, FontVec, GlyphId, PxScale, ScaleFont};
+
+use crate::camera;
+use crate::game_ffi::Sprite;
+use crate::layer;
+use crate::renderer::with_renderer;
+use crate::text::TextMetrics;
+use crate::texture::{Image, SourceRect, Texture};
+use crate::window;
+
+/// Height in logical pixels of a line of text drawn with a scale of 1
+pub const DEFAULT_SIZE: f32 = 18.0;
+
+/// DejaVu Sans, the font used until another default font is set
+const BUILT_IN_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+
+/// Side of a new atlas, doubled whenever it is full up to the maximum texture size
+const ATLAS_SIZE: i32 = 256;
+/// Free pixels around each glyph of the atlas
+const GLYPH_PADDING: i32 = 1;
+
+/// A glyph rasterized in the atlas
+#[derive(Clone, Copy)]
+struct AtlasGlyph {
+    source: SourceRect,
+    /// Position of the top-left corner of the glyph from its origin on the baseline
+    offset: (f32, f32),
+}
+
+/// Glyphs packed in rows, in an image uploaded as a texture when glyphs are added
+struct Atlas {
+    size: i32,
+    pixels: Vec<[u8; 4]>,
+    /// Glyphs by id and size in quarters of a pixel, `None` for glyphs without outline
+    glyphs: HashMap<(GlyphId, u32), Option<AtlasGlyph>>,
+    /// Where the next glyph goes, and the height of the current row
+    cursor: (i32, i32),
+    row_height: i32,
+    texture: Option<Texture>,
+}
+
+impl Atlas {
+    fn new(size: i32) -> Atlas {
+        Atlas {
+            size,
+            pixels: vec![[0; 4]; (size * size) as usize],
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_height: 0,
+            texture: None,
+        }
+    }
+
+    /// Find room for a `width` by `height` glyph, `None` when the atlas is full
+    fn allocate(&mut self, width: i32, height: i32) -> Option<(i32, i32)> {
+        let (width, height) = (width + GLYPH_PADDING, height + GLYPH_PADDING);
+        if self.cursor.0 + width > self.size {
+            self.cursor = (0, self.cursor.1 + self.row_height);
+            self.row_height = 0;
+        }
+        if self.cursor.0 + width > self.size || self.cursor.1 + height > self.size {
+            return None;
+        }
+        let position = self.cursor;
+        self.cursor.0 += width;
+        self.row_height = self.row_height.max(height);
+        Some(position)
+    }
+
+    /// The texture of the atlas, uploaded again when glyphs were added
+    fn texture(&mut self) -> Texture {
+        self.texture
+            .get_or_insert_with(|| {
+                Texture::new(&Image::new(self.size, self.size, self.pixels.clone()))
+            })
+            .clone()
+    }
+}
+
+struct FontData {
+    font: FontVec,
+    atlas: RefCell<Atlas>,
+}
+
+/// A font loaded from a TTF or OTF file. Clones are cheap and share the glyphs
+/// rasterized so far.
+#[derive(Clone)]
+pub struct Font {
+    data: Rc<FontData>,
+}
+
+/// A glyph placed on a line of text, in window pixels from the origin of the text
+struct PlacedGlyph {
+    id: GlyphId,
+    x: f32,
+    y: f32,
+}
+
+impl Font {
+    /// Read a TTF or OTF file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Font> {
+        Font::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Parse the contents of a TTF or OTF file
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Font> {
+        let font = FontVec::try_from_vec(bytes)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(Font {
+            data: Rc::new(FontData {
+                font,
+                atlas: RefCell::new(Atlas::new(ATLAS_SIZE)),
+            }),
+        })
+    }
+
+    /// Whether the font has a glyph for `c`
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.data.font.glyph_id(c) != GlyphId(0)
+    }
+
+    /// Draw `text` with its first baseline starting at `x`, `y`, `size` logical pixels
+    /// high, on `layer`. Lines are separated by `\n`. Text is not moved by the camera.
+    pub fn draw(&self, layer: i32, text: &str, x: f32, y: f32, size: f32, color: [i32; 4]) {
+        let pixel_scale = pixel_scale();
+        let px = size * pixel_scale;
+        let glyphs = self.layout(text, px);
+        let (atlas_glyphs, texture) = self.rasterize(&glyphs, px);
+
+        // start on a pixel, so that texels land on pixels
+        let origin_x = (x * pixel_scale).round();
+        let origin_y = (y * pixel_scale).round();
+        camera::on_screen(|| {
+            for (glyph, atlas_glyph) in glyphs.iter().zip(atlas_glyphs) {
+                let Some(AtlasGlyph { source, offset }) = atlas_glyph else {
+                    continue;
+                };
+                let sprite = Sprite {
+                    width: source.width,
+                    height: source.height,
+                    color,
+                    x: ((origin_x + glyph.x).round() + offset.0) / pixel_scale,
+                    y: ((origin_y + glyph.y).round() + offset.1) / pixel_scale,
+                    texture: texture.id(),
+                    tex_coords: texture.tex_coords(source),
+                    rotation: 0.0,
+                    // glyphs are rasterized in window pixels
+                    scale: [1.0 / pixel_scale, 1.0 / pixel_scale],
+                    origin: [0.0, 0.0],
+                };
+                layer::draw_sprite(layer, &sprite, Some(&texture));
+            }
+        });
+    }
+
+    /// Size of `text` drawn `size` logical pixels high: the width of its widest line, and
+    /// the height of a line above and below the baseline
+    pub fn metrics(&self, text: &str, size: f32) -> TextMetrics {
+        let font = self.data.font.as_scaled(PxScale::from(size));
+        let width = self
+            .layout(text, size)
+            .iter()
+            .map(|glyph| glyph.x + font.h_advance(glyph.id))
+            .fold(0.0, f32::max);
+        TextMetrics {
+            width,
+            ascent: font.ascent(),
+            descent: font.line_gap() - font.descent(),
+        }
+    }
+
+    /// Place the glyphs of `text` at `px` pixels high, with kerning, a line per `\n`
+    fn layout(&self, text: &str, px: f32) -> Vec<PlacedGlyph> {
+        let font = self.data.font.as_scaled(PxScale::from(px));
+        let line_height = font.height() + font.line_gap();
+
+        let mut glyphs = Vec::new();
+        let (mut x, mut y) = (0.0, 0.0);
+        let mut previous = None;
+        for c in text.chars() {
+            if c == '\n' {
+                (x, y) = (0.0, y + line_height);
+                previous = None;
+                continue;
+            }
+            if c.is_control() {
+                continue;
+            }
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                x += font.kern(previous, id);
+            }
+            glyphs.push(PlacedGlyph { id, x, y });
+            x += font.h_advance(id);
+            previous = Some(id);
+        }
+        glyphs
+    }
+
+    /// Make sure the glyphs are in the atlas, returning where and its texture. Glyphs that
+    /// don't fit in the largest atlas are skipped.
+    fn rasterize(&self, glyphs: &[PlacedGlyph], px: f32) -> (Vec<Option<AtlasGlyph>>, Texture) {
+        let atlas = &mut *self.data.atlas.borrow_mut();
+        let size_key = (px * 4.0).round() as u32;
+        let max_size = with_renderer(|renderer| renderer.max_texture_size());
+        let mut emptied = false;
+        loop {
+            // an emptied atlas that can't grow is all there is: glyphs that still don't fit
+            // are remembered as having nothing to draw, not to start over at every draw
+            let last_try = emptied && atlas.size >= max_size;
+            let placed: Option<Vec<Option<AtlasGlyph>>> = glyphs
+                .iter()
+                .map(
+                    |glyph| match self.atlas_glyph(atlas, glyph.id, px, size_key) {
+                        None if last_try => {
+                            atlas.glyphs.insert((glyph.id, size_key), None);
+                            Some(None)
+                        }
+                        placed => placed,
+                    },
+                )
+                .collect();
+            match placed {
+                Some(placed) => return (placed, atlas.texture()),
+                // start over in a larger atlas, draws already queued keep the old texture
+                None => {
+                    *atlas = Atlas::new((atlas.size * 2).min(max_size));
+                    emptied = true;
+                }
+            }
+        }
+    }
+
+    /// Find a glyph in the atlas or rasterize it there. Returns `None` when the atlas is
+    /// full, and `Some(None)` for glyphs with nothing to draw, like spaces.
+    fn atlas_glyph(
+        &self,
+        atlas: &mut Atlas,
+        id: GlyphId,
+        px: f32,
+        size_key: u32,
+    ) -> Option<Option<AtlasGlyph>> {
+        if let Some(&glyph) = atlas.glyphs.get(&(id, size_key)) {
+            return Some(glyph);
+        }
+
+        let glyph = id.with_scale(PxScale::from(px));
+        let Some(outline) = self.data.font.outline_glyph(glyph) else {
+            atlas.glyphs.insert((id, size_key), None);
+            return Some(None);
+        };
+        let bounds = outline.px_bounds();
+        let (width, height) = (bounds.width() as i32, bounds.height() as i32);
+        let (left, top) = atlas.allocate(width, height)?;
+
+        let size = atlas.size;
+        let pixels = &mut atlas.pixels;
+        outline.draw(|x, y, coverage| {
+            let alpha = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+            pixels[((top + y as i32) * size + left + x as i32) as usize] = [255, 255, 255, alpha];
+        });
+        atlas.texture = None;
+
+        let atlas_glyph = Some(AtlasGlyph {
+            source: SourceRect::new(left, top, width, height),
+            offset: (bounds.min.x, bounds.min.y),
+        });
+        atlas.glyphs.insert((id, size_key), atlas_glyph);
+        Some(atlas_glyph)
+    }
+}
+
+impl Font {
+    /// Release the texture of the atlas, uploaded again at the next draw
+    fn release_texture(&self) {
+        self.data.atlas.borrow_mut().texture = None;
+    }
+}
+
+impl std::fmt::Debug for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Font")
+            .field("glyphs", &self.data.font.glyph_count())
+            .finish()
+    }
+}
+
+/// Window pixels per logical pixel of the current window, glyphs are rasterized at the
+/// size they are shown at
+fn pixel_scale() -> f32 {
+    let (_, logical_height) = window::logical_size();
+    let viewport = window::viewport();
+    match viewport.height > 0 && logical_height > 0 {
+        true => viewport.height as f32 / logical_height as f32,
+        false => 1.0,
+    }
+}
+
+thread_local! {
+    static BUILT_IN: Font =
+        Font::from_bytes(BUILT_IN_FONT.to_vec()).expect("the built-in font is invalid");
+    static DEFAULT_FONT: RefCell<Option<Font>> = const { RefCell::new(None) };
+}
+
+/// The font built into the engine, DejaVu Sans
+pub fn built_in() -> Font {
+    BUILT_IN.with(Font::clone)
+}
+
+/// Use `font` for `TEXT_RENDER!` and the frame statistics, or the built-in font again with
+/// `None`
+pub fn set_default(font: Option<Font>) {
+    DEFAULT_FONT.with(|default| *default.borrow_mut() = font);
+}
+
+/// The font used by `TEXT_RENDER!`
+pub fn default_font() -> Font {
+    DEFAULT_FONT
+        .with(|default| default.borrow().clone())
+        .unwrap_or_else(built_in)
+}
+
+/// Release the atlas textures of the built-in and default fonts, before the renderer that
+/// created them is replaced
+pub(crate) fn release_textures() {
+    BUILT_IN.with(Font::release_texture);
+    DEFAULT_FONT.with(|default| default.borrow().as_ref().map(Font::release_texture));
+}
+
+/// Load a font from a TTF or OTF file, returning an `io::Result<Font>`
+#[macro_export]
+macro_rules! LOAD_FONT {
+    ($path:expr) => {
+        $crate::font::Font::load($path)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{self, Backend};
+
+    fn test_font() -> Font {
+        Font::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/fonts/DejaVuSans.ttf"
+        ))
+        .expect("the font file is missing")
+    }
+
+    #[test]
+    fn atlas_packs_glyphs_in_rows() {
+        let mut atlas = Atlas::new(10);
+        assert_eq!(atlas.allocate(4, 3), Some((0, 0)));
+        assert_eq!(atlas.allocate(4, 5), Some((5, 0)));
+        // the next glyph goes under the highest one of the row
+        assert_eq!(atlas.allocate(4, 2), Some((0, 6)));
+        assert_eq!(atlas.allocate(4, 4), None);
+    }
+
+    #[test]
+    fn lines_and_kerning() {
+        let font = test_font();
+        assert!(font.has_glyph('é') && font.has_glyph('λ'));
+        assert!(!font.has_glyph('\u{E000}'));
+
+        let glyphs = font.layout("AV\nA", 20.0);
+        assert_eq!(glyphs.len(), 3);
+        // kerning pulls the V closer to the A than its advance
+        let advance = font
+            .data
+            .font
+            .as_scaled(PxScale::from(20.0))
+            .h_advance(glyphs[0].id);
+        assert!(glyphs[1].x < advance);
+        assert_eq!((glyphs[2].x, glyphs[0].y), (0.0, 0.0));
+        assert!(glyphs[2].y >= 20.0);
+    }
+
+    #[test]
+    fn measures_the_widest_line() {
+        let font = test_font();
+        let one_line = font.metrics("AV", 20.0);
+        let two_lines = font.metrics("A\nAV", 20.0);
+        assert_eq!(two_lines, one_line);
+        assert!(one_line.width > 20.0 && one_line.width < 30.0);
+        assert!(one_line.line_height() >= 20.0);
+        assert_eq!(font.metrics("", 20.0).width, 0.0);
+    }
+
+    #[test]
+    fn glyphs_are_rasterized_once() {
+        renderer::select_backend(Backend::Headless);
+        let font = test_font();
+        let glyphs = font.layout("a a", 20.0);
+        let (first, texture) = font.rasterize(&glyphs, 20.0);
+        // the space has nothing to draw, the second `a` reuses the first one
+        assert!(first[0].is_some() && first[1].is_none());
+        assert_eq!(
+            first[0].map(|glyph| glyph.source),
+            first[2].map(|glyph| glyph.source)
+        );
+        let (again, same_texture) = font.rasterize(&glyphs, 20.0);
+        assert_eq!(
+            again[0].map(|glyph| glyph.source),
+            first[0].map(|glyph| glyph.source)
+        );
+        assert_eq!(same_texture.id(), texture.id());
+
+        // a glyph larger than the atlas makes it grow
+        let (large, larger_texture) = font.rasterize(&glyphs, 600.0);
+        assert!(large[0].is_some());
+        assert_eq!(larger_texture.width(), ATLAS_SIZE * 2);
+    }
+
+    #[test]
+    fn glyphs_too_large_for_any_atlas_are_skipped() {
+        renderer::select_backend(Backend::Headless);
+        let max_size = renderer::with_renderer(|renderer| renderer.max_texture_size());
+        let font = test_font();
+        let glyphs = font.layout("a.", 3000.0);
+        let (placed, texture) = font.rasterize(&glyphs, 3000.0);
+        // the `a` is taller than the largest atlas, the dot still fits
+        assert!(placed[0].is_none() && placed[1].is_some());
+        assert_eq!(texture.width(), max_size);
+
+        // the atlas isn't rebuilt at every draw
+        let (again, same_texture) = font.rasterize(&glyphs, 3000.0);
+        assert!(again[0].is_none() && again[1].is_some());
+        assert_eq!(same_texture.id(), texture.id());
+    }
+    #[test]
+    fn default_fonts_are_uploaded_again_by_new_renderers() {
+        renderer::select_backend(Backend::Headless);
+        assert!(Rc::ptr_eq(&default_font().data, &built_in().data));
+        set_default(Some(test_font()));
+        for font in [built_in(), default_font()] {
+            let glyphs = font.layout("a", 20.0);
+            font.rasterize(&glyphs, 20.0);
+        }
+
+        // the atlas textures don't keep the previous renderer
+        renderer::select_backend(Backend::Headless);
+        let font = default_font();
+        let glyphs = font.layout("a", 20.0);
+        let (placed, _) = font.rasterize(&glyphs, 20.0);
+        assert!(placed[0].is_some());
+
+        set_default(None);
+        assert!(Rc::ptr_eq(&default_font().data, &built_in().data));
+    }
+}
@@ -0,0 +1,286 @@
+//! framebuffer.rs
+//!
+//! An in-memory RGB image, drawn into by the headless backend and returned by
+//! `Renderer::capture_frame`.
+
+/// An RGB image the size of the game window. The origin is the top-left corner, as
+/// set up by the `glOrtho` projection of the OpenGL backend.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    width: i32,
+    height: i32,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Framebuffer {
+    /// Create a black framebuffer
+    pub fn new(width: i32, height: i32) -> Framebuffer {
+        let width = width.max(0);
+        let height = height.max(0);
+
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![[0, 0, 0]; (width * height) as usize],
+        }
+    }
+
+    /// Create a framebuffer from packed RGB bytes, row by row starting from the top-left corner
+    pub fn from_rgb(width: i32, height: i32, rgb: &[u8]) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(width, height);
+        assert_eq!(
+            rgb.len(),
+            framebuffer.pixels.len() * 3,
+            "expected {width}x{height} RGB pixels"
+        );
+
+        for (pixel, rgb) in framebuffer.pixels.iter_mut().zip(rgb.chunks_exact(3)) {
+            *pixel = [rgb[0], rgb[1], rgb[2]];
+        }
+        framebuffer
+    }
+
+    /// Packed RGB bytes, row by row starting from the top-left corner
+    pub fn to_rgb(&self) -> Vec<u8> {
+        self.pixels.iter().flatten().copied().collect()
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// All pixels, row by row starting from the top-left corner
+    pub fn pixels(&self) -> &[[u8; 3]] {
+        &self.pixels
+    }
+
+    /// Color of a pixel, `None` when outside of the framebuffer
+    pub fn pixel(&self, x: i32, y: i32) -> Option<[u8; 3]> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.pixels[(y * self.width + x) as usize])
+    }
+
+    /// Fill the whole framebuffer with black
+    pub fn clear(&mut self) {
+        self.pixels.fill([0, 0, 0]);
+    }
+
+    /// Fill a rectangle of pixels. As with OpenGL, a pixel is covered when its center
+    /// lies inside the rectangle. Parts outside of the framebuffer are clipped.
+    pub fn fill_rect(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, color: [u8; 3]) {
+        let [r, g, b] = color;
+        let clip = (0.0, 0.0, self.width as f32, self.height as f32);
+        self.shade_rect((x1, y1, x2, y2), clip, |_, _| Some([r, g, b, 255]));
+    }
+
+    /// Draw a rectangle pixel by pixel, e.g. to map a texture on it. `shade` gets the
+    /// position of each covered pixel center in the rectangle, from `(0, 0)` at its
+    /// top-left corner to `(1, 1)` at its bottom-right corner, and returns the color of
+    /// the RGBA color of the pixel or `None` to leave it untouched. Translucent colors are
+    /// blended with the pixel, as OpenGL does with `GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA`.
+    /// Only the pixels inside the `clip` rectangle and the framebuffer are drawn.
+    pub fn shade_rect(
+        &mut self,
+        rect: (f32, f32, f32, f32),
+        clip: (f32, f32, f32, f32),
+        mut shade: impl FnMut(f32, f32) -> Option<[u8; 4]>,
+    ) {
+        let (x1, y1, x2, y2) = rect;
+        let (clip_x1, clip_y1, clip_x2, clip_y2) = clip;
+        let first_pixel = |a: f32, clip: f32| ((a.max(clip) - 0.5).ceil() as i32).max(0);
+        let last_pixel =
+            |b: f32, clip: f32, size: i32| ((b.min(clip) - 0.5).ceil() as i32).min(size);
+
+        let first_x = first_pixel(x1, clip_x1);
+        let last_x = last_pixel(x2, clip_x2, self.width);
+        let first_y = first_pixel(y1, clip_y1);
+        let last_y = last_pixel(y2, clip_y2, self.height);
+
+        for y in first_y..last_y {
+            let row = (y * self.width) as usize;
+            let v = (y as f32 + 0.5 - y1) / (y2 - y1);
+            for x in first_x..last_x {
+                let u = (x as f32 + 0.5 - x1) / (x2 - x1);
+                if let Some(color) = shade(u, v) {
+                    let pixel = &mut self.pixels[row + x as usize];
+                    *pixel = blend(*pixel, color);
+                }
+            }
+        }
+    }
+
+    /// Fill a convex polygon with an RGBA color, clockwise or not. As with `fill_rect`, a
+    /// pixel is covered when its center lies inside the polygon.
+    pub fn fill_polygon(
+        &mut self,
+        points: &[(f32, f32)],
+        clip: (f32, f32, f32, f32),
+        color: [u8; 4],
+    ) {
+        if points.len() < 3 {
+            return;
+        }
+        // which side of the edges is inside depends on the winding
+        let area: f32 = edges(points)
+            .map(|((x1, y1), (x2, y2))| x1 * y2 - x2 * y1)
+            .sum();
+        if area == 0.0 {
+            return;
+        }
+
+        let min_x = points.iter().map(|p| p.0).fold(f32::INFINITY, f32::min);
+        let max_x = points.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+        let min_y = points.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+        let max_y = points.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+        let (clip_x1, clip_y1, clip_x2, clip_y2) = clip;
+        let first_x = ((min_x.max(clip_x1) - 0.5).ceil() as i32).max(0);
+        let last_x = ((max_x.min(clip_x2) - 0.5).ceil() as i32).min(self.width);
+        let first_y = ((min_y.max(clip_y1) - 0.5).ceil() as i32).max(0);
+        let last_y = ((max_y.min(clip_y2) - 0.5).ceil() as i32).min(self.height);
+
+        for y in first_y..last_y {
+            let row = (y * self.width) as usize;
+            // centers are nudged so that a center lying on an edge is only covered on one
+            // side of it, like `fill_rect` covers centers from its left edge up to its right
+            let center_y = y as f32 + 0.5 + EDGE_NUDGE;
+            for x in first_x..last_x {
+                let center_x = x as f32 + 0.5 + EDGE_NUDGE;
+                let inside = edges(points).all(|((x1, y1), (x2, y2))| {
+                    let side = (x2 - x1) * (center_y - y1) - (y2 - y1) * (center_x - x1);
+                    side * area > 0.0
+                });
+                if inside {
+                    let pixel = &mut self.pixels[row + x as usize];
+                    *pixel = blend(*pixel, color);
+                }
+            }
+        }
+    }
+
+    /// Draw a parallelogram pixel by pixel, like `shade_rect` does for rectangles, e.g. a
+    /// rotated sprite. `corners` are where the top-left, top-right and bottom-left corners
+    /// of the rectangle ended up, and `shade` gets positions in the rectangle as before.
+    pub fn shade_quad(
+        &mut self,
+        corners: [(f32, f32); 3],
+        clip: (f32, f32, f32, f32),
+        mut shade: impl FnMut(f32, f32) -> Option<[u8; 4]>,
+    ) {
+        let [(x0, y0), (x1, y1), (x3, y3)] = corners;
+        let (ux, uy) = (x1 - x0, y1 - y0);
+        let (vx, vy) = (x3 - x0, y3 - y0);
+        let determinant = ux * vy - uy * vx;
+        if determinant == 0.0 {
+            return;
+        }
+
+        // bounding box of the four corners, clipped
+        let xs = [x0, x1, x3, x1 + vx];
+        let ys = [y0, y1, y3, y1 + vy];
+        let min = |values: [f32; 4]| values.into_iter().fold(f32::INFINITY, f32::min);
+        let max = |values: [f32; 4]| values.into_iter().fold(f32::NEG_INFINITY, f32::max);
+        let (clip_x1, clip_y1, clip_x2, clip_y2) = clip;
+        let first_x = ((min(xs).max(clip_x1) - 0.5).ceil() as i32).max(0);
+        let last_x = ((max(xs).min(clip_x2) - 0.5).ceil() as i32).min(self.width);
+        let first_y = ((min(ys).max(clip_y1) - 0.5).ceil() as i32).max(0);
+        let last_y = ((max(ys).min(clip_y2) - 0.5).ceil() as i32).min(self.height);
+
+        for y in first_y..last_y {
+            let row = (y * self.width) as usize;
+            let dy = y as f32 + 0.5 - y0;
+            for x in first_x..last_x {
+                let dx = x as f32 + 0.5 - x0;
+                // position of the pixel center in the rectangle
+                let u = (dx * vy - dy * vx) / determinant;
+                let v = (ux * dy - uy * dx) / determinant;
+                if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                    continue;
+                }
+                if let Some(color) = shade(u, v) {
+                    let pixel = &mut self.pixels[row + x as usize];
+                    *pixel = blend(*pixel, color);
+                }
+            }
+        }
+    }
+}
+
+/// Offset of pixel centers tested against polygon edges
+const EDGE_NUDGE: f32 = 1.0 / 1024.0;
+
+/// Edges of a polygon, the last one going back to the first point
+fn edges(points: &[(f32, f32)]) -> impl Iterator<Item = ((f32, f32), (f32, f32))> + '_ {
+    points
+        .iter()
+        .copied()
+        .zip(points.iter().copied().cycle().skip(1))
+}
+
+/// Draw `color` over `pixel`, weighted by its alpha
+fn blend(pixel: [u8; 3], color: [u8; 4]) -> [u8; 3] {
+    let alpha = color[3] as u32;
+    let mix =
+        |dst: u8, src: u8| ((src as u32 * alpha + dst as u32 * (255 - alpha) + 127) / 255) as u8;
+    [
+        mix(pixel[0], color[0]),
+        mix(pixel[1], color[1]),
+        mix(pixel[2], color[2]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translucent_colors_are_blended() {
+        let mut frame = Framebuffer::new(2, 1);
+        frame.fill_rect(0.0, 0.0, 2.0, 1.0, [0, 0, 200]);
+        frame.shade_rect((0.0, 0.0, 2.0, 1.0), (0.0, 0.0, 2.0, 1.0), |u, _| {
+            (u < 0.5).then_some([255, 0, 0, 128])
+        });
+
+        assert_eq!(frame.pixel(0, 0), Some([128, 0, 100]));
+        assert_eq!(frame.pixel(1, 0), Some([0, 0, 200]));
+    }
+
+    #[test]
+    fn polygons_cover_pixel_centers() {
+        let mut frame = Framebuffer::new(4, 4);
+        let clip = (0.0, 0.0, 4.0, 4.0);
+        // a triangle with its right angle at the top-left corner, counter-clockwise
+        frame.fill_polygon(&[(0.0, 0.0), (0.0, 4.0), (4.0, 0.0)], clip, [9, 9, 9, 255]);
+
+        assert_eq!(frame.pixel(0, 0), Some([9, 9, 9]));
+        assert_eq!(frame.pixel(2, 0), Some([9, 9, 9]));
+        assert_eq!(frame.pixel(2, 2), Some([0, 0, 0]));
+        assert_eq!(frame.pixel(0, 2), Some([9, 9, 9]));
+        // on the edge
+        assert_eq!(frame.pixel(1, 2), Some([0, 0, 0]));
+        assert_eq!(frame.pixel(3, 3), Some([0, 0, 0]));
+    }
+
+    #[test]
+    fn quads_are_rotated() {
+        // a 4x2 rectangle standing up: its top edge goes down the right side
+        let mut frame = Framebuffer::new(4, 4);
+        let clip = (0.0, 0.0, 4.0, 4.0);
+        frame.shade_quad([(3.0, 0.0), (3.0, 4.0), (1.0, 0.0)], clip, |u, _| {
+            Some(match u < 0.5 {
+                true => [255, 0, 0, 255],
+                false => [0, 255, 0, 255],
+            })
+        });
+
+        assert_eq!(frame.pixel(0, 0), Some([0, 0, 0]));
+        assert_eq!(frame.pixel(2, 0), Some([255, 0, 0]));
+        assert_eq!(frame.pixel(1, 3), Some([0, 255, 0]));
+        assert_eq!(frame.pixel(3, 3), Some([0, 0, 0]));
+    }
+}
@@ -4,24 +4,28 @@
 //! 
 
 use std::ffi::c_void;
-use std::os::raw::{c_char, c_int};
+#[cfg(feature = "opengl")]
+use std::os::raw::c_char;
+use std::os::raw::{c_int, c_uint};
 
+pub const GLFW_RELEASE: c_int = 0;
 pub const GLFW_PRESS: c_int = 1;
-pub const GLFW_KEY_SPACE: c_int = 32;
-
-pub const GLFW_KEY_RIGHT: c_int = 262;
-pub const GLFW_KEY_LEFT: c_int = 263;
-pub const GLFW_KEY_DOWN: c_int = 264;
-pub const GLFW_KEY_UP: c_int = 265;
+pub const GLFW_REPEAT: c_int = 2;
 
 /// The representation of a Sprite in the C library
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct Sprite {
     pub width: i32,
     pub height: i32,
-    pub color: [c_int; 3], // RGB color
+    pub color: [c_int; 4], // RGBA color, tinting textured sprites. Alpha 255 is opaque
     pub x: f32,
-    pub y: f32, // Position
+    pub y: f32,               // Position
+    pub texture: c_uint,      // Texture from `create_texture`, 0 for a solid color sprite
+    pub tex_coords: [f32; 4], // Part of the texture drawn: left, top, right and bottom from 0 to 1
+    pub rotation: f32,        // Rotation around the origin, in degrees clockwise
+    pub scale: [f32; 2],      // Horizontal and vertical scale around the origin
+    pub origin: [f32; 2],     // Pivot of rotation and scale, from 0 to 1 across the sprite
 }
 
 pub struct Window {
@@ -30,9 +34,29 @@ pub struct Window {
     pub sprite_side: i32,
 }
 
+/// Callback receiving the GLFW key events: window, key, scancode, action and modifiers
+pub type KeyEventCallback = extern "C" fn(*mut c_void, c_int, c_int, c_int, c_int);
+
+/// Callback receiving the GLFW mouse button events: window, button, action, modifiers
+/// and the cursor position
+pub type MouseButtonEventCallback = extern "C" fn(*mut c_void, c_int, c_int, c_int, f64, f64);
+
+/// Callback receiving the GLFW scroll offsets of a window
+pub type ScrollEventCallback = extern "C" fn(*mut c_void, f64, f64);
+
+/// Callback receiving the new size of a window in pixels
+pub type ResizeEventCallback = extern "C" fn(*mut c_void, c_int, c_int);
+
+#[cfg(feature = "opengl")]
 extern "C" {
-    /// Function to create a game window
-    pub fn create_game_window(title: *const c_char, width: i32, height: i32);
+    /// Function to create a game window, which becomes the target of the next draw calls
+    pub fn create_game_window(title: *const c_char, width: i32, height: i32) -> *mut c_void;
+
+    /// Function to close a game window
+    pub fn destroy_game_window(window: *mut c_void);
+
+    /// Function to make a window the target of the next draw calls
+    pub fn make_window_current(window: *mut c_void);
 
     /// Function to create a sprite
     pub fn create_sprite(
@@ -45,28 +69,97 @@ extern "C" {
         b: i32,
     ) -> *mut Sprite;
 
-    /// Function to render a sprite
-    pub fn render_sprite(sprite: *mut Sprite);
+    /// Function to free a sprite returned by `create_sprite`
+    pub fn destroy_sprite(sprite: *mut Sprite);
+
+    /// Function to render a sprite. Sprites and polygons are batched, and drawn together
+    /// when the window is updated or read back
+    pub fn render_sprite(window: *mut c_void, sprite: *mut Sprite);
+
+    /// Function to fill a convex polygon given as `count` x, y pairs with an RGBA color
+    pub fn render_polygon(
+        window: *mut c_void,
+        points: *const f32,
+        count: c_int,
+        r: c_int,
+        g: c_int,
+        b: c_int,
+        a: c_int,
+    );
 
-    /// Function to update a sprite position
-    pub fn update_sprite_position(sprite: *mut Sprite, x: f32, y: f32);
+    /// Function to create a texture from RGBA pixels, top row first. Textures are shared by
+    /// all game windows
+    pub fn create_texture(pixels: *const u8, width: i32, height: i32) -> c_uint;
 
-    /// Function to update the game window
-    pub fn update_game_window();
+    /// Function to destroy a texture created with `create_texture`
+    pub fn destroy_texture(texture: c_uint);
+
+    /// Function to get the largest width and height of a texture, a window must be created
+    /// first
+    pub fn get_max_texture_size() -> c_int;
+
+    /// Function to update the game window. Events of every window are processed
+    pub fn update_game_window(window: *mut c_void);
 
     /// Function to clear the screen
-    pub fn clear_screen();
+    pub fn clear_screen(window: *mut c_void);
 
     /// Function to check if the window should close
-    pub fn window_should_close() -> i32;
+    pub fn window_should_close(window: *mut c_void) -> i32;
 
     /// Function to get key state
     pub fn get_key(window: *mut c_void, key: i32) -> i32;
 
-    /// Function to get the window pointer
-    pub fn get_window() -> *mut c_void;
+    /// Function to receive the key events of a window, delivered while windows are updated
+    pub fn set_key_callback(window: *mut c_void, callback: Option<KeyEventCallback>);
+
+    /// Function to receive the mouse button and scroll events of a window, delivered while
+    /// windows are updated
+    pub fn set_mouse_callbacks(
+        window: *mut c_void,
+        button_callback: Option<MouseButtonEventCallback>,
+        wheel_callback: Option<ScrollEventCallback>,
+    );
+
+    /// Function to get the cursor position in window coordinates
+    pub fn get_cursor_position(window: *mut c_void, x: *mut f64, y: *mut f64);
+
+    /// Function to get mouse button state
+    pub fn get_mouse_button(window: *mut c_void, button: i32) -> i32;
+
+    /// Function to receive the resizes of a window, delivered while windows are updated
+    pub fn set_resize_callback(window: *mut c_void, callback: Option<ResizeEventCallback>);
+
+    /// Function to draw a logical_width x logical_height area into a part of the window
+    pub fn set_viewport(
+        window: *mut c_void,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        logical_width: i32,
+        logical_height: i32,
+    );
+
+    /// Function to get the time in seconds since the first window was created
+    pub fn get_time() -> f64;
+
+    /// Function to get the size of the window in pixels
+    pub fn get_window_size(window: *mut c_void, width: *mut i32, height: *mut i32);
+
+    /// Function to read back the RGB pixels drawn so far, bottom row first
+    pub fn read_pixels(window: *mut c_void, pixels: *mut u8, width: i32, height: i32);
 
     /// Function to render text on the window
-    pub fn renderText(text: *const c_char, x: f32, y: f32, scale: f32, r: f32, g: f32, b: f32);
+    pub fn renderText(
+        window: *mut c_void,
+        text: *const c_char,
+        x: f32,
+        y: f32,
+        scale: f32,
+        r: f32,
+        g: f32,
+        b: f32,
+    );
 
 }
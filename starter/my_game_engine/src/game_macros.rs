@@ -2,17 +2,85 @@
 //! 
 //! The main macros for game management
 
-/// Create a sprite and rended it on the screen
+use std::borrow::Cow;
+use std::ffi::{c_char, CStr, CString};
+
+/// Text taken by `CREATE_GAME!` and `TEXT_RENDER!`: string slices and strings, or C strings
+/// such as the ones made with `C_STRING!`
+pub trait MacroText {
+    /// The text as UTF-8, invalid sequences replaced
+    ///
+    /// # Safety
+    /// A raw C string must point to a NUL-terminated string
+    unsafe fn to_text(&self) -> Cow<'_, str>;
+}
+
+impl MacroText for str {
+    unsafe fn to_text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl MacroText for String {
+    unsafe fn to_text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl MacroText for CStr {
+    unsafe fn to_text(&self) -> Cow<'_, str> {
+        self.to_string_lossy()
+    }
+}
+
+impl MacroText for CString {
+    unsafe fn to_text(&self) -> Cow<'_, str> {
+        self.to_string_lossy()
+    }
+}
+
+impl MacroText for *const c_char {
+    unsafe fn to_text(&self) -> Cow<'_, str> {
+        CStr::from_ptr(*self).to_string_lossy()
+    }
+}
+
+impl MacroText for *mut c_char {
+    unsafe fn to_text(&self) -> Cow<'_, str> {
+        CStr::from_ptr(*self).to_string_lossy()
+    }
+}
+
+impl<T: MacroText + ?Sized> MacroText for &T {
+    unsafe fn to_text(&self) -> Cow<'_, str> {
+        (**self).to_text()
+    }
+}
+
+/// Create a sprite and rended it on the screen. Returns an owned `SpriteHandle`,
+/// the sprite is freed when the handle is dropped.
 #[macro_export]
 macro_rules! SPAWN_SPRITE {
     ($render:literal, $($x:expr),*) => {
         {
-            let sprite: *mut game_ffi::Sprite;
-            unsafe {
-                sprite = game_ffi::create_sprite( $($x),* );
-                if $render {
-                    game_ffi::render_sprite(sprite);
-                }
+            let sprite = $crate::sprite::SpriteHandle::new( $($x),* );
+            if $render {
+                sprite.render();
+            }
+            sprite
+        }
+    };
+}
+
+/// Create a sprite drawing a whole `Texture` and possibly render it. Returns an owned
+/// `SpriteHandle`, which keeps the texture alive.
+#[macro_export]
+macro_rules! SPAWN_TEXTURED_SPRITE {
+    ($render:literal, $x:expr, $y:expr, $width:expr, $height:expr, $texture:expr) => {
+        {
+            let sprite = $crate::sprite::SpriteHandle::textured($x, $y, $width, $height, &$texture);
+            if $render {
+                sprite.render();
             }
             sprite
         }
@@ -23,16 +91,7 @@ macro_rules! SPAWN_SPRITE {
 #[macro_export]
 macro_rules! DUPE_SPRITE {
     ($sprite:expr, $x:expr, $y:expr) => {{
-        SPAWN_SPRITE!(
-            false,
-            $x,
-            $y,
-            (*$sprite).width,
-            (*$sprite).height,
-            (*$sprite).color[0],
-            (*$sprite).color[1],
-            (*$sprite).color[2]
-        )
+        $sprite.duplicate_at($x, $y)
     }};
 }
 /// move a sprite to a new position, potentially clearing screen first
@@ -41,15 +100,14 @@ macro_rules! DUPE_SPRITE {
 #[macro_export]
 macro_rules! MOVE_SPRITE {
     ($clear_before:literal, $render_after:literal, $sprite:expr, $new_x:expr, $new_y:expr) => {{
-        unsafe {
-            if $clear_before {
-                game_ffi::clear_screen();
-            }
-            game_ffi::update_sprite_position($sprite, $new_x, $new_y);
+        let (new_x, new_y) = ($new_x, $new_y);
+        if $clear_before {
+            $crate::CLEAR_SCREEN!();
+        }
+        $sprite.set_position(new_x, new_y);
 
-            if $render_after {
-                game_ffi::render_sprite($sprite);
-            }
+        if $render_after {
+            $sprite.render();
         }
     }};
 }
@@ -58,11 +116,15 @@ macro_rules! MOVE_SPRITE {
 #[macro_export]
 macro_rules! TICK {
     ($sleepms:expr) => {{
-        // Update the game window
-        unsafe {
-            game_ffi::update_game_window();
-        }
-        thread::sleep($sleepms);
+        $crate::stats::render_overlay();
+        $crate::layer::flush();
+        $crate::stats::enter($crate::stats::Phase::Idle);
+
+        // Update the game window, then hand the events it received to the next frame
+        $crate::window::update();
+        $crate::input::start_frame();
+        std::thread::sleep($sleepms);
+        $crate::stats::end_frame();
     }};
 }
 
@@ -70,12 +132,66 @@ macro_rules! TICK {
 #[macro_export]
 macro_rules! ON_KEY_PRESS {
     ($key:expr, $block:block) => {{
-        let key_state: i32;
-        unsafe {
-            key_state = game_ffi::get_key(game_ffi::get_window(), $key);
+        let key = $key;
+        let pressed =
+            $crate::renderer::with_renderer(|renderer| renderer.key_state(key).is_down());
+
+        if pressed {
+            $block
+        }
+    }};
+}
+
+/// Run the provided code when a keyboard key went down during the current frame.
+/// Unlike `ON_KEY_PRESS!`, a held key only triggers once.
+#[macro_export]
+macro_rules! ON_KEY_DOWN {
+    ($key:expr, $block:block) => {{
+        if $crate::input::key_went_down($key) {
+            $block
+        }
+    }};
+}
+
+/// Run the provided code when a keyboard key was released during the current frame
+#[macro_export]
+macro_rules! ON_KEY_UP {
+    ($key:expr, $block:block) => {{
+        if $crate::input::key_went_up($key) {
+            $block
         }
+    }};
+}
+
+/// Run the provided code when a mouse button went down during the current frame, with
+/// the cursor position of the click bound to `x`, `y`
+#[macro_export]
+macro_rules! ON_MOUSE_DOWN {
+    ($button:expr, |$x:ident, $y:ident| $block:block) => {{
+        if let Some(($x, $y)) = $crate::input::mouse_button_went_down($button) {
+            $block
+        }
+    }};
+}
+
+/// Run the provided code when a mouse button was released during the current frame, with
+/// the cursor position of the release bound to `x`, `y`
+#[macro_export]
+macro_rules! ON_MOUSE_UP {
+    ($button:expr, |$x:ident, $y:ident| $block:block) => {{
+        if let Some(($x, $y)) = $crate::input::mouse_button_went_up($button) {
+            $block
+        }
+    }};
+}
 
-        if key_state == game_ffi::GLFW_PRESS {
+/// Run the provided code when the mouse wheel scrolled during the current frame, with
+/// the total scroll offsets bound to `dx`, `dy`
+#[macro_export]
+macro_rules! ON_SCROLL {
+    (|$dx:ident, $dy:ident| $block:block) => {{
+        let ($dx, $dy) = $crate::input::scroll_delta();
+        if $dx != 0.0 || $dy != 0.0 {
             $block
         }
     }};
@@ -85,12 +201,12 @@ macro_rules! ON_KEY_PRESS {
 #[macro_export]
 macro_rules! CHANGE_SPRITE_COLOR {
     ($sprite:expr, $r:literal, $g:literal, $b:literal) => {{
-        let sprite = SPAWN_SPRITE!(
+        let sprite = $crate::SPAWN_SPRITE!(
             true,
-            (*$sprite).x,
-            (*$sprite).y,
-            (*$sprite).width,
-            (*$sprite).height,
+            $sprite.x(),
+            $sprite.y(),
+            $sprite.width(),
+            $sprite.height(),
             $r,
             $g,
             $b
@@ -106,16 +222,17 @@ macro_rules! START_WINDOW_AND_GAME_LOOP {
     ($sleepms:expr, $loop_block:block) => {
         {
             loop {
-                let should_close: i32;
-                unsafe { should_close = game_ffi::window_should_close(); }
+                let should_close =
+                    $crate::renderer::with_renderer(|renderer| renderer.window_should_close());
 
-                if should_close == 1 {
+                if should_close {
                     break;
                 }
 
+                $crate::stats::enter($crate::stats::Phase::Update);
                 $loop_block
 
-                TICK!($sleepms);
+                $crate::TICK!($sleepms);
             }
         }
 
@@ -132,19 +249,11 @@ macro_rules! C_STRING {
     }};
 }
 
-/// Get a Sprite attribute. 
-#[macro_export]
-macro_rules! SPRITE_ATTR {
-    ($sprite:expr, $attr:ident) => {{
-        unsafe { (*$sprite).$attr }
-    }};
-}
-
 /// Get the `x` attribute of the Sprite
 #[macro_export]
 macro_rules! SPRITE_X {
     ($sprite:expr ) => {{
-        SPRITE_ATTR!($sprite, x)
+        $sprite.x()
     }};
 }
 
@@ -152,7 +261,7 @@ macro_rules! SPRITE_X {
 #[macro_export]
 macro_rules! SPRITE_Y {
     ($sprite:expr) => {{
-        SPRITE_ATTR!($sprite, y)
+        $sprite.y()
     }};
 }
 
@@ -160,7 +269,7 @@ macro_rules! SPRITE_Y {
 #[macro_export]
 macro_rules! SPRITE_WIDTH {
     ($sprite:expr) => {{
-        SPRITE_ATTR!($sprite, width)
+        $sprite.width()
     }};
 }
 
@@ -168,7 +277,51 @@ macro_rules! SPRITE_WIDTH {
 #[macro_export]
 macro_rules! SPRITE_HEIGHT {
     ($sprite:expr) => {{
-        SPRITE_ATTR!($sprite, height)
+        $sprite.height()
+    }};
+}
+
+/// Get the rotation of the Sprite, in degrees clockwise
+#[macro_export]
+macro_rules! SPRITE_ROTATION {
+    ($sprite:expr) => {{
+        $sprite.rotation()
+    }};
+}
+
+/// Get the horizontal and vertical scale of the Sprite
+#[macro_export]
+macro_rules! SPRITE_SCALE {
+    ($sprite:expr) => {{
+        $sprite.scale()
+    }};
+}
+
+/// Get the origin of the Sprite, the pivot of its rotation and scale
+#[macro_export]
+macro_rules! SPRITE_ORIGIN {
+    ($sprite:expr) => {{
+        $sprite.origin()
+    }};
+}
+
+/// Rotate a sprite to an angle in degrees clockwise, around its origin
+#[macro_export]
+macro_rules! ROTATE_SPRITE {
+    ($sprite:expr, $degrees:expr) => {{
+        $sprite.set_rotation($degrees);
+    }};
+}
+
+/// Scale a sprite around its origin, uniformly or with a horizontal and a vertical scale
+#[macro_export]
+macro_rules! SCALE_SPRITE {
+    ($sprite:expr, $scale:expr) => {{
+        let scale = $scale;
+        $sprite.set_scale(scale, scale);
+    }};
+    ($sprite:expr, $scale_x:expr, $scale_y:expr) => {{
+        $sprite.set_scale($scale_x, $scale_y);
     }};
 }
 
@@ -177,10 +330,10 @@ macro_rules! SPRITE_HEIGHT {
 #[macro_export]
 macro_rules! GO_LEFT {
     ($sprite:expr, $window:expr, $speed:expr) => {
-        if SPRITE_X!($sprite) < -$window.sprite_side as f32 {
+        if $crate::SPRITE_X!($sprite) < -$window.sprite_side as f32 {
             $window.width as f32
         } else {
-            SPRITE_X!($sprite) - 1.0 * $speed
+            $crate::SPRITE_X!($sprite) - 1.0 * $speed
         }
     };
 }
@@ -190,10 +343,10 @@ macro_rules! GO_LEFT {
 #[macro_export]
 macro_rules! GO_RIGHT {
     ($sprite:expr, $window:expr, $speed:expr) => {
-        if SPRITE_X!($sprite) > $window.width as f32 {
+        if $crate::SPRITE_X!($sprite) > $window.width as f32 {
             -$window.sprite_side as f32
         } else {
-            SPRITE_X!($sprite) + 1.0 * $speed
+            $crate::SPRITE_X!($sprite) + 1.0 * $speed
         }
     };
 }
@@ -203,10 +356,10 @@ macro_rules! GO_RIGHT {
 #[macro_export]
 macro_rules! GO_UP {
     ($sprite:expr, $window:expr, $speed:expr) => {
-        if SPRITE_Y!($sprite) < -$window.sprite_side as f32 {
+        if $crate::SPRITE_Y!($sprite) < -$window.sprite_side as f32 {
             $window.height as f32
         } else {
-            SPRITE_Y!($sprite) - 1.0 * $speed
+            $crate::SPRITE_Y!($sprite) - 1.0 * $speed
         }
     };
 }
@@ -216,44 +369,52 @@ macro_rules! GO_UP {
 #[macro_export]
 macro_rules! GO_DOWN {
     ($sprite:expr, $window:expr, $speed:expr) => {
-        if SPRITE_Y!($sprite) > $window.height as f32 {
+        if $crate::SPRITE_Y!($sprite) > $window.height as f32 {
             -$window.sprite_side as f32
         } else {
-            SPRITE_Y!($sprite) + 1.0 * $speed
+            $crate::SPRITE_Y!($sprite) + 1.0 * $speed
         }
     };
 }
 
-/// Render a text box on the screen at a specific coordinate
+/// Render a text box on the screen at a specific coordinate, over the sprites of the
+/// default layer. The text is drawn with the default font, `font::DEFAULT_SIZE` pixels high
+/// at a scale of 1. A layer can be given first to draw it elsewhere.
 #[macro_export]
 macro_rules! TEXT_RENDER {
-    ($text:ident, $x:expr, $y:expr, $scale:expr, $r:expr, $g:expr, $b:expr) => {{
-        unsafe {
-            game_ffi::renderText($text, $x, $y, $scale, $r, $g, $b);
-        }
+    ($text:expr, $x:expr, $y:expr, $scale:expr, $r:expr, $g:expr, $b:expr) => {
+        $crate::TEXT_RENDER!(layer = $crate::layer::TEXT_LAYER; $text, $x, $y, $scale, $r, $g, $b)
+    };
+    (layer = $layer:expr; $text:expr, $x:expr, $y:expr, $scale:expr, $r:expr, $g:expr, $b:expr) => {{
+        let text = $text;
+        let text = unsafe { $crate::game_macros::MacroText::to_text(&text) };
+        let (x, y, scale, color) = ($x, $y, $scale, [$r, $g, $b]);
+        $crate::layer::draw_text($layer, &text, x, y, scale, color);
+        $crate::stats::count_text_draw();
     }};
 }
 
-/// clear the screen
+/// clear the screen, along with what was rendered and not drawn yet
 #[macro_export]
 macro_rules! CLEAR_SCREEN {
     () => {
         {
-            unsafe {
-                game_ffi::clear_screen();
-            }
+            $crate::layer::discard();
+            $crate::renderer::with_renderer(|renderer| renderer.clear_screen());
         }
     };
 }
 
-/// Create a game window
+/// Create a game window and return its `WindowId`. Several windows can be created, the
+/// last one becomes the current window.
 #[macro_export]
 macro_rules! CREATE_GAME {
     ($title:expr, $width:expr, $height:expr) => {
         {
-            unsafe {
-                game_ffi::create_game_window($title, $width, $height);
-            }
+            let title = $title;
+            let title = unsafe { $crate::game_macros::MacroText::to_text(&title) };
+            let (width, height) = ($width, $height);
+            $crate::window::create(&title, width, height)
         }
     };
 }
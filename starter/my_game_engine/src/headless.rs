@@ -0,0 +1,463 @@
+//! headless.rs
+//!
+//! A `Renderer` drawing into an in-memory framebuffer instead of an OpenGL window, so the
+//! engine, its tests and games can run on machines without an X server or a GPU.
+//!
+//! The window state is kept per thread, the same way an OpenGL context is bound to the
+//! thread that created it. The helpers simulating the user act on the current window.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ptr::NonNull;
+use std::time::Duration;
+
+use crate::framebuffer::Framebuffer;
+use crate::game_ffi::Sprite;
+use crate::input::{self, KeyEvent, Modifiers};
+use crate::key::{Key, KeyState};
+use crate::mouse::{MouseButton, MouseEvent};
+use crate::renderer::Renderer;
+use crate::texture::Image;
+use crate::window::{self, Viewport, WindowId};
+
+/// Environment variable used to close the headless window after a number of frames
+pub const HEADLESS_MAX_FRAMES_ENV: &str = "HEADLESS_MAX_FRAMES";
+
+/// Time a headless frame lasts unless changed with `set_frame_time`, 60 frames per second
+pub const DEFAULT_FRAME_TIME: Duration = Duration::from_nanos(16_666_667);
+
+/// Largest width and height of a texture, the smallest maximum of OpenGL 3 so that games
+/// tested headless fit on any GPU
+const MAX_TEXTURE_SIZE: i32 = 1024;
+
+/// Everything a headless window keeps track of
+struct HeadlessWindow {
+    title: String,
+    framebuffer: Framebuffer,
+    viewport: Viewport,
+    logical_size: (i32, i32),
+    pressed_keys: HashSet<Key>,
+    pressed_buttons: HashSet<MouseButton>,
+    cursor: (f32, f32),
+    close_requested: bool,
+    frames: u64,
+    max_frames: Option<u64>,
+    time: Duration,
+    frame_time: Duration,
+}
+
+/// The headless windows of a thread
+#[derive(Default)]
+struct HeadlessWindows {
+    windows: HashMap<WindowId, HeadlessWindow>,
+    current: Option<WindowId>,
+    /// Textures are shared by all windows, like OpenGL contexts sharing their objects
+    textures: HashMap<u32, Image>,
+    last_texture: u32,
+}
+
+thread_local! {
+    static WINDOWS: RefCell<HeadlessWindows> = RefCell::new(HeadlessWindows::default());
+}
+
+/// Run a closure against the current window of the current thread, and its id
+fn with_current_window<T>(f: impl FnOnce(WindowId, &mut HeadlessWindow) -> T) -> T {
+    WINDOWS.with(|windows| {
+        let windows = &mut *windows.borrow_mut();
+        let id = windows
+            .current
+            .expect("the game window must be created first");
+        f(
+            id,
+            windows
+                .windows
+                .get_mut(&id)
+                .expect("the current window was closed"),
+        )
+    })
+}
+
+/// Run a closure against the current window of the current thread
+fn with_window<T>(f: impl FnOnce(&mut HeadlessWindow) -> T) -> T {
+    with_current_window(|_, window| f(window))
+}
+
+impl HeadlessWindow {
+    /// Draw a rectangle given in logical coordinates pixel by pixel, clipped to the
+    /// viewport, see `Framebuffer::shade_rect`
+    fn shade_logical_rect(
+        &mut self,
+        rect: (f32, f32, f32, f32),
+        shade: impl FnMut(f32, f32) -> Option<[u8; 4]>,
+    ) {
+        let (x1, y1, x2, y2) = rect;
+        let (window_x1, window_y1) = self.to_window(x1, y1);
+        let (window_x2, window_y2) = self.to_window(x2, y2);
+        let clip = self.clip();
+        self.framebuffer
+            .shade_rect((window_x1, window_y1, window_x2, window_y2), clip, shade);
+    }
+
+    /// Draw a sprite pixel by pixel, rotated and scaled around its origin like the OpenGL
+    /// backend does
+    fn shade_sprite(&mut self, sprite: &Sprite, shade: impl FnMut(f32, f32) -> Option<[u8; 4]>) {
+        let (width, height) = (sprite.width as f32, sprite.height as f32);
+        if sprite.rotation == 0.0 && sprite.scale == [1.0, 1.0] {
+            let rect = (sprite.x, sprite.y, sprite.x + width, sprite.y + height);
+            self.shade_logical_rect(rect, shade);
+            return;
+        }
+
+        // with y pointing down, this rotation turns clockwise
+        let pivot_x = sprite.x + sprite.origin[0] * width;
+        let pivot_y = sprite.y + sprite.origin[1] * height;
+        let (sin, cos) = sprite.rotation.to_radians().sin_cos();
+        let corner = |x: f32, y: f32| {
+            let dx = (x - pivot_x) * sprite.scale[0];
+            let dy = (y - pivot_y) * sprite.scale[1];
+            self.to_window(pivot_x + dx * cos - dy * sin, pivot_y + dx * sin + dy * cos)
+        };
+        let corners = [
+            corner(sprite.x, sprite.y),
+            corner(sprite.x + width, sprite.y),
+            corner(sprite.x, sprite.y + height),
+        ];
+        let clip = self.clip();
+        self.framebuffer.shade_quad(corners, clip, shade);
+    }
+
+    /// Window position of a point in logical coordinates
+    fn to_window(&self, x: f32, y: f32) -> (f32, f32) {
+        let viewport = self.viewport;
+        let (logical_width, logical_height) = self.logical_size;
+        let scale_x = viewport.width as f32 / logical_width.max(1) as f32;
+        let scale_y = viewport.height as f32 / logical_height.max(1) as f32;
+        (
+            viewport.x as f32 + x * scale_x,
+            viewport.y as f32 + y * scale_y,
+        )
+    }
+
+    /// The viewport, outside of which nothing is drawn
+    fn clip(&self) -> (f32, f32, f32, f32) {
+        let viewport = self.viewport;
+        (
+            viewport.x as f32,
+            viewport.y as f32,
+            (viewport.x + viewport.width) as f32,
+            (viewport.y + viewport.height) as f32,
+        )
+    }
+}
+
+/// Color of the texel of `image` at `u`, `v` in `tex_coords`, with nearest sampling like
+/// the OpenGL backend. The sprite `tint` multiplies the texel, alpha included, and fully
+/// transparent texels are skipped.
+fn sample(image: &Image, tex_coords: [f32; 4], u: f32, v: f32, tint: [u8; 4]) -> Option<[u8; 4]> {
+    let [left, top, right, bottom] = tex_coords;
+    let texel_x = ((left + (right - left) * u) * image.width() as f32).floor() as i32;
+    let texel_y = ((top + (bottom - top) * v) * image.height() as f32).floor() as i32;
+    let [r, g, b, a] = image.pixel(
+        texel_x.clamp(0, image.width() - 1),
+        texel_y.clamp(0, image.height() - 1),
+    )?;
+
+    let modulate = |c: u8, t: u8| ((c as u32 * t as u32 + 127) / 255) as u8;
+    match modulate(a, tint[3]) {
+        0 => None,
+        alpha => Some([
+            modulate(r, tint[0]),
+            modulate(g, tint[1]),
+            modulate(b, tint[2]),
+            alpha,
+        ]),
+    }
+}
+
+/// Renders into the in-memory framebuffers of the current thread
+pub struct HeadlessRenderer;
+
+impl Renderer for HeadlessRenderer {
+    fn create_window(&mut self, id: WindowId, title: &str, width: i32, height: i32) {
+        let max_frames = std::env::var(HEADLESS_MAX_FRAMES_ENV)
+            .ok()
+            .and_then(|frames| frames.parse().ok());
+
+        WINDOWS.with(|windows| {
+            let windows = &mut *windows.borrow_mut();
+            windows.windows.insert(
+                id,
+                HeadlessWindow {
+                    title: title.to_string(),
+                    framebuffer: Framebuffer::new(width, height),
+                    viewport: Viewport::full(width, height),
+                    logical_size: (width, height),
+                    pressed_keys: HashSet::new(),
+                    pressed_buttons: HashSet::new(),
+                    cursor: (0.0, 0.0),
+                    close_requested: false,
+                    frames: 0,
+                    max_frames,
+                    time: Duration::ZERO,
+                    frame_time: DEFAULT_FRAME_TIME,
+                },
+            );
+            windows.current = Some(id);
+        });
+    }
+
+    fn destroy_window(&mut self, id: WindowId) {
+        WINDOWS.with(|windows| {
+            let windows = &mut *windows.borrow_mut();
+            windows.windows.remove(&id);
+            if windows.current == Some(id) {
+                windows.current = None;
+            }
+        });
+    }
+
+    fn make_current(&mut self, id: WindowId) {
+        WINDOWS.with(|windows| windows.borrow_mut().current = Some(id));
+    }
+
+    fn set_viewport(&mut self, id: WindowId, viewport: Viewport, logical_size: (i32, i32)) {
+        WINDOWS.with(|windows| {
+            if let Some(window) = windows.borrow_mut().windows.get_mut(&id) {
+                window.viewport = viewport;
+                window.logical_size = logical_size;
+            }
+        });
+    }
+
+    fn update_window(&mut self) {
+        with_window(|window| {
+            window.frames += 1;
+            window.time += window.frame_time;
+        });
+    }
+
+    /// The window closes once `request_close` was called or after the
+    /// `HEADLESS_MAX_FRAMES` frame budget is used up
+    fn window_should_close(&self) -> bool {
+        with_window(|window| {
+            let out_of_frames = window
+                .max_frames
+                .is_some_and(|max_frames| window.frames >= max_frames);
+            window.close_requested || out_of_frames
+        })
+    }
+
+    fn clear_screen(&mut self) {
+        with_window(|window| window.framebuffer.clear());
+    }
+
+    fn create_sprite(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: i32,
+        height: i32,
+        color: [i32; 4],
+    ) -> NonNull<Sprite> {
+        NonNull::from(Box::leak(Box::new(Sprite {
+            width,
+            height,
+            color,
+            x,
+            y,
+            texture: 0,
+            tex_coords: [0.0, 0.0, 1.0, 1.0],
+            rotation: 0.0,
+            scale: [1.0, 1.0],
+            origin: [0.5, 0.5],
+        })))
+    }
+
+    unsafe fn destroy_sprite(&mut self, sprite: NonNull<Sprite>) {
+        drop(Box::from_raw(sprite.as_ptr()));
+    }
+
+    fn render_sprite(&mut self, sprite: &Sprite) {
+        // same truncation as `glColor4ub`
+        let color = sprite.color.map(|c| c as u8);
+
+        if sprite.texture == 0 {
+            with_window(|window| window.shade_sprite(sprite, |_, _| Some(color)));
+            return;
+        }
+
+        WINDOWS.with(|windows| {
+            let windows = &mut *windows.borrow_mut();
+            let image = windows
+                .textures
+                .get(&sprite.texture)
+                .expect("the sprite texture was destroyed");
+            let window = windows
+                .current
+                .and_then(|id| windows.windows.get_mut(&id))
+                .expect("the game window must be created first");
+
+            window.shade_sprite(sprite, |u, v| sample(image, sprite.tex_coords, u, v, color));
+        });
+    }
+
+    fn render_polygon(&mut self, points: &[(f32, f32)], color: [i32; 4]) {
+        // same truncation as `glColor4ub`
+        let color = color.map(|c| c as u8);
+        with_window(|window| {
+            let points: Vec<(f32, f32)> = points
+                .iter()
+                .map(|&(x, y)| window.to_window(x, y))
+                .collect();
+            let clip = window.clip();
+            window.framebuffer.fill_polygon(&points, clip, color);
+        });
+    }
+
+    fn create_texture(&mut self, image: &Image) -> u32 {
+        WINDOWS.with(|windows| {
+            let windows = &mut *windows.borrow_mut();
+            windows.last_texture += 1;
+            windows.textures.insert(windows.last_texture, image.clone());
+            windows.last_texture
+        })
+    }
+
+    fn destroy_texture(&mut self, texture: u32) {
+        WINDOWS.with(|windows| windows.borrow_mut().textures.remove(&texture));
+    }
+
+    fn max_texture_size(&self) -> i32 {
+        MAX_TEXTURE_SIZE
+    }
+
+    fn key_state(&self, key: Key) -> KeyState {
+        with_window(|window| match window.pressed_keys.contains(&key) {
+            true => KeyState::Pressed,
+            false => KeyState::Released,
+        })
+    }
+
+    fn cursor_position(&self) -> (f32, f32) {
+        with_window(|window| window.cursor)
+    }
+
+    fn mouse_button_pressed(&self, button: MouseButton) -> bool {
+        with_window(|window| window.pressed_buttons.contains(&button))
+    }
+
+    /// Headless windows run on a simulated clock advancing by the frame time at every
+    /// presented frame, so runs are reproducible whatever the speed of the machine
+    fn time(&self) -> Duration {
+        with_window(|window| window.time)
+    }
+
+    fn capture_frame(&mut self) -> Framebuffer {
+        framebuffer()
+    }
+}
+
+/// Press or release a key in the current window, as seen by `key_state`. A `KeyDown` or
+/// `KeyUp` event is queued when the state of the key changes.
+pub fn set_key(key: Key, pressed: bool) {
+    let (id, changed) = with_current_window(|id, window| match pressed {
+        true => (id, window.pressed_keys.insert(key)),
+        false => (id, window.pressed_keys.remove(&key)),
+    });
+
+    let modifiers = Modifiers::NONE;
+    match (changed, pressed) {
+        (true, true) => input::push_key_event(id, KeyEvent::KeyDown { key, modifiers }),
+        (true, false) => input::push_key_event(id, KeyEvent::KeyUp { key, modifiers }),
+        (false, _) => {}
+    }
+}
+
+/// Move the mouse cursor to a position in window pixels, as seen by `cursor_position` and
+/// the following button events
+pub fn set_cursor_position(x: f32, y: f32) {
+    with_window(|window| window.cursor = (x, y));
+}
+
+/// Press or release a mouse button at the cursor position of the current window. A
+/// `ButtonDown` or `ButtonUp` event is queued when the state of the button changes.
+pub fn set_mouse_button(button: MouseButton, pressed: bool) {
+    let (id, changed, (window_x, window_y)) = with_current_window(|id, window| {
+        let changed = match pressed {
+            true => window.pressed_buttons.insert(button),
+            false => window.pressed_buttons.remove(&button),
+        };
+        (id, changed, window.cursor)
+    });
+
+    let (x, y) = window::to_logical(id, window_x, window_y);
+    let modifiers = Modifiers::NONE;
+    match (changed, pressed) {
+        (true, true) => input::push_mouse_event(
+            id,
+            MouseEvent::ButtonDown {
+                button,
+                x,
+                y,
+                modifiers,
+            },
+        ),
+        (true, false) => input::push_mouse_event(
+            id,
+            MouseEvent::ButtonUp {
+                button,
+                x,
+                y,
+                modifiers,
+            },
+        ),
+        (false, _) => {}
+    }
+}
+
+/// Scroll the mouse wheel over the current window, queueing a `Scroll` event
+pub fn scroll(dx: f32, dy: f32) {
+    let id = with_current_window(|id, _| id);
+    input::push_mouse_event(id, MouseEvent::Scroll { dx, dy });
+}
+
+/// Resize the current window, as a user dragging its border would. The window is cleared
+/// and a `Resized` event is queued.
+pub fn resize_window(width: i32, height: i32) {
+    let id = with_current_window(|id, window| {
+        window.framebuffer = Framebuffer::new(width, height);
+        id
+    });
+    window::handle_resize(id, width, height);
+}
+
+/// Ask the current window to close, as a user clicking the close button would
+pub fn request_close() {
+    with_window(|window| window.close_requested = true);
+}
+
+/// Close the window once `max_frames` frames were presented, `None` to run forever.
+/// Overrides the `HEADLESS_MAX_FRAMES` environment variable.
+pub fn set_max_frames(max_frames: Option<u64>) {
+    with_window(|window| window.max_frames = max_frames);
+}
+
+/// Change how much the simulated clock advances at every presented frame
+pub fn set_frame_time(frame_time: Duration) {
+    with_window(|window| window.frame_time = frame_time);
+}
+
+/// Number of frames presented through `update_window`
+pub fn frame_count() -> u64 {
+    with_window(|window| window.frames)
+}
+
+/// Title given to the window at creation
+pub fn window_title() -> String {
+    with_window(|window| window.title.clone())
+}
+
+/// A copy of what is currently drawn on the current window
+pub fn framebuffer() -> Framebuffer {
+    with_window(|window| window.framebuffer.clone())
+}
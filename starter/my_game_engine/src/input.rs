@@ -0,0 +1,233 @@
+//! input.rs
+//!
+//! Event based keyboard, mouse and window input. Backends push events as they arrive, and at every
+//! frame boundary the pending events become the events of the new frame. Every part of
+//! the game can look at the events of the current frame, nothing consumes them.
+//!
+//! Events are recorded with the window they happened in, and the queries only see the
+//! events of the current window.
+
+use std::cell::RefCell;
+use std::ops::BitOr;
+use std::os::raw::c_int;
+
+use crate::key::{Key, KeyState};
+use crate::mouse::{MouseButton, MouseEvent};
+use crate::renderer::with_renderer;
+use crate::window::{self, WindowEvent, WindowId};
+
+/// Modifier keys held when a key event happened, using the GLFW bit values
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers(c_int);
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const SHIFT: Modifiers = Modifiers(0x0001);
+    pub const CONTROL: Modifiers = Modifiers(0x0002);
+    pub const ALT: Modifiers = Modifiers(0x0004);
+    pub const SUPER: Modifiers = Modifiers(0x0008);
+    pub const CAPS_LOCK: Modifiers = Modifiers(0x0010);
+    pub const NUM_LOCK: Modifiers = Modifiers(0x0020);
+
+    /// Modifiers from the `mods` bit field of a GLFW key callback
+    pub fn from_glfw(mods: c_int) -> Modifiers {
+        Modifiers(mods & 0x003f)
+    }
+
+    /// Whether all of `other` modifiers are held
+    pub fn contains(self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 | other.0)
+    }
+}
+
+/// A change in the state of a keyboard key
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyEvent {
+    /// The key went down
+    KeyDown { key: Key, modifiers: Modifiers },
+    /// The key was released
+    KeyUp { key: Key, modifiers: Modifiers },
+    /// The key is held down long enough for the OS to repeat it
+    KeyRepeat { key: Key, modifiers: Modifiers },
+}
+
+impl KeyEvent {
+    /// Event from the arguments of a GLFW key callback, `None` for unknown keys or actions
+    pub fn from_glfw(key: c_int, action: c_int, mods: c_int) -> Option<KeyEvent> {
+        let key = Key::from_glfw(key)?;
+        let modifiers = Modifiers::from_glfw(mods);
+        match KeyState::from_glfw(action)? {
+            KeyState::Pressed => Some(KeyEvent::KeyDown { key, modifiers }),
+            KeyState::Released => Some(KeyEvent::KeyUp { key, modifiers }),
+            KeyState::Repeat => Some(KeyEvent::KeyRepeat { key, modifiers }),
+        }
+    }
+
+    pub fn key(&self) -> Key {
+        match *self {
+            KeyEvent::KeyDown { key, .. }
+            | KeyEvent::KeyUp { key, .. }
+            | KeyEvent::KeyRepeat { key, .. } => key,
+        }
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        match *self {
+            KeyEvent::KeyDown { modifiers, .. }
+            | KeyEvent::KeyUp { modifiers, .. }
+            | KeyEvent::KeyRepeat { modifiers, .. } => modifiers,
+        }
+    }
+}
+
+/// Events received since the last frame boundary, and the events of the current frame,
+/// with the window they happened in
+#[derive(Default)]
+struct Events {
+    pending_keys: Vec<(WindowId, KeyEvent)>,
+    pending_mouse: Vec<(WindowId, MouseEvent)>,
+    pending_window: Vec<(WindowId, WindowEvent)>,
+    keys: Vec<(WindowId, KeyEvent)>,
+    mouse: Vec<(WindowId, MouseEvent)>,
+    window: Vec<(WindowId, WindowEvent)>,
+}
+
+thread_local! {
+    static EVENTS: RefCell<Events> = RefCell::new(Events::default());
+}
+
+/// Queue a key event of `window`, it will be part of the next frame. Called by the
+/// backends.
+pub fn push_key_event(window: WindowId, event: KeyEvent) {
+    EVENTS.with(|events| events.borrow_mut().pending_keys.push((window, event)));
+}
+
+/// Queue a mouse event of `window`, it will be part of the next frame. Called by the
+/// backends.
+pub fn push_mouse_event(window: WindowId, event: MouseEvent) {
+    EVENTS.with(|events| events.borrow_mut().pending_mouse.push((window, event)));
+}
+
+/// Queue a window event of `window`, it will be part of the next frame. Called by the
+/// backends.
+pub fn push_window_event(window: WindowId, event: WindowEvent) {
+    EVENTS.with(|events| events.borrow_mut().pending_window.push((window, event)));
+}
+
+/// The events of the current window from one of the event lists
+fn of_current_window<T: Copy>(events: &[(WindowId, T)]) -> Vec<T> {
+    let current = window::current();
+    events
+        .iter()
+        .filter(|(window, _)| Some(*window) == current)
+        .map(|(_, event)| *event)
+        .collect()
+}
+
+/// Start a new frame: the events received since the previous call become the events
+/// of this frame. Called by the game loop right after the window processed its events.
+pub fn start_frame() {
+    EVENTS.with(|events| {
+        let events = &mut *events.borrow_mut();
+        events.keys = std::mem::take(&mut events.pending_keys);
+        events.mouse = std::mem::take(&mut events.pending_mouse);
+        events.window = std::mem::take(&mut events.pending_window);
+    });
+}
+
+/// Key events of the current frame, in the order they happened
+pub fn key_events() -> Vec<KeyEvent> {
+    EVENTS.with(|events| of_current_window(&events.borrow().keys))
+}
+
+/// Whether `key` went down during the current frame
+pub fn key_went_down(key: Key) -> bool {
+    key_events()
+        .iter()
+        .any(|event| matches!(event, KeyEvent::KeyDown { key: k, .. } if *k == key))
+}
+
+/// Whether `key` was released during the current frame
+pub fn key_went_up(key: Key) -> bool {
+    key_events()
+        .iter()
+        .any(|event| matches!(event, KeyEvent::KeyUp { key: k, .. } if *k == key))
+}
+
+/// Mouse events of the current frame, in the order they happened
+pub fn mouse_events() -> Vec<MouseEvent> {
+    EVENTS.with(|events| of_current_window(&events.borrow().mouse))
+}
+
+/// Where `button` went down during the current frame, if it did
+pub fn mouse_button_went_down(button: MouseButton) -> Option<(f32, f32)> {
+    mouse_events().iter().find_map(|event| match *event {
+        MouseEvent::ButtonDown {
+            button: b, x, y, ..
+        } if b == button => Some((x, y)),
+        _ => None,
+    })
+}
+
+/// Where `button` was released during the current frame, if it was
+pub fn mouse_button_went_up(button: MouseButton) -> Option<(f32, f32)> {
+    mouse_events().iter().find_map(|event| match *event {
+        MouseEvent::ButtonUp {
+            button: b, x, y, ..
+        } if b == button => Some((x, y)),
+        _ => None,
+    })
+}
+
+/// Total scrolling during the current frame, horizontal then vertical
+pub fn scroll_delta() -> (f32, f32) {
+    mouse_events()
+        .iter()
+        .fold((0.0, 0.0), |(total_x, total_y), event| match *event {
+            MouseEvent::Scroll { dx, dy } => (total_x + dx, total_y + dy),
+            _ => (total_x, total_y),
+        })
+}
+
+/// Current position of the mouse cursor in logical coordinates
+pub fn cursor_position() -> (f32, f32) {
+    let (x, y) = with_renderer(|renderer| renderer.cursor_position());
+    match window::current() {
+        Some(current) => window::to_logical(current, x, y),
+        None => (x, y),
+    }
+}
+
+/// Whether a mouse button is currently held down
+pub fn mouse_button_pressed(button: MouseButton) -> bool {
+    with_renderer(|renderer| renderer.mouse_button_pressed(button))
+}
+
+/// Window events of the current frame, in the order they happened
+pub fn window_events() -> Vec<WindowEvent> {
+    EVENTS.with(|events| of_current_window(&events.borrow().window))
+}
+
+/// New size of the window if it was resized during the current frame
+pub fn window_resized() -> Option<(i32, i32)> {
+    let mut size = None;
+    for event in window_events() {
+        match event {
+            WindowEvent::Resized { width, height } => size = Some((width, height)),
+        }
+    }
+    size
+}
+
+/// Drop every pending and current event, e.g. when switching between game screens
+pub fn clear_events() {
+    EVENTS.with(|events| *events.borrow_mut() = Events::default());
+}
@@ -0,0 +1,326 @@
+//! key.rs
+//!
+//! Typed keyboard keys and key states, with their GLFW codes.
+
+use std::os::raw::c_int;
+
+use crate::game_ffi::{GLFW_PRESS, GLFW_RELEASE, GLFW_REPEAT};
+
+/// A keyboard key, identified by its position on a US keyboard layout.
+/// Discriminants are the GLFW key codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum Key {
+    Space = 32,
+    /// `'`
+    Apostrophe = 39,
+    /// `,`
+    Comma = 44,
+    /// `-`
+    Minus = 45,
+    /// `.`
+    Period = 46,
+    /// `/`
+    Slash = 47,
+    /// The `0` key of the main keyboard
+    Num0 = 48,
+    Num1 = 49,
+    Num2 = 50,
+    Num3 = 51,
+    Num4 = 52,
+    Num5 = 53,
+    Num6 = 54,
+    Num7 = 55,
+    Num8 = 56,
+    Num9 = 57,
+    /// `;`
+    Semicolon = 59,
+    /// `=`
+    Equal = 61,
+    A = 65,
+    B = 66,
+    C = 67,
+    D = 68,
+    E = 69,
+    F = 70,
+    G = 71,
+    H = 72,
+    I = 73,
+    J = 74,
+    K = 75,
+    L = 76,
+    M = 77,
+    N = 78,
+    O = 79,
+    P = 80,
+    Q = 81,
+    R = 82,
+    S = 83,
+    T = 84,
+    U = 85,
+    V = 86,
+    W = 87,
+    X = 88,
+    Y = 89,
+    Z = 90,
+    /// `[`
+    LeftBracket = 91,
+    /// `\`
+    Backslash = 92,
+    /// `]`
+    RightBracket = 93,
+    /// `` ` ``
+    GraveAccent = 96,
+    /// Non-US key #1
+    World1 = 161,
+    /// Non-US key #2
+    World2 = 162,
+    Escape = 256,
+    Enter = 257,
+    Tab = 258,
+    Backspace = 259,
+    Insert = 260,
+    Delete = 261,
+    Right = 262,
+    Left = 263,
+    Down = 264,
+    Up = 265,
+    PageUp = 266,
+    PageDown = 267,
+    Home = 268,
+    End = 269,
+    CapsLock = 280,
+    ScrollLock = 281,
+    NumLock = 282,
+    PrintScreen = 283,
+    Pause = 284,
+    F1 = 290,
+    F2 = 291,
+    F3 = 292,
+    F4 = 293,
+    F5 = 294,
+    F6 = 295,
+    F7 = 296,
+    F8 = 297,
+    F9 = 298,
+    F10 = 299,
+    F11 = 300,
+    F12 = 301,
+    F13 = 302,
+    F14 = 303,
+    F15 = 304,
+    F16 = 305,
+    F17 = 306,
+    F18 = 307,
+    F19 = 308,
+    F20 = 309,
+    F21 = 310,
+    F22 = 311,
+    F23 = 312,
+    F24 = 313,
+    F25 = 314,
+    /// Keypad `0`
+    Kp0 = 320,
+    Kp1 = 321,
+    Kp2 = 322,
+    Kp3 = 323,
+    Kp4 = 324,
+    Kp5 = 325,
+    Kp6 = 326,
+    Kp7 = 327,
+    Kp8 = 328,
+    Kp9 = 329,
+    KpDecimal = 330,
+    KpDivide = 331,
+    KpMultiply = 332,
+    KpSubtract = 333,
+    KpAdd = 334,
+    KpEnter = 335,
+    KpEqual = 336,
+    LeftShift = 340,
+    LeftControl = 341,
+    LeftAlt = 342,
+    LeftSuper = 343,
+    RightShift = 344,
+    RightControl = 345,
+    RightAlt = 346,
+    RightSuper = 347,
+    Menu = 348,
+}
+
+impl Key {
+    /// Every key, in GLFW code order
+    pub const ALL: [Key; 120] = [
+        Key::Space,
+        Key::Apostrophe,
+        Key::Comma,
+        Key::Minus,
+        Key::Period,
+        Key::Slash,
+        Key::Num0,
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+        Key::Semicolon,
+        Key::Equal,
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+        Key::LeftBracket,
+        Key::Backslash,
+        Key::RightBracket,
+        Key::GraveAccent,
+        Key::World1,
+        Key::World2,
+        Key::Escape,
+        Key::Enter,
+        Key::Tab,
+        Key::Backspace,
+        Key::Insert,
+        Key::Delete,
+        Key::Right,
+        Key::Left,
+        Key::Down,
+        Key::Up,
+        Key::PageUp,
+        Key::PageDown,
+        Key::Home,
+        Key::End,
+        Key::CapsLock,
+        Key::ScrollLock,
+        Key::NumLock,
+        Key::PrintScreen,
+        Key::Pause,
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+        Key::F13,
+        Key::F14,
+        Key::F15,
+        Key::F16,
+        Key::F17,
+        Key::F18,
+        Key::F19,
+        Key::F20,
+        Key::F21,
+        Key::F22,
+        Key::F23,
+        Key::F24,
+        Key::F25,
+        Key::Kp0,
+        Key::Kp1,
+        Key::Kp2,
+        Key::Kp3,
+        Key::Kp4,
+        Key::Kp5,
+        Key::Kp6,
+        Key::Kp7,
+        Key::Kp8,
+        Key::Kp9,
+        Key::KpDecimal,
+        Key::KpDivide,
+        Key::KpMultiply,
+        Key::KpSubtract,
+        Key::KpAdd,
+        Key::KpEnter,
+        Key::KpEqual,
+        Key::LeftShift,
+        Key::LeftControl,
+        Key::LeftAlt,
+        Key::LeftSuper,
+        Key::RightShift,
+        Key::RightControl,
+        Key::RightAlt,
+        Key::RightSuper,
+        Key::Menu,
+    ];
+
+    /// Key for a GLFW key code, `None` for unknown keys
+    pub fn from_glfw(code: c_int) -> Option<Key> {
+        Key::ALL.iter().copied().find(|key| key.to_glfw() == code)
+    }
+
+    /// GLFW key code of the key
+    pub fn to_glfw(self) -> c_int {
+        self as c_int
+    }
+}
+
+/// State of a keyboard key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyState {
+    #[default]
+    Released,
+    Pressed,
+    /// Held down long enough for the OS to repeat it
+    Repeat,
+}
+
+impl KeyState {
+    /// State for a GLFW key action, `None` for unknown actions
+    pub fn from_glfw(action: c_int) -> Option<KeyState> {
+        match action {
+            GLFW_RELEASE => Some(KeyState::Released),
+            GLFW_PRESS => Some(KeyState::Pressed),
+            GLFW_REPEAT => Some(KeyState::Repeat),
+            _ => None,
+        }
+    }
+
+    /// Whether the key is held down
+    pub fn is_down(self) -> bool {
+        self != KeyState::Released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glfw_codes_round_trip() {
+        for key in Key::ALL {
+            assert_eq!(Key::from_glfw(key.to_glfw()), Some(key));
+        }
+        assert_eq!(Key::from_glfw(-1), None);
+        assert_eq!(Key::from_glfw(270), None);
+    }
+}
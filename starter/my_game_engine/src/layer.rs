@@ -0,0 +1,96 @@
+//! layer.rs
+//!
+//! Draw layers. Rendering a sprite, a shape or a text does not draw it right away: the draw is
+//! queued with its layer, and the queue is drawn from the lowest layer to the highest when
+//! the frame is presented. Draws on the same layer keep the order they were made in, so
+//! games pick layers instead of carefully ordering their render calls.
+//!
+//! The queue belongs to the current window. It is drawn before another window becomes
+//! current, and dropped when the screen is cleared. Sprites and polygons are placed
+//! through the camera of the window when they are queued, see `camera`.
+
+use std::cell::RefCell;
+
+use crate::camera;
+use crate::font;
+use crate::game_ffi::Sprite;
+use crate::renderer::with_renderer;
+use crate::texture::Texture;
+
+/// Layer of new sprites
+pub const DEFAULT_LAYER: i32 = 0;
+/// Layer of `TEXT_RENDER!`, above the sprites of the default layer
+pub const TEXT_LAYER: i32 = 100;
+/// Layer of the frame statistics overlay, above everything else
+pub const OVERLAY_LAYER: i32 = i32::MAX;
+
+/// A draw waiting for the end of the frame
+enum Draw {
+    Sprite {
+        sprite: Sprite,
+        /// Keeps the texture alive until the sprite is drawn
+        _texture: Option<Texture>,
+    },
+    Polygon {
+        points: Vec<(f32, f32)>,
+        color: [i32; 4],
+    },
+}
+
+thread_local! {
+    static QUEUE: RefCell<Vec<(i32, Draw)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Queue a copy of `sprite` on `layer`. Later changes to the sprite don't affect the draw.
+pub fn draw_sprite(layer: i32, sprite: &Sprite, texture: Option<&Texture>) {
+    let draw = Draw::Sprite {
+        sprite: camera::project_sprite(sprite),
+        _texture: texture.cloned(),
+    };
+    QUEUE.with(|queue| queue.borrow_mut().push((layer, draw)));
+}
+
+/// Queue a convex polygon filled with an RGBA color on `layer`
+pub fn draw_polygon(layer: i32, points: Vec<(f32, f32)>, color: [i32; 4]) {
+    let draw = Draw::Polygon {
+        points: camera::project_points(points),
+        color,
+    };
+    QUEUE.with(|queue| queue.borrow_mut().push((layer, draw)));
+}
+
+/// Queue a line of text on `layer` with the default font, `font::DEFAULT_SIZE` pixels high
+/// at a `scale` of 1
+pub fn draw_text(layer: i32, text: &str, x: f32, y: f32, scale: f32, color: [f32; 3]) {
+    // same clamping as `glColor3f`
+    let [r, g, b] = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as i32);
+    let size = font::DEFAULT_SIZE * scale;
+    font::default_font().draw(layer, text, x, y, size, [r, g, b, 255]);
+}
+
+/// Draw the queue on the current window, from the lowest layer to the highest
+pub fn flush() {
+    let mut draws = QUEUE.with(|queue| std::mem::take(&mut *queue.borrow_mut()));
+    if draws.is_empty() {
+        return;
+    }
+    // stable, draws on a layer keep their order
+    draws.sort_by_key(|(layer, _)| *layer);
+
+    with_renderer(|renderer| {
+        for (_, draw) in &draws {
+            match draw {
+                Draw::Sprite { sprite, .. } => renderer.render_sprite(sprite),
+                Draw::Polygon { points, color } => renderer.render_polygon(points, *color),
+            }
+        }
+    });
+    // textures are released once the renderer is available again
+    drop(draws);
+}
+
+/// Drop the queue without drawing it, e.g. when the screen is cleared
+pub fn discard() {
+    let draws = QUEUE.with(|queue| std::mem::take(&mut *queue.borrow_mut()));
+    drop(draws);
+}
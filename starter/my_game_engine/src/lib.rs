@@ -1,12 +1,43 @@
+pub mod animation;
+pub mod audio;
+#[cfg(feature = "audio-device")]
+pub mod audio_device;
+pub mod camera;
+pub mod collision;
+pub mod font;
+pub mod framebuffer;
 pub mod game_ffi;
 pub mod game_macros;
+pub mod headless;
+pub mod input;
+pub mod key;
+pub mod layer;
+pub mod mouse;
+#[cfg(feature = "opengl")]
+pub mod opengl;
+pub mod particles;
+pub mod renderer;
+pub mod shape;
+pub mod snapshot;
+pub mod spatial;
+pub mod sprite;
+pub mod stats;
+pub mod text;
+pub mod texture;
+pub mod tilemap;
+pub mod timestep;
+pub mod window;
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use key::Key;
+    use mouse::MouseButton;
+    use renderer::Backend;
     use std::ffi::CString;
-    use std::{thread, time};
+    use std::time;
+    use timestep::FixedTimestep;
 
     const LOOP_SLEEP_MS: time::Duration = time::Duration::from_millis(10);
 
@@ -16,6 +47,39 @@ mod tests {
         sprite_side: 60,
     };
 
+    /// Without a display nobody closes the window, so headless runs stop after a few frames
+    const HEADLESS_TEST_FRAMES: u64 = 50;
+
+    /// Tests run unattended on the headless backend and check frames against golden images.
+    /// Set `GAME_ENGINE_BACKEND=opengl` to watch them in a real window instead.
+    fn headless_run() -> bool {
+        std::env::var_os(renderer::BACKEND_ENV).is_none()
+            || Backend::from_env() == Backend::Headless
+    }
+
+    /// Select the test backend and create the window, closing it after a few frames when
+    /// nobody is watching
+    fn create_test_game(title: &str) -> window::WindowId {
+        renderer::select_backend(match headless_run() {
+            true => Backend::Headless,
+            false => Backend::from_env(),
+        });
+
+        let id = CREATE_GAME!(title, WINDOW.width, WINDOW.height);
+
+        if headless_run() {
+            headless::set_max_frames(Some(HEADLESS_TEST_FRAMES));
+        }
+        id
+    }
+
+    /// Compare the current frame with `tests/golden/<name>.png`
+    fn check_snapshot(name: &str) {
+        if headless_run() {
+            ASSERT_SNAPSHOT!(name);
+        }
+    }
+
     /// test_simple_game_loop:
     /// Tests the basic game loop functionality. In this test, you should create_window
     /// (use 800 width, and 600 height), and loop until window_should_close is true while
@@ -24,11 +88,15 @@ mod tests {
     /// some milliseconds to avoid overworking the CPU. Completing this test should spawn
     /// a blank window that stays open until closed.
     #[test]
-    #[ignore]
     fn test_simple_game_loop() {
-        CREATE_GAME!(C_STRING!("RUNNING test_simple_game_loop"), WINDOW.width, WINDOW.height);
+        create_test_game("RUNNING test_simple_game_loop");
         // Main loop
         START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {});
+
+        if headless_run() {
+            assert_eq!(headless::frame_count(), HEADLESS_TEST_FRAMES);
+        }
+        check_snapshot("simple_game_loop");
     }
 
     /// test_sprite_rendering:
@@ -36,9 +104,8 @@ mod tests {
     /// create_sprite, and render_sprite. The success criteria for this test is to
     /// have a functional window with a sprite rendered on it.
     #[test]
-    #[ignore]
     fn test_sprite_rendering() {
-        CREATE_GAME!(C_STRING!("RUNNING test_sprite_rendering"), WINDOW.width, WINDOW.height);
+        create_test_game("RUNNING test_sprite_rendering");
 
         // Create a sprite
         SPAWN_SPRITE!(
@@ -54,13 +121,14 @@ mod tests {
 
         // Main loop
         START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {});
+
+        check_snapshot("sprite_rendering");
     }
 
     // Same as test_sprite_rendering but flicker between two colors
     #[test]
-    #[ignore]
     fn test_sprite_flicker() {    
-        CREATE_GAME!(C_STRING!("RUNNING test_sprite_flicker"), WINDOW.width, WINDOW.height);
+        create_test_game("RUNNING test_sprite_flicker");
 
         let mut red: bool = true;
 
@@ -89,6 +157,9 @@ mod tests {
                 }
             };
         });
+
+        // an even number of frames ends on red
+        check_snapshot("sprite_flicker");
     }
 
     /// test_screen_clearing:
@@ -99,9 +170,8 @@ mod tests {
     /// cleared the screen and rendered a green sprite, and back to green afterwards in a loop
     ///  until the window closed.
     #[test]
-    #[ignore]
     fn test_screen_clearing() {        
-        CREATE_GAME!(C_STRING!("RUNNING test_screen_clearing"), WINDOW.width, WINDOW.height);
+        create_test_game("RUNNING test_screen_clearing");
 
         let switch_sprite_in_ms = time::Duration::from_millis(500);
 
@@ -125,6 +195,11 @@ mod tests {
             255,
             0
         );
+        check_snapshot("screen_clearing_red");
+
+        CLEAR_SCREEN!();
+        sprite_green.render();
+        check_snapshot("screen_clearing_green");
 
         let mut red = true;
         let mut now = time::Instant::now();
@@ -132,19 +207,17 @@ mod tests {
         // Main loop: switch between red and green
         START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
             if now.elapsed() >= switch_sprite_in_ms {
-                unsafe {
-                    game_ffi::clear_screen();
-                    red = match red {
-                        true => {
-                            game_ffi::render_sprite(sprite_red);
-                            false
-                        }
-                        false => {
-                            game_ffi::render_sprite(sprite_green);
-                            true
-                        }
-                    };
-                }
+                CLEAR_SCREEN!();
+                red = match red {
+                    true => {
+                        sprite_red.render();
+                        false
+                    }
+                    false => {
+                        sprite_green.render();
+                        true
+                    }
+                };
                 now = time::Instant::now();
             }
         });
@@ -155,9 +228,8 @@ mod tests {
     /// and right keypress and then closed the window when all were true. The
     /// success criteria for this test is to be able to visually perceive the key presses.
     #[test]
-    #[ignore]
     fn test_key_presses() {
-        CREATE_GAME!(C_STRING!("RUNNING test_key_presses - [ PRESS LEFT + RIGHT]"), WINDOW.width, WINDOW.height);
+        create_test_game("RUNNING test_key_presses - [ PRESS LEFT + RIGHT]");
 
         // Create a sprite
         SPAWN_SPRITE!(
@@ -171,39 +243,144 @@ mod tests {
             0
         );
 
+        // nobody is at the keyboard in headless mode
+        if headless_run() {
+            headless::set_key(Key::Left, true);
+            headless::set_key(Key::Right, true);
+        }
+
         let mut key_left_pressed = false;
         let mut key_right_pressed = false;
         // Main loop
         START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
-            ON_KEY_PRESS!(game_ffi::GLFW_KEY_LEFT, {
+            ON_KEY_PRESS!(Key::Left, {
                 key_left_pressed = true;
             });
-            ON_KEY_PRESS!(game_ffi::GLFW_KEY_RIGHT, {
+            ON_KEY_PRESS!(Key::Right, {
                 key_right_pressed = true;
             });
 
             if key_left_pressed && key_right_pressed {
-                unsafe {
-                    game_ffi::clear_screen();
-                }
+                CLEAR_SCREEN!();
+                break;
+            }
+        });
+
+        if headless_run() {
+            assert!(key_left_pressed && key_right_pressed);
+            assert_eq!(headless::frame_count(), 0);
+        }
+    }
+
+    /// test_key_events: Tests key press and release edges. A held key should only
+    /// trigger ON_KEY_DOWN once, and ON_KEY_UP once released, which ends the test.
+    #[test]
+    fn test_key_events() {
+        create_test_game("RUNNING test_key_events - [ HOLD AND RELEASE SPACE ]");
+
+        if headless_run() {
+            headless::set_key(Key::Space, true);
+        }
+
+        let mut key_down_count = 0;
+        let mut key_up_count = 0;
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            ON_KEY_DOWN!(Key::Space, {
+                key_down_count += 1;
+            });
+            ON_KEY_UP!(Key::Space, {
+                key_up_count += 1;
+                break;
+            });
+
+            if headless_run() && headless::frame_count() == 10 {
+                headless::set_key(Key::Space, false);
+            }
+        });
+
+        if headless_run() {
+            assert_eq!((key_down_count, key_up_count), (1, 1));
+            assert_eq!(headless::frame_count(), 11);
+        }
+    }
+
+    /// test_mouse_input: Tests mouse buttons, cursor position and the scroll wheel.
+    /// Scrolling moves the sprite up and down, the sprite jumps wherever the left button
+    /// is clicked, and releasing the right button ends the test.
+    #[test]
+    fn test_mouse_input() {
+        create_test_game("RUNNING test_mouse_input - [ SCROLL, LEFT CLICK, RIGHT CLICK TO END ]");
+
+        let mut sprite = SPAWN_SPRITE!(
+            true,
+            100.0,
+            150.0,
+            WINDOW.sprite_side,
+            WINDOW.sprite_side,
+            0,
+            0,
+            255
+        );
+
+        if headless_run() {
+            headless::scroll(0.0, -2.0);
+            headless::scroll(0.0, -3.0);
+        }
+
+        let mut clicks = Vec::new();
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            ON_SCROLL!(|_dx, dy| {
+                let new_y = SPRITE_Y!(sprite) - dy * 10.0;
+                MOVE_SPRITE!(true, true, sprite, SPRITE_X!(sprite), new_y);
+            });
+            ON_MOUSE_DOWN!(MouseButton::Left, |x, y| {
+                clicks.push((x, y));
+                MOVE_SPRITE!(true, true, sprite, x, y);
+            });
+            ON_MOUSE_UP!(MouseButton::Right, |_x, _y| {
                 break;
+            });
+
+            if headless_run() {
+                match headless::frame_count() {
+                    5 => {
+                        headless::set_cursor_position(400.0, 300.0);
+                        assert_eq!(input::cursor_position(), (400.0, 300.0));
+                        headless::set_mouse_button(MouseButton::Left, true);
+                    }
+                    6 => {
+                        assert!(input::mouse_button_pressed(MouseButton::Left));
+                        headless::set_mouse_button(MouseButton::Left, false);
+                        headless::set_mouse_button(MouseButton::Right, true);
+                    }
+                    7 => headless::set_mouse_button(MouseButton::Right, false),
+                    _ => {}
+                }
             }
         });
+
+        if headless_run() {
+            assert_eq!(clicks, vec![(400.0, 300.0)]);
+            assert_eq!(headless::frame_count(), 8);
+            assert_eq!(SPRITE_X!(sprite), 400.0);
+            check_snapshot("mouse_input");
+        }
     }
 
     /// test_sprite_position_update: Tests updating the sprite position.
     /// This test should create a window, render a sprite, and then update
-    ///  the sprite's position (with your bindings to update_sprite_position).
+    ///  the sprite's position (with `SpriteHandle::set_position`).
     ///  The success criteria for this test is to be able to visually perceive
     /// the sprite moving across the screen. Note: you might want to leverage
     /// clear_screen to clear the screen between sprite updates.
     #[test]
-    #[ignore]
     fn test_sprite_position_update() {
-        CREATE_GAME!(C_STRING!("RUNNING test_sprite_position_update - [MOVE AROUND]"), WINDOW.width, WINDOW.height);
+        create_test_game("RUNNING test_sprite_position_update - [MOVE AROUND]");
 
         // Create a sprite
-        let sprite = SPAWN_SPRITE!(
+        let mut sprite = SPAWN_SPRITE!(
             true,
             100.0,
             150.0,
@@ -214,39 +391,858 @@ mod tests {
             0
         );
 
+        // hold the right arrow for the whole run in headless mode
+        if headless_run() {
+            headless::set_key(Key::Right, true);
+        }
+
         // Main loop
         START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
-            ON_KEY_PRESS!(game_ffi::GLFW_KEY_LEFT, {
+            ON_KEY_PRESS!(Key::Left, {
                 let new_x = GO_LEFT!(sprite, WINDOW, 1.0);
                 MOVE_SPRITE!(true, true, sprite, new_x, SPRITE_Y!(sprite));
             });
 
-            ON_KEY_PRESS!(game_ffi::GLFW_KEY_RIGHT, {
+            ON_KEY_PRESS!(Key::Right, {
                 let new_x = GO_RIGHT!(sprite, WINDOW, 1.0);
                 MOVE_SPRITE!(true, true, sprite, new_x, SPRITE_Y!(sprite));
             });
 
-            ON_KEY_PRESS!(game_ffi::GLFW_KEY_UP, {
+            ON_KEY_PRESS!(Key::Up, {
                 let new_y = GO_UP!(sprite, WINDOW, 1.0);
                 MOVE_SPRITE!(true, true, sprite, SPRITE_X!(sprite), new_y);
             });
 
-            ON_KEY_PRESS!(game_ffi::GLFW_KEY_DOWN, {
+            ON_KEY_PRESS!(Key::Down, {
                 let new_y = GO_DOWN!(sprite, WINDOW, 1.0);
                 MOVE_SPRITE!(true, true, sprite, SPRITE_X!(sprite), new_y);
             });
         });
+
+        if headless_run() {
+            assert_eq!(SPRITE_X!(sprite), 100.0 + HEADLESS_TEST_FRAMES as f32);
+        }
+        check_snapshot("sprite_position_update");
     }
 
     #[test]
-    #[ignore]
     fn test_loop_with_text() {
-        CREATE_GAME!(C_STRING!("RUNNING test_simple_game_loop_with_text"), WINDOW.width, WINDOW.height);
+        create_test_game("RUNNING test_simple_game_loop_with_text");
 
         let text = C_STRING!("hello window");
-        TEXT_RENDER!(text, 0.0, 20.0, 100.0, 255.0, 0.0, 0.0);
+        TEXT_RENDER!(text, 0.0, 20.0, 1.0, 255.0, 0.0, 0.0);
 
         // Main loop
         START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {});
+
+        check_snapshot("loop_with_text");
+    }
+
+    /// test_fixed_timestep_loop: Tests the fixed-timestep loop. The sprite should glide
+    /// smoothly to the right at 100 pixels per second, whatever the frame rate.
+    #[test]
+    fn test_fixed_timestep_loop() {
+        create_test_game("RUNNING test_fixed_timestep_loop");
+
+        const SPEED: f32 = 100.0;
+        let mut sprite = SPAWN_SPRITE!(
+            false,
+            100.0,
+            150.0,
+            WINDOW.sprite_side,
+            WINDOW.sprite_side,
+            255,
+            255,
+            0
+        );
+        let (mut previous_x, mut x) = (100.0, 100.0);
+        let mut updates = 0;
+
+        // Main loop
+        START_FIXED_TIMESTEP_LOOP!(FixedTimestep::from_rate(100), |dt| {
+            previous_x = x;
+            x += SPEED * dt;
+            updates += 1;
+        }, |alpha| {
+            assert!((0.0..1.0).contains(&alpha));
+            let render_x = previous_x + (x - previous_x) * alpha;
+            MOVE_SPRITE!(true, true, sprite, render_x, SPRITE_Y!(sprite));
+        });
+
+        if headless_run() {
+            // the clock starts with the first frame, 49 frames of 1/60s later
+            assert_eq!(updates, 81);
+            assert!((x - 181.0).abs() < 1e-3);
+            check_snapshot("fixed_timestep_loop");
+        }
+    }
+
+    /// test_frame_stats: Tests the frame statistics. Three sprites and a text are drawn
+    /// every frame, with the statistics overlay at the bottom of the window.
+    #[test]
+    fn test_frame_stats() {
+        create_test_game("RUNNING test_frame_stats");
+        stats::show_overlay(10.0, 590.0);
+
+        let sprites: Vec<_> = (0..3)
+            .map(|i| {
+                let x = 100.0 + i as f32 * 100.0;
+                SPAWN_SPRITE!(false, x, 150.0, WINDOW.sprite_side, WINDOW.sprite_side, 0, 255, 0)
+            })
+            .collect();
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            sprites.iter().for_each(|sprite| sprite.render());
+            TEXT_RENDER!("stats", 0.0, 20.0, 1.0, 255.0, 0.0, 0.0);
+        });
+
+        let last_frame = stats::last_frame();
+        assert_eq!((last_frame.sprites_drawn, last_frame.texts_drawn), (3, 1));
+        assert!(last_frame.summary().ends_with("| sprites 3"));
+        assert_eq!(last_frame.updates, 1);
+        assert!(last_frame.frame_time >= LOOP_SLEEP_MS);
+        assert!(last_frame.fps > 0.0);
+
+        if headless_run() {
+            assert_eq!(last_frame.frame, HEADLESS_TEST_FRAMES);
+            // the overlay is drawn in yellow on top of the frame
+            assert_eq!(headless::framebuffer().pixel(12, 585), Some([255, 255, 0]));
+        }
+    }
+
+    /// test_window_resize: Tests resizing the window. The game keeps its 800x600 logical
+    /// resolution, letterboxed in the resized window. Resize the window to end the test.
+    #[test]
+    fn test_window_resize() {
+        create_test_game("RUNNING test_window_resize - [ RESIZE THE WINDOW ]");
+
+        let sprite = SPAWN_SPRITE!(
+            false,
+            100.0,
+            150.0,
+            WINDOW.sprite_side,
+            WINDOW.sprite_side,
+            255,
+            0,
+            0
+        );
+
+        let mut resized = None;
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            if let Some(size) = input::window_resized() {
+                resized = Some(size);
+                break;
+            }
+
+            CLEAR_SCREEN!();
+            sprite.render();
+
+            if headless_run() && headless::frame_count() == 5 {
+                headless::resize_window(400, 400);
+            }
+        });
+
+        CLEAR_SCREEN!();
+        sprite.render();
+
+        if headless_run() {
+            assert_eq!(resized, Some((400, 400)));
+            assert_eq!(window::logical_size(), (800, 600));
+            assert_eq!(
+                window::viewport(),
+                window::Viewport {
+                    x: 0,
+                    y: 50,
+                    width: 400,
+                    height: 300
+                }
+            );
+
+            // the sprite's top-left corner, in window pixels
+            headless::set_cursor_position(50.0, 125.0);
+            assert_eq!(input::cursor_position(), (100.0, 150.0));
+            check_snapshot("window_resize");
+
+            // expanding, the game follows the window size
+            window::set_scaling(window::Scaling::Expand);
+            let mut game_window = game_ffi::Window { ..WINDOW };
+            game_window.sync_size();
+            assert_eq!((game_window.width, game_window.height), (400, 400));
+        }
+    }
+
+    /// test_multiple_windows:
+    /// Opens a game window and a smaller inspector window. Each one draws its own sprite
+    /// and only sees its own input: clicks in the inspector are counted there and never
+    /// reach the game window. Closing the inspector leaves the game running.
+    #[test]
+    fn test_multiple_windows() {
+        let game = create_test_game("RUNNING test_multiple_windows - [ CLICK THE INSPECTOR ]");
+        let inspector = CREATE_GAME!("RUNNING test_multiple_windows - inspector", 400, 300);
+        assert_eq!(window::windows(), vec![game, inspector]);
+        assert_eq!(window::current(), Some(inspector));
+
+        let sprite = SPAWN_SPRITE!(
+            false,
+            100.0,
+            150.0,
+            WINDOW.sprite_side,
+            WINDOW.sprite_side,
+            255,
+            0,
+            0
+        );
+        let marker = SPAWN_SPRITE!(false, 20.0, 20.0, 40, 40, 0, 0, 255);
+
+        let mut inspector_clicks = 0;
+        // Main loop, presenting both windows every frame
+        loop {
+            window::make_current(game);
+            if renderer::with_renderer(|renderer| renderer.window_should_close()) {
+                break;
+            }
+            input::start_frame();
+            assert_eq!(input::mouse_events(), vec![]);
+
+            CLEAR_SCREEN!();
+            sprite.render();
+            window::update();
+
+            if window::windows().contains(&inspector) {
+                window::make_current(inspector);
+                if input::mouse_button_went_down(MouseButton::Left).is_some() {
+                    inspector_clicks += 1;
+                }
+
+                CLEAR_SCREEN!();
+                marker.render();
+                window::update();
+
+                if headless_run() && headless::frame_count() == 5 {
+                    headless::set_cursor_position(30.0, 30.0);
+                    headless::set_mouse_button(MouseButton::Left, true);
+                }
+                if renderer::with_renderer(|renderer| renderer.window_should_close()) {
+                    window::close(inspector);
+                }
+            }
+
+            std::thread::sleep(LOOP_SLEEP_MS);
+        }
+
+        if headless_run() {
+            assert_eq!(inspector_clicks, 1);
+
+            check_snapshot("multiple_windows");
+            window::make_current(inspector);
+            check_snapshot("multiple_windows_inspector");
+
+            window::close(inspector);
+            assert_eq!(window::windows(), vec![game]);
+            assert_eq!(window::current(), Some(game));
+            assert_eq!(
+                headless::window_title(),
+                "RUNNING test_multiple_windows - [ CLICK THE INSPECTOR ]"
+            );
+        }
+    }
+
+    /// test_textured_sprites:
+    /// Draws sprites from textures: a whole checkerboard texture with a transparent
+    /// corner, a part of it tinted red, a texture loaded from a PNG file, and a solid
+    /// color sprite next to them.
+    #[test]
+    fn test_textured_sprites() {
+        create_test_game("RUNNING test_textured_sprites");
+
+        const WHITE: [u8; 4] = [255, 255, 255, 255];
+        const BLUE: [u8; 4] = [0, 0, 255, 255];
+        const CLEAR: [u8; 4] = [0, 0, 0, 0];
+        let checkerboard = texture::Texture::new(&texture::Image::new(
+            4,
+            4,
+            (0..16)
+                .map(|i| match (i, (i % 4 + i / 4) % 2) {
+                    (0, _) => CLEAR,
+                    (_, 0) => WHITE,
+                    _ => BLUE,
+                })
+                .collect(),
+        ));
+
+        let mut frame = framebuffer::Framebuffer::new(2, 2);
+        frame.fill_rect(0.0, 0.0, 1.0, 2.0, [0, 255, 0]);
+        let path = std::env::temp_dir().join(format!("textured_sprites_{}.png", std::process::id()));
+        snapshot::save_png(&frame, &path).unwrap();
+        let loaded = LOAD_TEXTURE!(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((loaded.width(), loaded.height()), (2, 2));
+
+        let whole = SPAWN_TEXTURED_SPRITE!(false, 100.0, 150.0, 120, 120, checkerboard);
+        let mut part = SPAWN_TEXTURED_SPRITE!(false, 300.0, 150.0, 120, 120, checkerboard);
+        part.set_source(texture::SourceRect::new(2, 2, 2, 2));
+        part.set_color(255, 0, 0);
+        let from_file = SPAWN_TEXTURED_SPRITE!(false, 500.0, 150.0, 120, 120, loaded);
+        let solid = DUPE_SPRITE!(
+            SPAWN_SPRITE!(false, 0.0, 0.0, 120, 120, 255, 255, 0),
+            100.0,
+            350.0
+        );
+        assert!(solid.texture().is_none());
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            whole.render();
+            part.render();
+            from_file.render();
+            solid.render();
+        });
+
+        check_snapshot("textured_sprites");
+        if headless_run() {
+            let frame = headless::framebuffer();
+            // the transparent corner leaves the background visible
+            assert_eq!(frame.pixel(110, 160), Some([0, 0, 0]));
+            assert_eq!(frame.pixel(140, 160), Some([0, 0, 255]));
+            assert_eq!(frame.pixel(170, 160), Some([255, 255, 255]));
+            assert_eq!(frame.pixel(310, 160), Some([255, 0, 0]));
+            assert_eq!(frame.pixel(370, 160), Some([0, 0, 0]));
+        }
+    }
+
+    /// test_sprite_animation: Plays a four frames sprite sheet on three sprites, looping,
+    /// ping-ponging and once, advanced by a fixed timestep loop.
+    #[test]
+    fn test_sprite_animation() {
+        create_test_game("RUNNING test_sprite_animation");
+
+        let sheet = animation::SpriteSheet::new(
+            &texture::Texture::new(&texture::Image::new(
+                2,
+                2,
+                vec![[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255], [255, 255, 255, 255]],
+            )),
+            1,
+            1,
+        );
+        let frame = time::Duration::from_millis(100);
+        let mut animations = [
+            sheet.animation(0..4, frame, animation::PlayMode::Loop),
+            sheet.animation(0..4, frame, animation::PlayMode::PingPong),
+            sheet.animation(0..4, frame, animation::PlayMode::Once),
+        ];
+        let mut sprites: Vec<_> = (0..3)
+            .map(|i| SPAWN_TEXTURED_SPRITE!(false, 100.0 + 200.0 * i as f32, 150.0, 120, 120, sheet.texture()))
+            .collect();
+
+        // Main loop
+        START_FIXED_TIMESTEP_LOOP!(FixedTimestep::from_rate(100), |dt| {
+            for (sprite, animation) in sprites.iter_mut().zip(animations.iter_mut()) {
+                ANIMATE_SPRITE!(*sprite, animation, dt);
+            }
+        }, |_alpha| {
+            CLEAR_SCREEN!();
+            for sprite in &sprites {
+                sprite.render();
+            }
+        });
+
+        if headless_run() {
+            // 81 updates of 10ms: 8 frames were shown to the end
+            let frames: Vec<_> = animations.iter().map(|a| a.frame_index()).collect();
+            assert_eq!(frames, vec![0, 2, 3]);
+            assert!(animations[2].is_finished());
+            check_snapshot("sprite_animation");
+        }
+    }
+
+    /// test_layers_and_opacity: Sprites are rendered in the wrong order but drawn by
+    /// layer: a blue sprite on layer 1 over a red one, a half transparent green sprite on
+    /// layer 2 over both, and a text above all of them on the text layer.
+    #[test]
+    fn test_layers_and_opacity() {
+        create_test_game("RUNNING test_layers_and_opacity");
+
+        let mut top = SPAWN_SPRITE!(false, 100.0, 150.0, 120, 120, 0, 0, 255);
+        top.set_layer(1);
+        let bottom = SPAWN_SPRITE!(false, 160.0, 210.0, 120, 120, 255, 0, 0);
+        let mut glass = SPAWN_SPRITE!(false, 60.0, 250.0, 300, 60, 0, 255, 0);
+        glass.set_layer(2);
+        glass.set_opacity(0.5);
+        assert_eq!(glass.color(), [0, 255, 0, 128]);
+        let faded = DUPE_SPRITE!(glass, 400.0, 150.0);
+        assert_eq!((faded.layer(), faded.opacity()), (2, glass.opacity()));
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            TEXT_RENDER!("layers", 110.0, 180.0, 1.0, 1.0, 1.0, 1.0);
+            glass.render();
+            faded.render();
+            top.render();
+            bottom.render();
+        });
+
+        check_snapshot("layers_and_opacity");
+        if headless_run() {
+            let frame = headless::framebuffer();
+            assert_eq!(frame.pixel(200, 200), Some([0, 0, 255]));
+            assert_eq!(frame.pixel(250, 240), Some([255, 0, 0]));
+            assert_eq!(frame.pixel(200, 260), Some([0, 128, 127]));
+            assert_eq!(frame.pixel(240, 280), Some([127, 128, 0]));
+            assert_eq!(frame.pixel(80, 280), Some([0, 128, 0]));
+            // the text is drawn over the sprites
+            assert_eq!(frame.pixel(121, 176), Some([255, 255, 255]));
+        }
+    }
+
+    /// test_sprite_transforms: Draws a square rotated by 45 degrees around its center, a
+    /// two color texture turned a quarter clockwise, a sprite scaled twice from its
+    /// top-left corner, and the texture mirrored horizontally.
+    #[test]
+    fn test_sprite_transforms() {
+        create_test_game("RUNNING test_sprite_transforms");
+
+        // red on the left half, green on the right one
+        let halves = texture::Texture::new(&texture::Image::new(
+            2,
+            1,
+            vec![[255, 0, 0, 255], [0, 255, 0, 255]],
+        ));
+
+        let mut diamond = SPAWN_SPRITE!(false, 100.0, 150.0, 100, 100, 255, 255, 0);
+        ROTATE_SPRITE!(diamond, 45.0);
+        let mut turned = SPAWN_TEXTURED_SPRITE!(false, 300.0, 150.0, 100, 100, halves);
+        ROTATE_SPRITE!(turned, 90.0);
+        let mut grown = SPAWN_SPRITE!(false, 100.0, 350.0, 50, 50, 0, 0, 255);
+        grown.set_origin(0.0, 0.0);
+        SCALE_SPRITE!(grown, 2.0);
+        let mirrored = {
+            let mut sprite = DUPE_SPRITE!(turned, 500.0, 150.0);
+            ROTATE_SPRITE!(sprite, 0.0);
+            SCALE_SPRITE!(sprite, -1.0, 1.0);
+            sprite
+        };
+        assert_eq!(SPRITE_ROTATION!(diamond), 45.0);
+        assert_eq!(SPRITE_SCALE!(mirrored), (-1.0, 1.0));
+        assert_eq!(SPRITE_ORIGIN!(grown), (0.0, 0.0));
+        assert_eq!(SPRITE_ORIGIN!(mirrored), (0.5, 0.5));
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            diamond.render();
+            turned.render();
+            grown.render();
+            mirrored.render();
+        });
+
+        check_snapshot("sprite_transforms");
+        if headless_run() {
+            let frame = headless::framebuffer();
+            // the corners of the diamond stick out of the square
+            assert_eq!(frame.pixel(150, 140), Some([255, 255, 0]));
+            assert_eq!(frame.pixel(105, 155), Some([0, 0, 0]));
+            // the left half went to the top
+            assert_eq!(frame.pixel(350, 160), Some([255, 0, 0]));
+            assert_eq!(frame.pixel(350, 240), Some([0, 255, 0]));
+            assert_eq!(frame.pixel(195, 445), Some([0, 0, 255]));
+            assert_eq!(frame.pixel(510, 200), Some([0, 255, 0]));
+            assert_eq!(frame.pixel(590, 200), Some([255, 0, 0]));
+        }
+    }
+
+    /// test_shapes: Draws primitive shapes: a filled circle, an outlined circle, a rounded
+    /// rectangle with an outline, a thick diagonal line, a triangle and translucent grid
+    /// lines over all of them.
+    #[test]
+    fn test_shapes() {
+        create_test_game("RUNNING test_shapes");
+
+        let triangle = shape::Shape::polygon(vec![(600.0, 150.0), (700.0, 300.0), (500.0, 300.0)])
+            .with_color(0, 255, 255);
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            DRAW_CIRCLE!(150.0, 200.0, 50.0, 255, 0, 0);
+            shape::Shape::circle(350.0, 200.0, 50.0)
+                .with_color(0, 255, 0)
+                .with_outline(4.0)
+                .render();
+            let rounded = shape::Shape::rounded_rect(100.0, 350.0, 200.0, 100.0, 30.0);
+            rounded.clone().with_color(0, 0, 255).render();
+            rounded.with_color(255, 255, 255).with_outline(2.0).with_layer(1).render();
+            DRAW_LINE!(350.0, 350.0, 450.0, 450.0, 10.0, 255, 255, 0);
+            triangle.render();
+            for x in (0..WINDOW.width).step_by(100) {
+                shape::Shape::line(x as f32, 0.0, x as f32, WINDOW.height as f32, 1.0)
+                    .with_rgba(255, 255, 255, 64)
+                    .with_layer(2)
+                    .render();
+            }
+        });
+
+        check_snapshot("shapes");
+        if headless_run() {
+            let frame = headless::framebuffer();
+            assert_eq!(frame.pixel(150, 200), Some([255, 0, 0]));
+            // only the edge of the outlined circle is drawn
+            assert_eq!(frame.pixel(350, 200), Some([0, 0, 0]));
+            assert_eq!(frame.pixel(398, 200), Some([0, 255, 0]));
+            // the rounded corner leaves the background visible
+            assert_eq!(frame.pixel(103, 353), Some([0, 0, 0]));
+            assert_eq!(frame.pixel(200, 400), Some([0, 0, 255]));
+            assert_eq!(frame.pixel(200, 350), Some([255, 255, 255]));
+            assert_eq!(frame.pixel(401, 401), Some([255, 255, 0]));
+            assert_eq!(frame.pixel(600, 250), Some([0, 255, 255]));
+            // the grid is blended over the shapes
+            assert_eq!(frame.pixel(99, 250), Some([64, 64, 64]));
+            assert_eq!(frame.pixel(199, 400), Some([64, 64, 255]));
+            assert_eq!(frame.pixel(299, 100), Some([64, 64, 64]));
+        }
+    }
+
+    /// test_camera: A yellow player crosses a world larger than the window, followed by a
+    /// zoomed-in camera. The world has gray grid lines every 100 units and a rotated blue
+    /// square, and a white bar is drawn on screen over it as a HUD.
+    #[test]
+    fn test_camera() {
+        create_test_game("RUNNING test_camera");
+
+        camera::set(
+            camera::Camera::new(0.0, 0.0)
+                .with_zoom(2.0)
+                .with_bounds(0.0, 0.0, 2000.0, 1500.0),
+        );
+        let mut player = SPAWN_SPRITE!(false, 100.0, 100.0, 40, 40, 255, 255, 0);
+        player.set_layer(1);
+        let mut marker = SPAWN_SPRITE!(false, 500.0, 300.0, 30, 30, 0, 0, 255);
+        ROTATE_SPRITE!(marker, 45.0);
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            let (x, y) = (SPRITE_X!(player) + 10.0, SPRITE_Y!(player) + 5.0);
+            MOVE_SPRITE!(false, false, player, x, y);
+            camera::with_camera(|camera| {
+                camera.follow(x + 20.0, y + 20.0);
+                camera.update(1.0 / 60.0);
+            });
+
+            let (left, top, width, height) = camera::visible_area();
+            for line in 0..=20 {
+                let at = line as f32 * 100.0;
+                shape::Shape::line(at, top, at, top + height, 2.0)
+                    .with_color(96, 96, 96)
+                    .render();
+                shape::Shape::line(left, at, left + width, at, 2.0)
+                    .with_color(96, 96, 96)
+                    .render();
+            }
+            marker.render();
+            player.render();
+            camera::on_screen(|| shape::Shape::rect(10.0, 10.0, 200.0, 20.0).render());
+        });
+
+        check_snapshot("camera");
+        if headless_run() {
+            let (x, y) = (SPRITE_X!(player), SPRITE_Y!(player));
+            // the player is in the middle of the window, twice as big
+            assert_eq!(camera::to_screen(x + 20.0, y + 20.0), (400.0, 300.0));
+            // so does the mouse cursor over it
+            headless::set_cursor_position(400.0, 300.0);
+            let (cursor_x, cursor_y) = input::cursor_position();
+            assert_eq!(camera::to_world(cursor_x, cursor_y), (x + 20.0, y + 20.0));
+
+            let frame = headless::framebuffer();
+            assert_eq!(frame.pixel(400, 300), Some([255, 255, 0]));
+            // over the grid line under its left edge
+            assert_eq!(frame.pixel(361, 300), Some([255, 255, 0]));
+            assert_eq!(frame.pixel(100, 20), Some([255, 255, 255]));
+            let (marker_x, marker_y) = camera::to_screen(515.0, 315.0);
+            assert_eq!(
+                frame.pixel(marker_x as i32, marker_y as i32),
+                Some([0, 0, 255])
+            );
+        }
+    }
+
+    /// test_fonts: Draws text with a TrueType font: a small red and a large green
+    /// "GAME OVER", accented and Greek letters in white, and two translucent blue lines.
+    #[test]
+    fn test_fonts() {
+        create_test_game("RUNNING test_fonts");
+
+        let font = LOAD_FONT!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/fonts/DejaVuSans.ttf"
+        ))
+        .expect("the font file is missing");
+        font::set_default(Some(font.clone()));
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            TEXT_RENDER!("GAME OVER", 20.0, 40.0, 1.0, 1.0, 0.0, 0.0);
+            TEXT_RENDER!("GAME OVER", 20.0, 160.0, 4.0, 0.0, 1.0, 0.0);
+            TEXT_RENDER!("Größe – λόγος ★", 20.0, 260.0, 2.0, 1.0, 1.0, 1.0);
+            font.draw(0, "line one\nline two", 20.0, 340.0, 32.0, [0, 0, 255, 255]);
+            let sprite = SPAWN_SPRITE!(false, 100.0, 220.0, 100, 160, 255, 255, 0);
+            sprite.render();
+        });
+
+        check_snapshot("fonts");
+        if headless_run() {
+            let frame = headless::framebuffer();
+            let extent = |color: [u8; 3]| {
+                let columns: Vec<i32> = (0..WINDOW.width)
+                    .filter(|&x| (0..WINDOW.height).any(|y| frame.pixel(x, y) == Some(color)))
+                    .collect();
+                columns.last().unwrap_or(&0) - columns.first().unwrap_or(&0)
+            };
+            // the scale changes the size of the text
+            let (small, large) = (extent([255, 0, 0]), extent([0, 255, 0]));
+            assert!(small > 60 && (large as f32 / small as f32 - 4.0).abs() < 0.25);
+            assert!(extent([255, 255, 255]) > 200);
+            // the text is drawn over the sprites of the default layer, the blue lines under
+            // the sprite drawn after them
+            let found = |color: [u8; 3], columns: std::ops::Range<i32>, rows: std::ops::Range<i32>| {
+                columns
+                    .flat_map(|x| rows.clone().map(move |y| (x, y)))
+                    .any(|(x, y)| frame.pixel(x, y) == Some(color))
+            };
+            assert!(found([255, 255, 255], 100..200, 220..270));
+            assert!(found([0, 0, 255], 20..100, 300..380));
+            assert!(!found([0, 0, 255], 100..200, 300..380));
+        }
+        font::set_default(None);
+    }
+
+    /// test_text_layout: Draws "Left", "Center" and "Right" aligned in three gray boxes, a
+    /// paragraph wrapped in a blue box and two lines in a yellow box fitting them.
+    #[test]
+    fn test_text_layout() {
+        use text::{Align, TextBox, VerticalAlign};
+
+        create_test_game("RUNNING test_text_layout");
+
+        let aligned = |text: &str, x: f32, align: Align| {
+            TextBox::new(text, x, 20.0)
+                .with_width(240.0)
+                .with_height(60.0)
+                .with_align(align)
+                .with_vertical_align(VerticalAlign::Middle)
+                .with_padding(10.0)
+                .with_scale(1.5)
+                .with_background(60, 60, 60, 255)
+        };
+        let paragraph = TextBox::new(
+            "The quick brown fox jumps over the lazy dog.\nA second paragraph, wrapped at the width of its box.",
+            20.0,
+            120.0,
+        )
+        .with_width(360.0)
+        .with_padding(12.0)
+        .with_background(0, 0, 128, 255);
+        let fitted = TextBox::new("fits\nthe text", 420.0, 120.0)
+            .with_padding(8.0)
+            .with_color(0, 0, 0)
+            .with_background(200, 200, 0, 255);
+        assert_eq!(paragraph.lines().len(), 4);
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            aligned("Left", 20.0, Align::Left).with_color(255, 0, 0).render();
+            aligned("Center", 280.0, Align::Center).with_color(0, 255, 0).render();
+            aligned("Right", 540.0, Align::Right).with_color(0, 0, 255).render();
+            paragraph.render();
+            fitted.render();
+        });
+
+        check_snapshot("text_layout");
+        if headless_run() {
+            let frame = headless::framebuffer();
+            let columns = |color: [u8; 3]| {
+                let columns: Vec<i32> = (0..WINDOW.width)
+                    .filter(|&x| (20..80).any(|y| frame.pixel(x, y) == Some(color)))
+                    .collect();
+                (columns[0], columns[columns.len() - 1])
+            };
+            // the text is aligned inside the padding of its box
+            let (left, _) = columns([255, 0, 0]);
+            assert!((30..34).contains(&left));
+            let (center_left, center_right) = columns([0, 255, 0]);
+            assert!(((center_left + center_right) / 2 - 400).abs() <= 2);
+            let (_, right) = columns([0, 0, 255]);
+            assert!((766..=770).contains(&right));
+
+            // the background fits the text and its padding
+            let (width, height) = fitted.size();
+            let (right, bottom) = (420 + width as i32, 120 + height as i32);
+            assert_eq!(frame.pixel(right - 1, bottom - 1), Some([200, 200, 0]));
+            assert_eq!(frame.pixel(right + 1, bottom - 1), Some([0, 0, 0]));
+            assert_eq!(frame.pixel(right - 1, bottom + 1), Some([0, 0, 0]));
+        }
+    }
+
+    /// test_audio: Loops a 440 Hz tone as quiet music and plays a higher beep over it every
+    /// 20 frames. Headless runs record them to a WAV file instead of playing them.
+    #[test]
+    fn test_audio() {
+        use audio::{AudioBackend, Channel, Sound};
+
+        create_test_game("RUNNING test_audio");
+        let recording = std::env::temp_dir().join(format!(
+            "my_game_engine_test_audio_{}.wav",
+            std::process::id()
+        ));
+        audio::select_backend(match headless_run() {
+            true => AudioBackend::WaveFile(recording.clone()),
+            false => AudioBackend::from_env(),
+        });
+
+        let music = LOAD_SOUND!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/sounds/sine_440hz_stereo.ogg"))
+            .expect("the test sound is missing");
+        let beep: Vec<f32> = (0..4000)
+            .map(|i| (i as f32 * std::f32::consts::TAU * 880.0 / 8000.0).sin())
+            .collect();
+        let beep = Sound::from_samples(1, 8000, beep);
+        audio::set_channel_volume(Channel::Music, 0.25);
+        PLAY_MUSIC!(music);
+
+        // Main loop
+        let mut frame = 0;
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            frame += 1;
+            if frame % 20 == 0 {
+                PLAY_SOUND!(beep, 0.5);
+            }
+        });
+        // closes the recording
+        audio::select_backend(AudioBackend::Null);
+        let recorded = Sound::load(&recording);
+        std::fs::remove_file(&recording).ok();
+
+        if headless_run() {
+            let recorded = recorded.expect("nothing was recorded");
+            assert_eq!((recorded.channels(), recorded.sample_rate()), (2, 44100));
+            // recorded from the first frame to the last one
+            let duration = headless::DEFAULT_FRAME_TIME * (HEADLESS_TEST_FRAMES as u32 - 1);
+            assert!((recorded.duration().as_secs_f64() - duration.as_secs_f64()).abs() < 0.001);
+
+            // only the music plays until the first beep
+            let peak = |samples: &[f32]| samples.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+            let before_beep = &recorded.samples()[..2 * 44100 / 4];
+            assert!((peak(before_beep) - 0.25).abs() < 0.02);
+            assert!(peak(recorded.samples()) > 0.6);
+        }
+    }
+
+    /// test_tilemap: Scrolls a camera over a 2000x2000 map of red and green texture tiles,
+    /// blue solid tiles and empty cells. Only the tiles in view are drawn.
+    #[test]
+    fn test_tilemap() {
+        use tilemap::{Tile, TileMap, Tileset, EMPTY};
+
+        create_test_game("RUNNING test_tilemap");
+
+        // one red and one green tile
+        let sheet = texture::Texture::new(&texture::Image::new(
+            2,
+            1,
+            vec![[255, 0, 0, 255], [0, 255, 0, 255]],
+        ));
+        let mut tileset = Tileset::from_sheet(&sheet, 1, 1);
+        let wall = tileset.add(Tile::color(0, 0, 255).with_solid(true));
+        let mut map = TileMap::new(100, 100, 20, 20, tileset);
+        for row in 0..map.rows() {
+            for column in 0..map.columns() {
+                let id = match (column * row) % 7 {
+                    0 => EMPTY,
+                    _ => (column + row) as u16 % wall + 1,
+                };
+                map.set(column, row, id);
+            }
+        }
+        let visible = (5..35)
+            .flat_map(|row| (5..45).map(move |column| (column, row)))
+            .filter(|&(column, row)| map.get(column, row) != Some(EMPTY))
+            .count() as u32;
+        camera::set(camera::Camera::new(500.0, 400.0));
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            map.render();
+        });
+
+        check_snapshot("tilemap");
+        assert_eq!(stats::last_frame().sprites_drawn, visible);
+        assert_eq!(map.tile_at(100.0, 100.0), Some(2));
+        assert!(map.is_solid_at(130.0, 100.0));
+        if headless_run() {
+            // cell (5, 5) at the top-left corner of the window, then (6, 5) and (6, 6)
+            let frame = headless::framebuffer();
+            assert_eq!(frame.pixel(0, 0), Some([0, 255, 0]));
+            assert_eq!(frame.pixel(39, 19), Some([0, 0, 255]));
+            assert_eq!(frame.pixel(30, 30), Some([255, 0, 0]));
+            // cells (7, 5) and (7, 7) are empty
+            assert_eq!(frame.pixel(50, 0), Some([0, 0, 0]));
+            assert_eq!(frame.pixel(50, 50), Some([0, 0, 0]));
+        }
+    }
+
+    /// test_particles: A fountain of blue particles shot up and falling back, over a budget
+    /// of 150, next to a burst of sparks fading from yellow to transparent red.
+    #[test]
+    fn test_particles() {
+        use particles::Emitter;
+
+        create_test_game("RUNNING test_particles");
+
+        let mut fountain = Emitter::new(250.0, 450.0)
+            .with_rate(200.0)
+            .with_direction(-90.0, 30.0)
+            .with_speed(250.0, 350.0)
+            .with_gravity(0.0, 400.0)
+            .with_lifetime(1.5, 2.0)
+            .with_colors([0, 128, 255, 255], [0, 0, 255, 255])
+            .with_sizes(6.0, 3.0)
+            .with_budget(150)
+            .with_seed(1);
+        let mut sparks = Emitter::new(550.0, 300.0)
+            .with_speed(50.0, 200.0)
+            .with_lifetime(1.0, 1.5)
+            .with_colors([255, 255, 0, 255], [255, 0, 0, 0])
+            .with_sizes(8.0, 2.0)
+            .with_layer(1)
+            .with_seed(2);
+        sparks.burst(100);
+
+        // Main loop
+        START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
+            CLEAR_SCREEN!();
+            fountain.update(1.0 / 60.0);
+            sparks.update(1.0 / 60.0);
+            fountain.render();
+            sparks.render();
+        });
+
+        check_snapshot("particles");
+        assert!(fountain.len() <= 150);
+        let drawn = (fountain.len() + sparks.len()) as u32;
+        assert_eq!(stats::last_frame().sprites_drawn, drawn);
+        if headless_run() {
+            assert_eq!(fountain.len(), 150);
+            // none of the sparks died yet
+            assert_eq!(sparks.len(), 100);
+        }
     }
 }
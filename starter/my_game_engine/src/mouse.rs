@@ -0,0 +1,102 @@
+//! mouse.rs
+//!
+//! Typed mouse buttons and mouse events, with their GLFW codes. Positions are in logical
+//! coordinates, origin at the top-left corner like sprite positions.
+
+use std::os::raw::c_int;
+
+use crate::input::Modifiers;
+use crate::key::KeyState;
+
+/// A mouse button. Discriminants are the GLFW button codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum MouseButton {
+    Left = 0,
+    Right = 1,
+    Middle = 2,
+    Button4 = 3,
+    Button5 = 4,
+    Button6 = 5,
+    Button7 = 6,
+    Button8 = 7,
+}
+
+impl MouseButton {
+    /// Every button, in GLFW code order
+    pub const ALL: [MouseButton; 8] = [
+        MouseButton::Left,
+        MouseButton::Right,
+        MouseButton::Middle,
+        MouseButton::Button4,
+        MouseButton::Button5,
+        MouseButton::Button6,
+        MouseButton::Button7,
+        MouseButton::Button8,
+    ];
+
+    /// Button for a GLFW button code, `None` for unknown buttons
+    pub fn from_glfw(code: c_int) -> Option<MouseButton> {
+        MouseButton::ALL
+            .iter()
+            .copied()
+            .find(|button| button.to_glfw() == code)
+    }
+
+    /// GLFW button code of the button
+    pub fn to_glfw(self) -> c_int {
+        self as c_int
+    }
+}
+
+/// Something that happened to the mouse
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MouseEvent {
+    /// A button went down with the cursor at `x`, `y`
+    ButtonDown {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+        modifiers: Modifiers,
+    },
+    /// A button was released with the cursor at `x`, `y`
+    ButtonUp {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+        modifiers: Modifiers,
+    },
+    /// The wheel or touchpad scrolled. Positive `dy` scrolls up, positive `dx` right.
+    Scroll { dx: f32, dy: f32 },
+}
+
+impl MouseEvent {
+    /// Event from the arguments of a GLFW mouse button callback, `None` for unknown
+    /// buttons or actions
+    pub fn from_glfw(
+        button: c_int,
+        action: c_int,
+        mods: c_int,
+        x: f64,
+        y: f64,
+    ) -> Option<MouseEvent> {
+        let button = MouseButton::from_glfw(button)?;
+        let modifiers = Modifiers::from_glfw(mods);
+        let (x, y) = (x as f32, y as f32);
+        match KeyState::from_glfw(action)? {
+            KeyState::Pressed => Some(MouseEvent::ButtonDown {
+                button,
+                x,
+                y,
+                modifiers,
+            }),
+            KeyState::Released => Some(MouseEvent::ButtonUp {
+                button,
+                x,
+                y,
+                modifiers,
+            }),
+            KeyState::Repeat => None,
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! opengl.rs
+//!
+//! The OpenGL `Renderer`, a thin layer over the `C` wrapper library in `game_ffi`.
+//!
+//! The library batches sprites and polygons into a vertex buffer, sent to the GPU once per
+//! frame or whenever the texture changes. Drawing a layer of sprites sharing a texture,
+//! like the body of a snake, is a single draw call.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_void, CString};
+use std::os::raw::c_int;
+use std::ptr::NonNull;
+use std::time::Duration;
+
+use crate::framebuffer::Framebuffer;
+use crate::game_ffi::{self, Sprite};
+use crate::input::{self, KeyEvent};
+use crate::key::{Key, KeyState};
+use crate::mouse::{MouseButton, MouseEvent};
+use crate::renderer::Renderer;
+use crate::texture::Image;
+use crate::window::{self, Viewport, WindowId};
+
+/// Renders through the OpenGL wrapper library in GLFW windows
+#[derive(Default)]
+pub struct OpenGlRenderer {
+    windows: HashMap<WindowId, *mut c_void>,
+    current: Option<WindowId>,
+}
+
+impl OpenGlRenderer {
+    /// The GLFW window drawn to
+    fn current_window(&self) -> *mut c_void {
+        let id = self.current.expect("the game window must be created first");
+        self.windows[&id]
+    }
+}
+
+thread_local! {
+    /// The ids of the GLFW windows, for the callbacks to tell which window an event
+    /// happened in
+    static WINDOW_IDS: RefCell<HashMap<usize, WindowId>> = RefCell::new(HashMap::new());
+}
+
+fn window_id(window: *mut c_void) -> Option<WindowId> {
+    WINDOW_IDS.with(|ids| ids.borrow().get(&(window as usize)).copied())
+}
+
+/// `C` strings can't hold nul bytes, drop them rather than failing
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).expect("CString::new failed")
+}
+
+/// Called by GLFW from `update_game_window` on the thread owning the window
+extern "C" fn on_key_event(
+    window: *mut c_void,
+    key: c_int,
+    _scancode: c_int,
+    action: c_int,
+    mods: c_int,
+) {
+    if let (Some(id), Some(event)) = (window_id(window), KeyEvent::from_glfw(key, action, mods)) {
+        input::push_key_event(id, event);
+    }
+}
+
+/// Called by GLFW from `update_game_window` on the thread owning the window
+extern "C" fn on_mouse_button_event(
+    window: *mut c_void,
+    button: c_int,
+    action: c_int,
+    mods: c_int,
+    x: f64,
+    y: f64,
+) {
+    let Some(id) = window_id(window) else {
+        return;
+    };
+    let (x, y) = window::to_logical(id, x as f32, y as f32);
+    if let Some(event) = MouseEvent::from_glfw(button, action, mods, x.into(), y.into()) {
+        input::push_mouse_event(id, event);
+    }
+}
+
+/// Called by GLFW from `update_game_window` on the thread owning the window
+extern "C" fn on_scroll_event(window: *mut c_void, dx: f64, dy: f64) {
+    if let Some(id) = window_id(window) {
+        input::push_mouse_event(
+            id,
+            MouseEvent::Scroll {
+                dx: dx as f32,
+                dy: dy as f32,
+            },
+        );
+    }
+}
+
+/// Called by GLFW from `update_game_window` on the thread owning the window
+extern "C" fn on_resize_event(window: *mut c_void, width: c_int, height: c_int) {
+    if let Some(id) = window_id(window) {
+        window::handle_resize(id, width, height);
+    }
+}
+
+impl Renderer for OpenGlRenderer {
+    fn create_window(&mut self, id: WindowId, title: &str, width: i32, height: i32) {
+        let title = c_string(title);
+        let window = unsafe { game_ffi::create_game_window(title.as_ptr(), width, height) };
+        WINDOW_IDS.with(|ids| ids.borrow_mut().insert(window as usize, id));
+        unsafe {
+            game_ffi::set_key_callback(window, Some(on_key_event));
+            game_ffi::set_mouse_callbacks(
+                window,
+                Some(on_mouse_button_event),
+                Some(on_scroll_event),
+            );
+            game_ffi::set_resize_callback(window, Some(on_resize_event));
+        }
+        self.windows.insert(id, window);
+        self.current = Some(id);
+    }
+
+    fn destroy_window(&mut self, id: WindowId) {
+        let Some(window) = self.windows.remove(&id) else {
+            return;
+        };
+        WINDOW_IDS.with(|ids| ids.borrow_mut().remove(&(window as usize)));
+        unsafe {
+            game_ffi::destroy_game_window(window);
+        }
+        if self.current == Some(id) {
+            self.current = None;
+        }
+    }
+
+    fn make_current(&mut self, id: WindowId) {
+        if let Some(&window) = self.windows.get(&id) {
+            unsafe {
+                game_ffi::make_window_current(window);
+            }
+            self.current = Some(id);
+        }
+    }
+
+    fn set_viewport(&mut self, id: WindowId, viewport: Viewport, logical_size: (i32, i32)) {
+        let Some(&window) = self.windows.get(&id) else {
+            return;
+        };
+        let (logical_width, logical_height) = logical_size;
+        unsafe {
+            game_ffi::set_viewport(
+                window,
+                viewport.x,
+                viewport.y,
+                viewport.width,
+                viewport.height,
+                logical_width,
+                logical_height,
+            );
+        }
+    }
+
+    fn update_window(&mut self) {
+        unsafe {
+            game_ffi::update_game_window(self.current_window());
+        }
+    }
+
+    fn window_should_close(&self) -> bool {
+        unsafe { game_ffi::window_should_close(self.current_window()) != 0 }
+    }
+
+    fn clear_screen(&mut self) {
+        unsafe {
+            game_ffi::clear_screen(self.current_window());
+        }
+    }
+
+    fn create_sprite(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: i32,
+        height: i32,
+        color: [i32; 4],
+    ) -> NonNull<Sprite> {
+        let [r, g, b, a] = color;
+        let sprite = unsafe { game_ffi::create_sprite(x, y, width, height, r, g, b) };
+        let mut sprite = NonNull::new(sprite).expect("create_sprite returned a null sprite");
+        unsafe { sprite.as_mut() }.color[3] = a;
+        sprite
+    }
+
+    unsafe fn destroy_sprite(&mut self, sprite: NonNull<Sprite>) {
+        game_ffi::destroy_sprite(sprite.as_ptr());
+    }
+
+    fn render_sprite(&mut self, sprite: &Sprite) {
+        // the C library only reads the sprite
+        unsafe {
+            game_ffi::render_sprite(
+                self.current_window(),
+                sprite as *const Sprite as *mut Sprite,
+            );
+        }
+    }
+
+    fn render_polygon(&mut self, points: &[(f32, f32)], color: [i32; 4]) {
+        let coordinates: Vec<f32> = points.iter().flat_map(|&(x, y)| [x, y]).collect();
+        let [r, g, b, a] = color;
+        unsafe {
+            game_ffi::render_polygon(
+                self.current_window(),
+                coordinates.as_ptr(),
+                points.len() as i32,
+                r,
+                g,
+                b,
+                a,
+            );
+        }
+    }
+
+    fn create_texture(&mut self, image: &Image) -> u32 {
+        let pixels: Vec<u8> = image.pixels().iter().flatten().copied().collect();
+        unsafe { game_ffi::create_texture(pixels.as_ptr(), image.width(), image.height()) }
+    }
+
+    fn destroy_texture(&mut self, texture: u32) {
+        unsafe {
+            game_ffi::destroy_texture(texture);
+        }
+    }
+
+    fn max_texture_size(&self) -> i32 {
+        unsafe { game_ffi::get_max_texture_size() }
+    }
+
+    fn key_state(&self, key: Key) -> KeyState {
+        let action = unsafe { game_ffi::get_key(self.current_window(), key.to_glfw()) };
+        KeyState::from_glfw(action).unwrap_or_default()
+    }
+
+    fn cursor_position(&self) -> (f32, f32) {
+        let (mut x, mut y) = (0.0, 0.0);
+        unsafe {
+            game_ffi::get_cursor_position(self.current_window(), &mut x, &mut y);
+        }
+        (x as f32, y as f32)
+    }
+
+    fn mouse_button_pressed(&self, button: MouseButton) -> bool {
+        let state = unsafe { game_ffi::get_mouse_button(self.current_window(), button.to_glfw()) };
+        state == game_ffi::GLFW_PRESS
+    }
+
+    fn time(&self) -> Duration {
+        let seconds = unsafe { game_ffi::get_time() };
+        Duration::try_from_secs_f64(seconds).unwrap_or_default()
+    }
+
+    fn capture_frame(&mut self) -> Framebuffer {
+        let (mut width, mut height) = (0, 0);
+        unsafe {
+            game_ffi::get_window_size(self.current_window(), &mut width, &mut height);
+        }
+
+        let row_len = width.max(0) as usize * 3;
+        let mut rgb = vec![0u8; row_len * height.max(0) as usize];
+        unsafe {
+            game_ffi::read_pixels(self.current_window(), rgb.as_mut_ptr(), width, height);
+        }
+
+        // OpenGL returns the bottom row first
+        let rows: Vec<&[u8]> = rgb.chunks_exact(row_len.max(1)).rev().collect();
+        Framebuffer::from_rgb(width, height, &rows.concat())
+    }
+}
@@ -0,0 +1,390 @@
+//! particles.rs
+//!
+//! Particle effects: sparks, smoke, explosions. An `Emitter` spawns particles at its
+//! position, steadily at a rate or in bursts, each one flying away at a random speed and
+//! angle, pulled by gravity, and changing color and size until the end of its life.
+//!
+//! Particles are small squares, or a texture such as a soft dot, drawn in one go when
+//! the emitter is rendered without allocating a sprite for each of them. An emitter never
+//! has more particles alive than its budget.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+use crate::game_ffi::Sprite;
+use crate::layer;
+use crate::stats;
+use crate::texture::Texture;
+
+/// Particles an emitter can have alive at once, unless changed with `with_budget`
+pub const DEFAULT_BUDGET: usize = 1000;
+
+/// A particle in flight
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    /// Position of the center of the particle
+    pub x: f32,
+    pub y: f32,
+    /// Velocity in pixels per second
+    pub vx: f32,
+    pub vy: f32,
+    /// Seconds since the particle was spawned
+    pub age: f32,
+    /// Seconds the particle lives for
+    pub lifetime: f32,
+}
+
+impl Particle {
+    /// How far the particle is in its life, from 0 when spawned to 1 when it dies
+    pub fn progress(&self) -> f32 {
+        (self.age / self.lifetime).clamp(0.0, 1.0)
+    }
+}
+
+/// Spawns, moves and draws particles
+#[derive(Clone, Debug)]
+pub struct Emitter {
+    x: f32,
+    y: f32,
+    /// Particles spawned every second while emitting
+    rate: f32,
+    emitting: bool,
+    /// Particles owed from previous updates, when an update lasts less than a particle
+    pending: f32,
+    /// Shortest and longest lives, in seconds
+    lifetime: (f32, f32),
+    /// Slowest and fastest speeds, in pixels per second
+    speed: (f32, f32),
+    /// Mean direction of the particles, and how far from it they may go, in degrees
+    /// clockwise from the right
+    direction: f32,
+    spread: f32,
+    /// Acceleration in pixels per second squared
+    gravity: (f32, f32),
+    /// RGBA colors at the start and at the end of the life of the particles
+    colors: ([i32; 4], [i32; 4]),
+    /// Sides at the start and at the end of the life of the particles
+    sizes: (f32, f32),
+    budget: usize,
+    layer: i32,
+    texture: Option<Texture>,
+    particles: Vec<Particle>,
+    rng: Rng,
+}
+
+impl Emitter {
+    /// An emitter at position `x`, `y` only spawning particles in bursts, white particles
+    /// fading out after a second and flying in all directions
+    pub fn new(x: f32, y: f32) -> Emitter {
+        Emitter {
+            x,
+            y,
+            rate: 0.0,
+            emitting: true,
+            pending: 0.0,
+            lifetime: (1.0, 1.0),
+            speed: (50.0, 100.0),
+            direction: 0.0,
+            spread: 360.0,
+            gravity: (0.0, 0.0),
+            colors: ([255, 255, 255, 255], [255, 255, 255, 0]),
+            sizes: (4.0, 4.0),
+            budget: DEFAULT_BUDGET,
+            layer: layer::DEFAULT_LAYER,
+            texture: None,
+            particles: Vec::new(),
+            rng: Rng::new(RandomState::new().hash_one(0)),
+        }
+    }
+
+    /// Spawn `per_second` particles every second, until stopped
+    pub fn with_rate(mut self, per_second: f32) -> Emitter {
+        self.rate = per_second.max(0.0);
+        self
+    }
+
+    /// Let each particle live between `min` and `max` seconds
+    pub fn with_lifetime(mut self, min: f32, max: f32) -> Emitter {
+        self.lifetime = (min.max(f32::EPSILON), max.max(min).max(f32::EPSILON));
+        self
+    }
+
+    /// Launch particles between `min` and `max` pixels per second
+    pub fn with_speed(mut self, min: f32, max: f32) -> Emitter {
+        self.speed = (min, max.max(min));
+        self
+    }
+
+    /// Launch particles towards `degrees`, clockwise from the right, up to `spread / 2`
+    /// degrees away from it. A spread of 360 launches them in all directions.
+    pub fn with_direction(mut self, degrees: f32, spread: f32) -> Emitter {
+        self.direction = degrees;
+        self.spread = spread.clamp(0.0, 360.0);
+        self
+    }
+
+    /// Accelerate particles by `x`, `y` pixels per second squared, e.g. down to make them
+    /// fall
+    pub fn with_gravity(mut self, x: f32, y: f32) -> Emitter {
+        self.gravity = (x, y);
+        self
+    }
+
+    /// Fade the particles from the RGBA color `start` to `end` during their life
+    pub fn with_colors(mut self, start: [i32; 4], end: [i32; 4]) -> Emitter {
+        self.colors = (start, end);
+        self
+    }
+
+    /// Grow or shrink the particles from `start` to `end` pixels wide during their life
+    pub fn with_sizes(mut self, start: f32, end: f32) -> Emitter {
+        self.sizes = (start.max(0.0), end.max(0.0));
+        self
+    }
+
+    /// Never have more than `budget` particles alive, further ones aren't spawned
+    pub fn with_budget(mut self, budget: usize) -> Emitter {
+        self.budget = budget;
+        self
+    }
+
+    /// Draw the particles with a texture tinted with their color, instead of squares
+    pub fn with_texture(mut self, texture: &Texture) -> Emitter {
+        self.texture = Some(texture.clone());
+        self
+    }
+
+    /// Draw the particles on another layer than the default one
+    pub fn with_layer(mut self, layer: i32) -> Emitter {
+        self.layer = layer;
+        self
+    }
+
+    /// Make the random speeds, directions and lives the same on every run
+    pub fn with_seed(mut self, seed: u64) -> Emitter {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn position(&self) -> (f32, f32) {
+        (self.x, self.y)
+    }
+
+    /// Spawn the next particles at `x`, `y`. Particles in flight aren't moved.
+    pub fn set_position(&mut self, x: f32, y: f32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    /// Start spawning particles at the rate of the emitter again
+    pub fn start(&mut self) {
+        self.emitting = true;
+    }
+
+    /// Stop spawning particles at the rate of the emitter, those in flight live on
+    pub fn stop(&mut self) {
+        self.emitting = false;
+        self.pending = 0.0;
+    }
+
+    pub fn is_emitting(&self) -> bool {
+        self.emitting && self.rate > 0.0
+    }
+
+    /// The particles alive
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Number of particles alive
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Whether the emitter has nothing left to show: no particle alive, none to come
+    pub fn is_finished(&self) -> bool {
+        self.is_empty() && !self.is_emitting()
+    }
+
+    /// Spawn `count` particles at once, within the budget
+    pub fn burst(&mut self, count: usize) {
+        let count = count.min(self.budget.saturating_sub(self.particles.len()));
+        for _ in 0..count {
+            let particle = self.spawn();
+            self.particles.push(particle);
+        }
+    }
+
+    /// Age and move the particles for `dt` seconds, then spawn the particles due
+    pub fn update(&mut self, dt: f32) {
+        let (gravity_x, gravity_y) = self.gravity;
+        self.particles.retain_mut(|particle| {
+            particle.age += dt;
+            particle.vx += gravity_x * dt;
+            particle.vy += gravity_y * dt;
+            particle.x += particle.vx * dt;
+            particle.y += particle.vy * dt;
+            particle.age < particle.lifetime
+        });
+
+        if self.is_emitting() {
+            self.pending += self.rate * dt;
+            let due = self.pending.floor();
+            self.pending -= due;
+            self.burst(due as usize);
+        }
+    }
+
+    /// Queue the particles on the layer of the emitter, as they are now
+    pub fn render(&self) {
+        let texture = self.texture.as_ref();
+        for particle in &self.particles {
+            let progress = particle.progress();
+            let size = lerp(self.sizes.0, self.sizes.1, progress);
+            let (start, end) = self.colors;
+            let color =
+                [0, 1, 2, 3].map(|i| lerp(start[i] as f32, end[i] as f32, progress).round() as i32);
+            // a pixel scaled around its center, for sizes between pixels
+            let sprite = Sprite {
+                width: 1,
+                height: 1,
+                color,
+                x: particle.x - 0.5,
+                y: particle.y - 0.5,
+                texture: texture.map_or(0, Texture::id),
+                tex_coords: [0.0, 0.0, 1.0, 1.0],
+                rotation: 0.0,
+                scale: [size, size],
+                origin: [0.5, 0.5],
+            };
+            layer::draw_sprite(self.layer, &sprite, texture);
+            stats::count_sprite_draw();
+        }
+    }
+
+    /// A new particle at the position of the emitter
+    fn spawn(&mut self) -> Particle {
+        let speed = self.rng.range(self.speed.0, self.speed.1);
+        let half_spread = self.spread / 2.0;
+        let angle = self.direction + self.rng.range(-half_spread, half_spread);
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Particle {
+            x: self.x,
+            y: self.y,
+            vx: speed * cos,
+            vy: speed * sin,
+            age: 0.0,
+            lifetime: self.rng.range(self.lifetime.0, self.lifetime.1),
+        }
+    }
+}
+
+fn lerp(start: f32, end: f32, progress: f32) -> f32 {
+    start + (end - start) * progress
+}
+
+/// Small xorshift generator, plenty for scattering particles
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // the state must never be 0
+        Rng(seed | 1)
+    }
+
+    /// A number from 0 to 1, 1 excluded
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawns_at_its_rate_within_the_budget() {
+        let mut emitter = Emitter::new(0.0, 0.0)
+            .with_rate(100.0)
+            .with_lifetime(10.0, 10.0)
+            .with_budget(25);
+        emitter.update(0.005);
+        assert_eq!(emitter.len(), 0);
+        // half a particle is owed from the previous update
+        emitter.update(0.1);
+        assert_eq!(emitter.len(), 10);
+        emitter.update(1.0);
+        assert_eq!(emitter.len(), 25);
+        emitter.burst(10);
+        assert_eq!(emitter.len(), 25);
+
+        emitter.stop();
+        assert!(!emitter.is_emitting() && !emitter.is_finished());
+        emitter.update(10.0);
+        assert!(emitter.is_finished());
+    }
+
+    #[test]
+    fn particles_fly_and_fall() {
+        let mut emitter = Emitter::new(100.0, 50.0)
+            .with_speed(10.0, 10.0)
+            .with_direction(90.0, 0.0)
+            .with_gravity(0.0, 20.0)
+            .with_lifetime(1.0, 2.0)
+            .with_seed(7);
+        emitter.burst(50);
+        emitter.set_position(0.0, 0.0);
+        emitter.update(0.5);
+
+        for particle in emitter.particles() {
+            // straight down, and faster
+            assert!((particle.x - 100.0).abs() < 1e-3);
+            assert!((particle.y - 60.0).abs() < 1e-3);
+            assert!((particle.vy - 20.0).abs() < 1e-3);
+            assert!((1.0..2.0).contains(&particle.lifetime));
+        }
+        // the shortest lives are over
+        emitter.update(1.0);
+        assert!(!emitter.is_empty() && emitter.len() < 50);
+        assert!(emitter.particles().iter().all(|p| p.lifetime > 1.5));
+        assert!(
+            (emitter.particles()[0].progress() - 1.5 / emitter.particles()[0].lifetime).abs()
+                < 1e-5
+        );
+    }
+
+    #[test]
+    fn directions_stay_within_the_spread() {
+        let mut emitter = Emitter::new(0.0, 0.0)
+            .with_speed(50.0, 100.0)
+            .with_direction(-90.0, 60.0)
+            .with_seed(42);
+        emitter.burst(200);
+        for particle in emitter.particles() {
+            let angle = particle.vy.atan2(particle.vx).to_degrees();
+            let speed = particle.vx.hypot(particle.vy);
+            // up to rounding errors
+            assert!((-120.01..=-59.99).contains(&angle), "{angle}");
+            assert!((49.99..=100.01).contains(&speed), "{speed}");
+        }
+        // the same seed scatters them the same way
+        let mut again = Emitter::new(0.0, 0.0)
+            .with_speed(50.0, 100.0)
+            .with_direction(-90.0, 60.0)
+            .with_seed(42);
+        again.burst(200);
+        assert_eq!(emitter.particles(), again.particles());
+    }
+}
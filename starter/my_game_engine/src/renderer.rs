@@ -0,0 +1,247 @@
+//! renderer.rs
+//!
+//! The `Renderer` trait abstracts the platform the engine draws on: window lifecycle,
+//! sprites, polygons, textures, clearing and input polling. All game macros go through the
+//! renderer selected at startup instead of calling a backend directly. Text is drawn with
+//! textured sprites, see `font`.
+//!
+//! As with an OpenGL context, the active renderer belongs to the thread that selected it.
+//! Renderers can open several windows. Everything but the window lifecycle acts on the
+//! current window, chosen with `make_current`.
+
+use std::cell::{Cell, RefCell};
+use std::ptr::NonNull;
+use std::time::Duration;
+
+use crate::font;
+use crate::framebuffer::Framebuffer;
+use crate::game_ffi::Sprite;
+use crate::headless::HeadlessRenderer;
+use crate::key::{Key, KeyState};
+use crate::mouse::MouseButton;
+#[cfg(feature = "opengl")]
+use crate::opengl::OpenGlRenderer;
+use crate::texture::Image;
+use crate::window::{Viewport, WindowId};
+
+/// Environment variable used to pick a backend at startup, `opengl` or `headless`
+pub const BACKEND_ENV: &str = "GAME_ENGINE_BACKEND";
+
+/// A platform the engine can render on and read input from
+pub trait Renderer {
+    /// Open a game window identified by `id`, which becomes the current window
+    fn create_window(&mut self, id: WindowId, title: &str, width: i32, height: i32);
+
+    /// Close a window. The current window is left unchanged unless it was `id`.
+    fn destroy_window(&mut self, id: WindowId);
+
+    /// Make `id` the target of the next calls
+    fn make_current(&mut self, id: WindowId);
+
+    /// Draw a `logical_size` area into `viewport` of window `id`, from now on
+    fn set_viewport(&mut self, id: WindowId, viewport: Viewport, logical_size: (i32, i32));
+
+    /// Present the frame of the current window and process pending events of all windows
+    fn update_window(&mut self);
+
+    /// Whether the current window was asked to close
+    fn window_should_close(&self) -> bool;
+
+    /// Clear the whole current window
+    fn clear_screen(&mut self);
+
+    /// Allocate a sprite, to be released with `destroy_sprite`
+    fn create_sprite(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: i32,
+        height: i32,
+        color: [i32; 4],
+    ) -> NonNull<Sprite>;
+
+    /// Release a sprite
+    ///
+    /// # Safety
+    /// `sprite` must come from `create_sprite` on this renderer and not be used afterwards
+    unsafe fn destroy_sprite(&mut self, sprite: NonNull<Sprite>);
+
+    /// Draw a sprite, textured when its `texture` is not 0, blended with what is behind it
+    fn render_sprite(&mut self, sprite: &Sprite);
+
+    /// Fill a convex polygon with an RGBA color, blended with what is behind it
+    fn render_polygon(&mut self, points: &[(f32, f32)], color: [i32; 4]);
+
+    /// Upload an image for textured sprites, returning a texture id other than 0
+    fn create_texture(&mut self, image: &Image) -> u32;
+
+    /// Release a texture. Sprites must not draw it anymore.
+    fn destroy_texture(&mut self, texture: u32);
+
+    /// Largest width and height of a texture
+    fn max_texture_size(&self) -> i32;
+
+    /// Current state of a keyboard key
+    fn key_state(&self, key: Key) -> KeyState;
+
+    /// Current position of the mouse cursor in window pixels
+    fn cursor_position(&self) -> (f32, f32);
+
+    /// Whether a mouse button is currently held down
+    fn mouse_button_pressed(&self, button: MouseButton) -> bool;
+
+    /// Time elapsed since the window was created, the clock of fixed-timestep loops
+    fn time(&self) -> Duration;
+
+    /// Read back what was drawn so far in the current frame
+    fn capture_frame(&mut self) -> Framebuffer;
+}
+
+/// The renderers built into the engine
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    /// The OpenGL wrapper library, only available with the `opengl` feature
+    #[cfg(feature = "opengl")]
+    OpenGl,
+    /// In-memory framebuffer, no display needed
+    Headless,
+}
+
+impl Backend {
+    /// Backend named by the `GAME_ENGINE_BACKEND` environment variable, falling back to
+    /// OpenGL when it is compiled in and headless otherwise
+    pub fn from_env() -> Backend {
+        match std::env::var(BACKEND_ENV).as_deref() {
+            Ok("headless") => Backend::Headless,
+            #[cfg(feature = "opengl")]
+            Ok("opengl") => Backend::OpenGl,
+            Ok(other) => {
+                println!("Unknown {BACKEND_ENV}={other}, using the default backend");
+                Backend::default()
+            }
+            Err(_) => Backend::default(),
+        }
+    }
+
+    fn create(self) -> Box<dyn Renderer> {
+        match self {
+            #[cfg(feature = "opengl")]
+            Backend::OpenGl => Box::new(OpenGlRenderer::default()),
+            Backend::Headless => Box::new(HeadlessRenderer),
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Backend {
+        #[cfg(feature = "opengl")]
+        return Backend::OpenGl;
+        #[cfg(not(feature = "opengl"))]
+        return Backend::Headless;
+    }
+}
+
+thread_local! {
+    static RENDERER: RefCell<Option<Box<dyn Renderer>>> = const { RefCell::new(None) };
+    /// Sprites allocated by the renderer and not destroyed yet
+    static LIVE_SPRITES: Cell<usize> = const { Cell::new(0) };
+    /// Textures created by the renderer and not destroyed yet
+    static LIVE_TEXTURES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Select one of the built-in backends. Must be called before the game window is created,
+/// and panics while sprites or textures of the previous backend are alive, see
+/// `install_renderer`.
+pub fn select_backend(backend: Backend) {
+    install_renderer(backend.create());
+}
+
+/// Use a custom renderer, e.g. a mock in tests. Replaces any previously selected renderer.
+///
+/// Panics while sprites or textures allocated by the previous renderer are alive: only the
+/// renderer that allocated them can destroy them. The glyph atlases of the built-in and
+/// default fonts are uploaded again with the new renderer.
+pub fn install_renderer(renderer: Box<dyn Renderer>) {
+    font::release_textures();
+    let live_sprites = LIVE_SPRITES.with(Cell::get);
+    assert!(
+        live_sprites == 0,
+        "can't replace the renderer while {live_sprites} sprites it allocated are alive"
+    );
+    let live_textures = LIVE_TEXTURES.with(Cell::get);
+    assert!(
+        live_textures == 0,
+        "can't replace the renderer while {live_textures} textures it created are alive"
+    );
+    RENDERER.with(|current| *current.borrow_mut() = Some(renderer));
+}
+
+/// Run a closure against the active renderer. When none was selected, the backend
+/// from `Backend::from_env` is used.
+pub fn with_renderer<T>(f: impl FnOnce(&mut dyn Renderer) -> T) -> T {
+    try_with_renderer(f).expect("the renderer was dropped with its thread")
+}
+
+/// Like `with_renderer`, but returns `None` once the renderer of the thread was dropped,
+/// e.g. when resources held by other thread locals are released at thread exit
+pub(crate) fn try_with_renderer<T>(f: impl FnOnce(&mut dyn Renderer) -> T) -> Option<T> {
+    RENDERER
+        .try_with(|current| {
+            let mut current = current.borrow_mut();
+            let renderer = current.get_or_insert_with(|| Backend::from_env().create());
+            f(renderer.as_mut())
+        })
+        .ok()
+}
+
+/// Count a sprite allocated by the renderer, until `sprite_destroyed`
+pub(crate) fn sprite_created() {
+    LIVE_SPRITES.with(|sprites| sprites.set(sprites.get() + 1));
+}
+
+pub(crate) fn sprite_destroyed() {
+    // the count may be gone when a sprite is released at thread exit
+    let _ = LIVE_SPRITES.try_with(|sprites| sprites.set(sprites.get() - 1));
+}
+
+/// Count a texture created by the renderer, until `texture_destroyed`
+pub(crate) fn texture_created() {
+    LIVE_TEXTURES.with(|textures| textures.set(textures.get() + 1));
+}
+
+pub(crate) fn texture_destroyed() {
+    // the count may be gone when a texture is released at thread exit
+    let _ = LIVE_TEXTURES.try_with(|textures| textures.set(textures.get() - 1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprite::SpriteHandle;
+    use crate::texture::{Image, Texture};
+
+    #[test]
+    fn refuses_to_swap_renderers_under_live_sprites() {
+        select_backend(Backend::Headless);
+        let sprite = SpriteHandle::new(0.0, 0.0, 10, 10, 255, 0, 0);
+        // the new renderer would destroy a sprite it didn't allocate
+        assert!(std::panic::catch_unwind(|| select_backend(Backend::Headless)).is_err());
+
+        drop(sprite);
+        select_backend(Backend::Headless);
+        assert_eq!(LIVE_SPRITES.with(Cell::get), 0);
+    }
+
+    #[test]
+    fn refuses_to_swap_renderers_under_live_textures() {
+        select_backend(Backend::Headless);
+        let texture = Texture::new(&Image::new(1, 1, vec![[255; 4]]));
+        // the new renderer would destroy a texture it didn't create, or one of its own
+        // with the same id
+        assert!(std::panic::catch_unwind(|| select_backend(Backend::Headless)).is_err());
+
+        drop(texture);
+        select_backend(Backend::Headless);
+        assert_eq!(LIVE_TEXTURES.with(Cell::get), 0);
+    }
+}
@@ -0,0 +1,256 @@
+//! shape.rs
+//!
+//! Primitive shapes: circles, rectangles with rounded corners, line segments and convex
+//! polygons, filled or outlined. Shapes are cut into convex polygons that the renderer
+//! fills, and are drawn on layers like sprites.
+
+use std::f32::consts::TAU;
+
+use crate::layer;
+
+/// A shape in logical coordinates, with the color and layer to draw it with
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shape {
+    /// Convex outline of the shape, clockwise on the screen
+    points: Vec<(f32, f32)>,
+    /// Width of the outline, `None` to fill the shape
+    outline: Option<f32>,
+    color: [i32; 4],
+    layer: i32,
+}
+
+impl Shape {
+    /// A convex polygon through `points`, in order. Concave polygons are not supported.
+    pub fn polygon(mut points: Vec<(f32, f32)>) -> Shape {
+        // repeated points, e.g. where the arcs of a rounded rectangle meet, would make
+        // empty outline edges
+        points.dedup_by(|a, b| same_point(*a, *b));
+        if points.len() > 1 && same_point(points[0], points[points.len() - 1]) {
+            points.pop();
+        }
+        Shape {
+            points,
+            outline: None,
+            color: [255, 255, 255, 255],
+            layer: layer::DEFAULT_LAYER,
+        }
+    }
+
+    /// A circle of `radius` around `x`, `y`, smooth enough for its size
+    pub fn circle(x: f32, y: f32, radius: f32) -> Shape {
+        let segments = arc_segments(radius) * 4;
+        Shape::polygon(
+            (0..segments)
+                .map(|i| {
+                    let (sin, cos) = (TAU * i as f32 / segments as f32).sin_cos();
+                    (x + radius * cos, y + radius * sin)
+                })
+                .collect(),
+        )
+    }
+
+    /// A rectangle with its top-left corner at `x`, `y`
+    pub fn rect(x: f32, y: f32, width: f32, height: f32) -> Shape {
+        Shape::polygon(vec![
+            (x, y),
+            (x + width, y),
+            (x + width, y + height),
+            (x, y + height),
+        ])
+    }
+
+    /// A rectangle with corners rounded by `radius`, limited to half of its smaller side
+    pub fn rounded_rect(x: f32, y: f32, width: f32, height: f32, radius: f32) -> Shape {
+        let radius = radius.clamp(0.0, width.min(height) / 2.0);
+        if radius == 0.0 {
+            return Shape::rect(x, y, width, height);
+        }
+
+        let segments = arc_segments(radius);
+        // centers of the corner arcs, clockwise from the top-right one
+        let corners = [
+            (x + width - radius, y + radius),
+            (x + width - radius, y + height - radius),
+            (x + radius, y + height - radius),
+            (x + radius, y + radius),
+        ];
+        let points = corners
+            .iter()
+            .enumerate()
+            .flat_map(|(corner, &(center_x, center_y))| {
+                (0..=segments).map(move |i| {
+                    let angle = TAU / 4.0 * (corner as f32 - 1.0 + i as f32 / segments as f32);
+                    let (sin, cos) = angle.sin_cos();
+                    (center_x + radius * cos, center_y + radius * sin)
+                })
+            })
+            .collect();
+        Shape::polygon(points)
+    }
+
+    /// A line segment from `x1`, `y1` to `x2`, `y2`, `width` wide
+    pub fn line(x1: f32, y1: f32, x2: f32, y2: f32, width: f32) -> Shape {
+        Shape::polygon(segment((x1, y1), (x2, y2), width, 0.0).to_vec())
+    }
+
+    /// Use an opaque color
+    pub fn with_color(self, r: i32, g: i32, b: i32) -> Shape {
+        self.with_rgba(r, g, b, 255)
+    }
+
+    /// Use a color with an alpha, 255 being opaque
+    pub fn with_rgba(mut self, r: i32, g: i32, b: i32, a: i32) -> Shape {
+        self.color = [r, g, b, a];
+        self
+    }
+
+    /// Only draw the outline of the shape, `width` wide and centered on its edges
+    pub fn with_outline(mut self, width: f32) -> Shape {
+        self.outline = Some(width);
+        self
+    }
+
+    /// Draw the shape on another layer than the default one
+    pub fn with_layer(mut self, layer: i32) -> Shape {
+        self.layer = layer;
+        self
+    }
+
+    /// Points of the shape, in order
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points
+    }
+
+    pub fn color(&self) -> [i32; 4] {
+        self.color
+    }
+
+    pub fn layer(&self) -> i32 {
+        self.layer
+    }
+
+    /// The convex polygons covering the shape: itself when filled, or one for each edge
+    /// of its outline
+    pub fn polygons(&self) -> Vec<Vec<(f32, f32)>> {
+        match self.outline {
+            None => vec![self.points.clone()],
+            Some(width) => {
+                // extend each edge by half the width so that corners are covered
+                let next = self.points.iter().cycle().skip(1);
+                self.points
+                    .iter()
+                    .zip(next)
+                    .map(|(&from, &to)| segment(from, to, width, width / 2.0).to_vec())
+                    .collect()
+            }
+        }
+    }
+
+    /// Render the shape on the game window, on its layer
+    pub fn render(&self) {
+        if self.points.len() < 3 && self.outline.is_none() {
+            return;
+        }
+        for polygon in self.polygons() {
+            layer::draw_polygon(self.layer, polygon, self.color);
+        }
+    }
+}
+
+fn same_point(a: (f32, f32), b: (f32, f32)) -> bool {
+    (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+}
+
+/// Number of segments drawing a quarter of a circle of `radius`
+fn arc_segments(radius: f32) -> usize {
+    ((radius.abs().sqrt() * 2.0) as usize).clamp(3, 24)
+}
+
+/// Corners of a `width` wide segment, extended by `cap` at both ends
+fn segment(from: (f32, f32), to: (f32, f32), width: f32, cap: f32) -> [(f32, f32); 4] {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let (dx, dy) = match length > 0.0 {
+        true => (dx / length, dy / length),
+        false => (1.0, 0.0),
+    };
+    // half a width across the segment, and the cap along it
+    let (nx, ny) = (-dy * width / 2.0, dx * width / 2.0);
+    let (cx, cy) = (dx * cap, dy * cap);
+    [
+        (from.0 - cx + nx, from.1 - cy + ny),
+        (from.0 - cx - nx, from.1 - cy - ny),
+        (to.0 + cx - nx, to.1 + cy - ny),
+        (to.0 + cx + nx, to.1 + cy + ny),
+    ]
+}
+
+/// Draw a filled circle of a color around a point
+#[macro_export]
+macro_rules! DRAW_CIRCLE {
+    ($x:expr, $y:expr, $radius:expr, $r:expr, $g:expr, $b:expr) => {{
+        $crate::shape::Shape::circle($x, $y, $radius)
+            .with_color($r, $g, $b)
+            .render();
+    }};
+}
+
+/// Draw a line segment of a width and a color between two points
+#[macro_export]
+macro_rules! DRAW_LINE {
+    ($x1:expr, $y1:expr, $x2:expr, $y2:expr, $width:expr, $r:expr, $g:expr, $b:expr) => {{
+        $crate::shape::Shape::line($x1, $y1, $x2, $y2, $width)
+            .with_color($r, $g, $b)
+            .render();
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn circles_and_rounded_rects() {
+        let circle = Shape::circle(10.0, 20.0, 5.0);
+        assert!(circle.points().len() >= 12);
+        assert_near(circle.points()[0], (15.0, 20.0));
+        for &(x, y) in circle.points() {
+            assert!(((x - 10.0).hypot(y - 20.0) - 5.0).abs() < 1e-4);
+        }
+
+        let rounded = Shape::rounded_rect(0.0, 0.0, 40.0, 20.0, 30.0);
+        // the radius is limited to half the height, the arcs meet on the short sides
+        let points = rounded.points();
+        assert_near(points[0], (30.0, 0.0));
+        assert_near(points[points.len() - 1], (10.0, 0.0));
+        assert_eq!(
+            Shape::rounded_rect(1.0, 2.0, 3.0, 4.0, 0.0),
+            Shape::rect(1.0, 2.0, 3.0, 4.0)
+        );
+    }
+
+    #[test]
+    fn lines_and_outlines() {
+        let line = Shape::line(0.0, 0.0, 10.0, 0.0, 2.0);
+        assert_eq!(
+            line.points(),
+            &[(0.0, 1.0), (0.0, -1.0), (10.0, -1.0), (10.0, 1.0)]
+        );
+
+        let outline = Shape::rect(0.0, 0.0, 10.0, 10.0).with_outline(2.0);
+        let polygons = outline.polygons();
+        assert_eq!(polygons.len(), 4);
+        // the top edge covers the corners
+        assert_eq!(
+            polygons[0],
+            vec![(-1.0, 1.0), (-1.0, -1.0), (11.0, -1.0), (11.0, 1.0)]
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! snapshot.rs
+//!
+//! Golden-image testing. A rendered frame is captured from the active renderer and
+//! compared against a PNG stored next to the tests, within a tolerance.
+//!
+//! Run the tests with `UPDATE_SNAPSHOTS=1` to (re)write the golden images.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::framebuffer::Framebuffer;
+use crate::layer;
+use crate::renderer::with_renderer;
+
+/// Environment variable asking snapshot assertions to overwrite golden images
+pub const UPDATE_SNAPSHOTS_ENV: &str = "UPDATE_SNAPSHOTS";
+
+/// How far a frame may drift from its golden image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /// Largest difference allowed on a single color channel of a pixel
+    pub channel: u8,
+    /// Number of pixels allowed to differ by more than `channel`
+    pub pixels: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Tolerance {
+        Tolerance {
+            channel: 2,
+            pixels: 0,
+        }
+    }
+}
+
+/// Why a frame doesn't match its golden image
+#[derive(Debug, PartialEq)]
+pub enum Mismatch {
+    /// Frames of different sizes can't be compared
+    Size {
+        expected: (i32, i32),
+        actual: (i32, i32),
+    },
+    /// Too many pixels are off
+    Pixels {
+        differing: usize,
+        /// First differing pixel, top-left origin
+        first: (i32, i32),
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Size { expected, actual } => write!(
+                f,
+                "frame is {}x{}, golden image is {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Mismatch::Pixels { differing, first } => write!(
+                f,
+                "{differing} pixels differ, first one at ({}, {})",
+                first.0, first.1
+            ),
+        }
+    }
+}
+
+/// Compare two frames within a tolerance
+pub fn compare(
+    actual: &Framebuffer,
+    expected: &Framebuffer,
+    tolerance: Tolerance,
+) -> Result<(), Mismatch> {
+    if (actual.width(), actual.height()) != (expected.width(), expected.height()) {
+        return Err(Mismatch::Size {
+            expected: (expected.width(), expected.height()),
+            actual: (actual.width(), actual.height()),
+        });
+    }
+
+    let mut differing = actual
+        .pixels()
+        .iter()
+        .zip(expected.pixels())
+        .enumerate()
+        .filter(|(_, (a, e))| {
+            a.iter()
+                .zip(e.iter())
+                .any(|(a, e)| a.abs_diff(*e) > tolerance.channel)
+        })
+        .map(|(i, _)| i);
+
+    let Some(first) = differing.next() else {
+        return Ok(());
+    };
+    let differing = 1 + differing.count();
+
+    match differing <= tolerance.pixels {
+        true => Ok(()),
+        false => Err(Mismatch::Pixels {
+            differing,
+            first: (first as i32 % actual.width(), first as i32 / actual.width()),
+        }),
+    }
+}
+
+/// Write a frame as an RGB PNG
+pub fn save_png(frame: &Framebuffer, path: &Path) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, frame.width() as u32, frame.height() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(&frame.to_rgb())
+        .map_err(io::Error::other)
+}
+
+/// Read an 8 bit RGB or RGBA PNG into a frame, dropping the alpha channel
+pub fn load_png(path: &Path) -> io::Result<Framebuffer> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(io::Error::other)?;
+
+    let channels = match (info.color_type, info.bit_depth) {
+        (png::ColorType::Rgb, png::BitDepth::Eight) => 3,
+        (png::ColorType::Rgba, png::BitDepth::Eight) => 4,
+        (color_type, bit_depth) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported PNG format {color_type:?} {bit_depth:?}"),
+            ))
+        }
+    };
+
+    let rgb: Vec<u8> = buffer[..info.buffer_size()]
+        .chunks_exact(channels)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    Ok(Framebuffer::from_rgb(
+        info.width as i32,
+        info.height as i32,
+        &rgb,
+    ))
+}
+
+/// Compare a frame against the golden image at `golden`. With `UPDATE_SNAPSHOTS` set
+/// the golden image is written instead. On a mismatch the frame is saved next to the
+/// golden image with an `.actual.png` extension and the call panics.
+pub fn assert_frame_matches(frame: &Framebuffer, golden: &Path, tolerance: Tolerance) {
+    if std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some() {
+        if let Some(dir) = golden.parent() {
+            std::fs::create_dir_all(dir).expect("can't create the golden image directory");
+        }
+        save_png(frame, golden).expect("can't write the golden image");
+        return;
+    }
+
+    let expected = load_png(golden).unwrap_or_else(|e| {
+        panic!(
+            "can't read golden image {}: {e}. Run with {UPDATE_SNAPSHOTS_ENV}=1 to create it",
+            golden.display()
+        )
+    });
+
+    if let Err(mismatch) = compare(frame, &expected, tolerance) {
+        let actual = actual_path(golden);
+        let saved = match save_png(frame, &actual) {
+            Ok(()) => format!("frame saved to {}", actual.display()),
+            Err(e) => format!("can't save the frame: {e}"),
+        };
+        panic!(
+            "frame doesn't match golden image {}: {mismatch} ({saved})",
+            golden.display()
+        );
+    }
+}
+
+/// Capture the current frame of the active renderer and compare it against a golden image
+pub fn assert_snapshot(golden: &Path, tolerance: Tolerance) {
+    // include what was rendered but not drawn yet
+    layer::flush();
+    let frame = with_renderer(|renderer| renderer.capture_frame());
+    assert_frame_matches(&frame, golden, tolerance);
+}
+
+/// `name.png` becomes `name.actual.png`
+fn actual_path(golden: &Path) -> PathBuf {
+    golden.with_extension("actual.png")
+}
+
+/// Compare the current frame against `tests/golden/<name>.png` in the calling crate,
+/// optionally with a custom `Tolerance`
+#[macro_export]
+macro_rules! ASSERT_SNAPSHOT {
+    ($name:expr) => {
+        $crate::ASSERT_SNAPSHOT!($name, $crate::snapshot::Tolerance::default())
+    };
+    ($name:expr, $tolerance:expr) => {{
+        let golden = ::std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden")
+            .join(format!("{}.png", $name));
+        $crate::snapshot::assert_snapshot(&golden, $tolerance);
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_within_tolerance() {
+        let mut expected = Framebuffer::new(4, 4);
+        expected.fill_rect(0.0, 0.0, 2.0, 2.0, [200, 0, 0]);
+        let mut actual = Framebuffer::new(4, 4);
+        actual.fill_rect(0.0, 0.0, 2.0, 2.0, [201, 0, 0]);
+
+        assert_eq!(compare(&actual, &expected, Tolerance::default()), Ok(()));
+
+        let exact = Tolerance {
+            channel: 0,
+            pixels: 0,
+        };
+        assert_eq!(
+            compare(&actual, &expected, exact),
+            Err(Mismatch::Pixels {
+                differing: 4,
+                first: (0, 0)
+            })
+        );
+        assert_eq!(
+            compare(&actual, &expected, Tolerance { pixels: 4, ..exact }),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn compare_different_sizes() {
+        assert_eq!(
+            compare(
+                &Framebuffer::new(2, 3),
+                &Framebuffer::new(3, 2),
+                Tolerance::default()
+            ),
+            Err(Mismatch::Size {
+                expected: (3, 2),
+                actual: (2, 3)
+            })
+        );
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let mut frame = Framebuffer::new(5, 3);
+        frame.fill_rect(1.0, 1.0, 4.0, 2.0, [10, 20, 30]);
+
+        let path = std::env::temp_dir().join(format!("png_round_trip_{}.png", std::process::id()));
+        save_png(&frame, &path).unwrap();
+        let loaded = load_png(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, frame);
+    }
+}
@@ -0,0 +1,186 @@
+//! spatial.rs
+//!
+//! A uniform grid indexing items by their bounds, to find what overlaps an area without
+//! testing every item: the broad phase of collision detection. Items are identified by a
+//! key chosen by the game, e.g. the index of an enemy, and are moved cell by cell as
+//! their bounds change.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::collision::Rect;
+
+/// Cells covered by some bounds: first and last column, first and last row
+type CellRange = (i32, i32, i32, i32);
+
+/// Items indexed by bounds in square cells
+#[derive(Clone, Debug)]
+pub struct SpatialGrid<K> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<K>>,
+    items: HashMap<K, (Rect, CellRange)>,
+}
+
+impl<K: Copy + Eq + Hash> SpatialGrid<K> {
+    /// A grid of `cell_size` wide cells, about the size of the items works best
+    pub fn new(cell_size: f32) -> SpatialGrid<K> {
+        assert!(cell_size > 0.0, "grid cells need a size");
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new(),
+            items: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.items.contains_key(&key)
+    }
+
+    /// Bounds an item was indexed with
+    pub fn bounds(&self, key: K) -> Option<Rect> {
+        self.items.get(&key).map(|(bounds, _)| *bounds)
+    }
+
+    /// Index an item, or move it when it already is
+    pub fn insert(&mut self, key: K, bounds: Rect) {
+        let range = self.cell_range(&bounds);
+        let previous = self.items.insert(key, (bounds, range));
+        match previous {
+            // still in the same cells
+            Some((_, previous)) if previous == range => return,
+            Some((_, previous)) => self.remove_from_cells(key, previous),
+            None => {}
+        }
+        for cell in cells(range) {
+            self.cells.entry(cell).or_default().push(key);
+        }
+    }
+
+    /// Move an indexed item, same as `insert`
+    pub fn update(&mut self, key: K, bounds: Rect) {
+        self.insert(key, bounds);
+    }
+
+    /// Remove an item, returning the bounds it had
+    pub fn remove(&mut self, key: K) -> Option<Rect> {
+        let (bounds, range) = self.items.remove(&key)?;
+        self.remove_from_cells(key, range);
+        Some(bounds)
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.items.clear();
+    }
+
+    /// Items whose bounds overlap `area`, see `Rect::intersects`
+    pub fn query(&self, area: &Rect) -> Vec<K> {
+        let mut seen = HashSet::new();
+        let mut found = Vec::new();
+        for cell in cells(self.cell_range(area)) {
+            let Some(keys) = self.cells.get(&cell) else {
+                continue;
+            };
+            for &key in keys {
+                if seen.insert(key) && self.items[&key].0.intersects(area) {
+                    found.push(key);
+                }
+            }
+        }
+        found
+    }
+
+    /// Items whose bounds contain point `x`, `y`
+    pub fn query_point(&self, x: f32, y: f32) -> Vec<K> {
+        let cell = (self.cell(x), self.cell(y));
+        self.cells.get(&cell).map_or(Vec::new(), |keys| {
+            keys.iter()
+                .copied()
+                .filter(|key| self.items[key].0.contains(x, y))
+                .collect()
+        })
+    }
+
+    fn cell(&self, coordinate: f32) -> i32 {
+        (coordinate / self.cell_size).floor() as i32
+    }
+
+    /// Cells covered by `bounds`, the right and bottom edges excluded
+    fn cell_range(&self, bounds: &Rect) -> CellRange {
+        let (left, top) = (self.cell(bounds.x), self.cell(bounds.y));
+        let last = |first: i32, end: f32| ((end / self.cell_size).ceil() as i32 - 1).max(first);
+        (
+            left,
+            last(left, bounds.right()),
+            top,
+            last(top, bounds.bottom()),
+        )
+    }
+
+    fn remove_from_cells(&mut self, key: K, range: CellRange) {
+        for cell in cells(range) {
+            if let Some(keys) = self.cells.get_mut(&cell) {
+                keys.retain(|other| *other != key);
+                if keys.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+}
+
+fn cells((left, right, top, bottom): CellRange) -> impl Iterator<Item = (i32, i32)> {
+    (top..=bottom).flat_map(move |row| (left..=right).map(move |column| (column, row)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_what_overlaps_an_area() {
+        let mut grid = SpatialGrid::new(50.0);
+        grid.insert(1, Rect::new(10.0, 10.0, 20.0, 20.0));
+        // across four cells, one shared with the first item
+        grid.insert(2, Rect::new(40.0, 40.0, 20.0, 20.0));
+        grid.insert(3, Rect::new(-80.0, 200.0, 10.0, 10.0));
+        assert_eq!(grid.len(), 3);
+        assert_eq!(grid.cells.len(), 5);
+
+        assert_eq!(grid.query(&Rect::new(0.0, 0.0, 100.0, 100.0)), [1, 2]);
+        assert_eq!(grid.query(&Rect::new(55.0, 55.0, 1.0, 1.0)), [2]);
+        // same cell, but no overlap
+        assert!(grid.query(&Rect::new(30.0, 0.0, 5.0, 5.0)).is_empty());
+        assert_eq!(grid.query(&Rect::new(-100.0, 150.0, 50.0, 100.0)), [3]);
+        assert_eq!(grid.query_point(45.0, 59.0), [2]);
+        assert!(grid.query_point(30.0, 30.0).is_empty());
+    }
+
+    #[test]
+    fn moves_items_between_cells() {
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert("snake", Rect::new(0.0, 0.0, 10.0, 10.0));
+        assert_eq!(grid.cells.len(), 1);
+        grid.update("snake", Rect::new(2.0, 0.0, 10.0, 10.0));
+        assert_eq!(grid.cells.len(), 2);
+        grid.update("snake", Rect::new(30.0, 30.0, 10.0, 10.0));
+        assert_eq!(grid.cells.keys().collect::<Vec<_>>(), [&(3, 3)]);
+        assert!(grid.query(&Rect::new(0.0, 0.0, 10.0, 10.0)).is_empty());
+        assert_eq!(grid.query(&Rect::new(35.0, 35.0, 1.0, 1.0)), ["snake"]);
+
+        assert_eq!(
+            grid.remove("snake"),
+            Some(Rect::new(30.0, 30.0, 10.0, 10.0))
+        );
+        assert_eq!(grid.remove("snake"), None);
+        assert!(grid.is_empty() && grid.cells.is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+//! sprite.rs
+//!
+//! An owned handle over a backend sprite. The sprite is allocated by `create_sprite` and
+//! released through `destroy_sprite` when the handle goes out of scope.
+//!
+//! Sprites are solid color rectangles, unless given a texture. The sprite color then
+//! tints the texture, white leaving it unchanged. The alpha of the color makes the sprite
+//! translucent, and its layer decides what it is drawn over, see `layer`.
+//!
+//! The position is the top-left corner of the sprite before it is rotated and scaled
+//! around its origin, the center by default.
+
+use std::ptr::NonNull;
+
+use crate::collision::Rect;
+use crate::game_ffi;
+use crate::layer;
+use crate::renderer::{self, try_with_renderer, with_renderer};
+use crate::stats;
+use crate::texture::{SourceRect, Texture};
+
+/// Owned, safe handle to a sprite allocated by the rendering backend
+pub struct SpriteHandle {
+    sprite: NonNull<game_ffi::Sprite>,
+    /// Keeps the texture drawn by the sprite alive, with the part of it drawn
+    texture: Option<(Texture, SourceRect)>,
+    /// Sprites on higher layers are drawn over the ones on lower layers
+    layer: i32,
+}
+
+impl SpriteHandle {
+    /// Create a new opaque sprite at a position with a size and an RGB color, on the
+    /// default layer
+    pub fn new(x: f32, y: f32, width: i32, height: i32, r: i32, g: i32, b: i32) -> SpriteHandle {
+        let sprite =
+            with_renderer(|renderer| renderer.create_sprite(x, y, width, height, [r, g, b, 255]));
+        renderer::sprite_created();
+        SpriteHandle {
+            sprite,
+            texture: None,
+            layer: layer::DEFAULT_LAYER,
+        }
+    }
+
+    /// Create a new sprite drawing a whole texture, stretched to `width` by `height`
+    pub fn textured(x: f32, y: f32, width: i32, height: i32, texture: &Texture) -> SpriteHandle {
+        let mut sprite = SpriteHandle::new(x, y, width, height, 255, 255, 255);
+        sprite.set_texture(texture, None);
+        sprite
+    }
+
+    /// Create a new sprite with the same size, color, texture, transform and layer at a new
+    /// position
+    pub fn duplicate_at(&self, x: f32, y: f32) -> SpriteHandle {
+        let [r, g, b, a] = self.color();
+        let mut sprite = SpriteHandle::new(x, y, self.width(), self.height(), r, g, b);
+        sprite.set_rgba(r, g, b, a);
+        sprite.set_layer(self.layer);
+        let (scale_x, scale_y) = self.scale();
+        let (origin_x, origin_y) = self.origin();
+        sprite.set_rotation(self.rotation());
+        sprite.set_scale(scale_x, scale_y);
+        sprite.set_origin(origin_x, origin_y);
+        if let Some((texture, source)) = &self.texture {
+            sprite.set_texture(texture, Some(*source));
+        }
+        sprite
+    }
+
+    /// Borrow the underlying sprite
+    pub fn sprite(&self) -> &game_ffi::Sprite {
+        unsafe { self.sprite.as_ref() }
+    }
+
+    /// Raw pointer to the underlying sprite, for calls into `game_ffi`.
+    /// The pointer is only valid as long as the handle is alive.
+    pub fn as_ptr(&self) -> *mut game_ffi::Sprite {
+        self.sprite.as_ptr()
+    }
+
+    pub fn x(&self) -> f32 {
+        self.sprite().x
+    }
+
+    pub fn y(&self) -> f32 {
+        self.sprite().y
+    }
+
+    pub fn width(&self) -> i32 {
+        self.sprite().width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.sprite().height
+    }
+
+    /// RGBA color of the sprite
+    pub fn color(&self) -> [i32; 4] {
+        self.sprite().color
+    }
+
+    /// Alpha of the color from 0, invisible, to 1, opaque
+    pub fn opacity(&self) -> f32 {
+        self.sprite().color[3] as f32 / 255.0
+    }
+
+    pub fn layer(&self) -> i32 {
+        self.layer
+    }
+
+    /// Rotation around the origin, in degrees clockwise
+    pub fn rotation(&self) -> f32 {
+        self.sprite().rotation
+    }
+
+    /// Horizontal and vertical scale around the origin
+    pub fn scale(&self) -> (f32, f32) {
+        let [x, y] = self.sprite().scale;
+        (x, y)
+    }
+
+    /// Pivot of rotation and scale, from `(0, 0)` at the top-left corner of the sprite to
+    /// `(1, 1)` at its bottom-right corner
+    pub fn origin(&self) -> (f32, f32) {
+        let [x, y] = self.sprite().origin;
+        (x, y)
+    }
+
+    /// Rectangle covered by the sprite once rotated and scaled, to test collisions
+    pub fn bounds(&self) -> Rect {
+        Rect::of_sprite(self.sprite())
+    }
+
+    /// Move the sprite to a new position without rendering it
+    pub fn set_position(&mut self, x: f32, y: f32) {
+        let sprite = unsafe { self.sprite.as_mut() };
+        sprite.x = x;
+        sprite.y = y;
+    }
+
+    /// Change the color of the sprite, or the tint of its texture, keeping its opacity
+    pub fn set_color(&mut self, r: i32, g: i32, b: i32) {
+        let a = self.sprite().color[3];
+        self.set_rgba(r, g, b, a);
+    }
+
+    /// Change the color of the sprite with an alpha, 255 being opaque
+    pub fn set_rgba(&mut self, r: i32, g: i32, b: i32, a: i32) {
+        unsafe { self.sprite.as_mut() }.color = [r, g, b, a];
+    }
+
+    /// Make the sprite translucent, from 0, invisible, to 1, opaque
+    pub fn set_opacity(&mut self, opacity: f32) {
+        unsafe { self.sprite.as_mut() }.color[3] = (opacity.clamp(0.0, 1.0) * 255.0).round() as i32;
+    }
+
+    /// Turn the sprite around its origin, in degrees clockwise
+    pub fn set_rotation(&mut self, degrees: f32) {
+        unsafe { self.sprite.as_mut() }.rotation = degrees;
+    }
+
+    /// Scale the sprite around its origin, a negative scale mirrors it
+    pub fn set_scale(&mut self, x: f32, y: f32) {
+        unsafe { self.sprite.as_mut() }.scale = [x, y];
+    }
+
+    /// Move the pivot of rotation and scale, from `(0, 0)` at the top-left corner of the
+    /// sprite to `(1, 1)` at its bottom-right corner
+    pub fn set_origin(&mut self, x: f32, y: f32) {
+        unsafe { self.sprite.as_mut() }.origin = [x, y];
+    }
+
+    /// Draw the sprite over the sprites of lower layers, and under the ones of higher layers
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+
+    /// Draw `source`, a part of `texture`, instead of the solid color. `None` draws the
+    /// whole texture.
+    pub fn set_texture(&mut self, texture: &Texture, source: Option<SourceRect>) {
+        let source = source.unwrap_or_else(|| texture.full_rect());
+        let sprite = unsafe { self.sprite.as_mut() };
+        sprite.texture = texture.id();
+        sprite.tex_coords = texture.tex_coords(source);
+        self.texture = Some((texture.clone(), source));
+    }
+
+    /// Draw another part of the current texture. Does nothing on a solid color sprite.
+    pub fn set_source(&mut self, source: SourceRect) {
+        if let Some((texture, _)) = self.texture.take() {
+            self.set_texture(&texture, Some(source));
+        }
+    }
+
+    /// Go back to drawing a solid color
+    pub fn remove_texture(&mut self) {
+        let sprite = unsafe { self.sprite.as_mut() };
+        sprite.texture = 0;
+        sprite.tex_coords = [0.0, 0.0, 1.0, 1.0];
+        self.texture = None;
+    }
+
+    /// The texture drawn by the sprite, `None` for a solid color sprite
+    pub fn texture(&self) -> Option<&Texture> {
+        self.texture.as_ref().map(|(texture, _)| texture)
+    }
+
+    /// The part of the texture drawn by the sprite
+    pub fn source(&self) -> Option<SourceRect> {
+        self.texture.as_ref().map(|(_, source)| *source)
+    }
+
+    /// Render the sprite on the game window, on its layer. The sprite is drawn as it is
+    /// now when the frame is presented.
+    pub fn render(&self) {
+        layer::draw_sprite(self.layer, self.sprite(), self.texture());
+        stats::count_sprite_draw();
+    }
+}
+
+impl Drop for SpriteHandle {
+    fn drop(&mut self) {
+        // the renderer may be gone when a sprite is released at thread exit
+        try_with_renderer(|renderer| unsafe { renderer.destroy_sprite(self.sprite) });
+        renderer::sprite_destroyed();
+    }
+}
@@ -0,0 +1,250 @@
+//! stats.rs
+//!
+//! Frame timing statistics. The game loops tell the stats which phase of the frame they
+//! are in, and the time spent is attributed to it until the next phase starts. Draw calls
+//! are counted as they happen. At the end of each frame the numbers are published as
+//! `FrameStats` and, when enabled, drawn over the game as a text overlay.
+//!
+//! Timings use the wall clock, even on the headless backend.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::layer;
+
+/// Number of frames the frame rate is averaged over
+pub const FPS_AVERAGE_FRAMES: usize = 60;
+
+/// Overlay text color, yellow
+const OVERLAY_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+/// Overlay text scale, the size of the default font
+const OVERLAY_SCALE: f32 = 1.0;
+
+/// What a frame spends its time on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Simulating the game
+    Update,
+    /// Drawing the frame
+    Render,
+    /// Presenting the frame, processing window events and sleeping
+    Idle,
+}
+
+/// Timings and counters of a frame
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// Frames completed so far, this one included
+    pub frame: u64,
+    /// Time since the end of the previous frame
+    pub frame_time: Duration,
+    /// Frames per second, averaged over the last `FPS_AVERAGE_FRAMES` frames
+    pub fps: f32,
+    /// Time spent updating the game
+    pub update_time: Duration,
+    /// Time spent drawing
+    pub render_time: Duration,
+    /// Simulation steps run, several per frame when the game catches up
+    pub updates: u32,
+    /// Sprites drawn
+    pub sprites_drawn: u32,
+    /// Texts drawn
+    pub texts_drawn: u32,
+}
+
+impl FrameStats {
+    /// One line summary, as shown by the overlay
+    pub fn summary(&self) -> String {
+        format!(
+            "fps {:.1} | frame {:.2}ms | update {:.2}ms | render {:.2}ms | sprites {}",
+            self.fps,
+            millis(self.frame_time),
+            millis(self.update_time),
+            millis(self.render_time),
+            self.sprites_drawn
+        )
+    }
+}
+
+fn millis(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+/// Statistics of the frame in progress and of the last completed one
+struct FrameTimer {
+    current: FrameStats,
+    last: FrameStats,
+    phase: Phase,
+    phase_start: Option<Instant>,
+    frame_start: Option<Instant>,
+    recent_frame_times: VecDeque<Duration>,
+    overlay: Option<(f32, f32)>,
+}
+
+impl FrameTimer {
+    fn new() -> FrameTimer {
+        FrameTimer {
+            current: FrameStats::default(),
+            last: FrameStats::default(),
+            phase: Phase::Idle,
+            phase_start: None,
+            frame_start: None,
+            recent_frame_times: VecDeque::with_capacity(FPS_AVERAGE_FRAMES),
+            overlay: None,
+        }
+    }
+
+    /// Attribute the time since the current phase started to it, and start `phase`
+    fn enter(&mut self, phase: Phase, now: Instant) {
+        let elapsed = self
+            .phase_start
+            .map_or(Duration::ZERO, |start| now.saturating_duration_since(start));
+        match self.phase {
+            Phase::Update => self.current.update_time += elapsed,
+            Phase::Render => self.current.render_time += elapsed,
+            Phase::Idle => {}
+        }
+        if phase == Phase::Update {
+            self.current.updates += 1;
+        }
+
+        self.phase = phase;
+        self.phase_start = Some(now);
+        self.frame_start.get_or_insert(now);
+    }
+
+    fn end_frame(&mut self, now: Instant) {
+        self.enter(Phase::Idle, now);
+
+        let frame_start = self.frame_start.replace(now).unwrap_or(now);
+        let frame_time = now.saturating_duration_since(frame_start);
+
+        // a frame ended before anything happened in it only starts the clock
+        if !frame_time.is_zero() {
+            if self.recent_frame_times.len() == FPS_AVERAGE_FRAMES {
+                self.recent_frame_times.pop_front();
+            }
+            self.recent_frame_times.push_back(frame_time);
+        }
+        let total: Duration = self.recent_frame_times.iter().sum();
+        let fps = match total.is_zero() {
+            true => 0.0,
+            false => self.recent_frame_times.len() as f32 / total.as_secs_f32(),
+        };
+
+        self.last = FrameStats {
+            frame: self.last.frame + 1,
+            frame_time,
+            fps,
+            ..self.current
+        };
+        self.current = FrameStats::default();
+    }
+}
+
+thread_local! {
+    static TIMER: RefCell<FrameTimer> = RefCell::new(FrameTimer::new());
+}
+
+/// Start a phase of the frame. Called by the game loops.
+pub fn enter(phase: Phase) {
+    TIMER.with(|timer| timer.borrow_mut().enter(phase, Instant::now()));
+}
+
+/// Publish the statistics of the frame and start the next one. Called by the game loops
+/// once the frame is presented.
+pub fn end_frame() {
+    TIMER.with(|timer| timer.borrow_mut().end_frame(Instant::now()));
+}
+
+/// Count a sprite draw in the current frame
+pub fn count_sprite_draw() {
+    TIMER.with(|timer| timer.borrow_mut().current.sprites_drawn += 1);
+}
+
+/// Count a text draw in the current frame
+pub fn count_text_draw() {
+    TIMER.with(|timer| timer.borrow_mut().current.texts_drawn += 1);
+}
+
+/// Statistics of the last completed frame
+pub fn last_frame() -> FrameStats {
+    TIMER.with(|timer| timer.borrow().last)
+}
+
+/// Draw the statistics of the last frame at the end of every frame, with the text
+/// baseline starting at `x`, `y`
+pub fn show_overlay(x: f32, y: f32) {
+    TIMER.with(|timer| timer.borrow_mut().overlay = Some((x, y)));
+}
+
+/// Stop drawing the statistics overlay
+pub fn hide_overlay() {
+    TIMER.with(|timer| timer.borrow_mut().overlay = None);
+}
+
+/// Whether the statistics overlay is drawn
+pub fn overlay_shown() -> bool {
+    TIMER.with(|timer| timer.borrow().overlay.is_some())
+}
+
+/// Draw the overlay if it is shown. Called by the game loops before presenting the frame.
+pub fn render_overlay() {
+    let Some((x, y)) = TIMER.with(|timer| timer.borrow().overlay) else {
+        return;
+    };
+    let text = last_frame().summary();
+    layer::draw_text(
+        layer::OVERLAY_LAYER,
+        &text,
+        x,
+        y,
+        OVERLAY_SCALE,
+        OVERLAY_COLOR,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn phases_are_timed() {
+        let mut timer = FrameTimer::new();
+        let start = Instant::now();
+
+        timer.enter(Phase::Update, start);
+        timer.enter(Phase::Update, start + 2 * MS);
+        timer.enter(Phase::Render, start + 5 * MS);
+        timer.current.sprites_drawn += 3;
+        timer.end_frame(start + 9 * MS);
+
+        let stats = timer.last;
+        assert_eq!(stats.frame, 1);
+        assert_eq!(stats.frame_time, 9 * MS);
+        assert_eq!(stats.update_time, 5 * MS);
+        assert_eq!(stats.render_time, 4 * MS);
+        assert_eq!(stats.updates, 2);
+        assert_eq!(stats.sprites_drawn, 3);
+        assert_eq!(timer.current, FrameStats::default());
+    }
+
+    #[test]
+    fn fps_is_averaged() {
+        let mut timer = FrameTimer::new();
+        let mut now = Instant::now();
+
+        timer.end_frame(now);
+        for frame_time in [10, 30, 20] {
+            now += frame_time * MS;
+            timer.end_frame(now);
+        }
+
+        // 3 frames in 60ms, the first one only started the clock
+        assert_eq!(timer.last.frame, 4);
+        assert!((timer.last.fps - 50.0).abs() < 0.01);
+    }
+}
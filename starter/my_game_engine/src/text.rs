@@ -0,0 +1,406 @@
+//! text.rs
+//!
+//! Text layout: measuring text, wrapping it at a width and aligning its lines in a
+//! rectangle, optionally filled behind the text. Text is measured with the font it is drawn
+//! with, the default font unless another one is given.
+
+use crate::camera;
+use crate::font::{self, Font};
+use crate::layer;
+use crate::shape::Shape;
+use crate::stats;
+
+/// Size of a line of text in logical pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextMetrics {
+    /// Distance from the start of the line to its end
+    pub width: f32,
+    /// Height above the baseline
+    pub ascent: f32,
+    /// Height below the baseline, down to the top of the next line
+    pub descent: f32,
+}
+
+impl TextMetrics {
+    /// Distance between the baselines of two lines
+    pub fn line_height(&self) -> f32 {
+        self.ascent + self.descent
+    }
+}
+
+/// Where lines go across the width of a text box
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    /// Offset of a line leaving `free` pixels of the width unused
+    fn offset(self, free: f32) -> f32 {
+        match self {
+            Align::Left => 0.0,
+            Align::Center => free / 2.0,
+            Align::Right => free,
+        }
+    }
+}
+
+/// Where the text goes in the height of a text box
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl VerticalAlign {
+    /// Offset of the text leaving `free` pixels of the height unused
+    fn offset(self, free: f32) -> f32 {
+        match self {
+            VerticalAlign::Top => 0.0,
+            VerticalAlign::Middle => free / 2.0,
+            VerticalAlign::Bottom => free,
+        }
+    }
+}
+
+/// The font text is measured and drawn with, at a size
+struct Face {
+    font: Font,
+    size: f32,
+}
+
+impl Face {
+    /// `font` or the default font at `scale`, like `TEXT_RENDER!`
+    fn new(font: Option<Font>, scale: f32) -> Face {
+        Face {
+            font: font.unwrap_or_else(font::default_font),
+            size: font::DEFAULT_SIZE * scale,
+        }
+    }
+
+    fn measure(&self, line: &str) -> TextMetrics {
+        self.font.metrics(line, self.size)
+    }
+
+    fn draw(&self, layer: i32, line: &str, x: f32, y: f32, color: [i32; 4]) {
+        self.font.draw(layer, line, x, y, self.size, color);
+    }
+}
+
+/// Lines of a text box and their widths
+struct Layout {
+    lines: Vec<(String, f32)>,
+    ascent: f32,
+    line_height: f32,
+}
+
+impl Layout {
+    /// Width of the widest line and height of all lines
+    fn size(&self) -> (f32, f32) {
+        let width = self
+            .lines
+            .iter()
+            .map(|(_, width)| *width)
+            .fold(0.0, f32::max);
+        (width, self.lines.len() as f32 * self.line_height)
+    }
+}
+
+/// Text laid out in a rectangle with its top-left corner at `x`, `y`, in window
+/// coordinates. The rectangle fits the text unless given a width or a height.
+#[derive(Clone, Debug)]
+pub struct TextBox {
+    text: String,
+    x: f32,
+    y: f32,
+    width: Option<f32>,
+    height: Option<f32>,
+    align: Align,
+    vertical_align: VerticalAlign,
+    scale: f32,
+    font: Option<Font>,
+    color: [i32; 4],
+    background: Option<[i32; 4]>,
+    /// Space between the text and the edges of the rectangle
+    padding: f32,
+    layer: i32,
+}
+
+impl TextBox {
+    /// White text with its top-left corner at `x`, `y`. Lines are separated by `\n`.
+    pub fn new(text: impl Into<String>, x: f32, y: f32) -> TextBox {
+        TextBox {
+            text: text.into(),
+            x,
+            y,
+            width: None,
+            height: None,
+            align: Align::Left,
+            vertical_align: VerticalAlign::Top,
+            scale: 1.0,
+            font: None,
+            color: [255, 255, 255, 255],
+            background: None,
+            padding: 0.0,
+            layer: layer::TEXT_LAYER,
+        }
+    }
+
+    /// Make the rectangle `width` wide: longer lines are wrapped between words, or
+    /// anywhere in words too long for a line
+    pub fn with_width(mut self, width: f32) -> TextBox {
+        self.width = Some(width);
+        self
+    }
+
+    /// Make the rectangle `height` high, the text may overflow it
+    pub fn with_height(mut self, height: f32) -> TextBox {
+        self.height = Some(height);
+        self
+    }
+
+    pub fn with_align(mut self, align: Align) -> TextBox {
+        self.align = align;
+        self
+    }
+
+    pub fn with_vertical_align(mut self, vertical_align: VerticalAlign) -> TextBox {
+        self.vertical_align = vertical_align;
+        self
+    }
+
+    /// Draw the text `scale` times larger than `font::DEFAULT_SIZE`
+    pub fn with_scale(mut self, scale: f32) -> TextBox {
+        self.scale = scale;
+        self
+    }
+
+    /// Draw the text with `font` instead of the default font
+    pub fn with_font(mut self, font: Font) -> TextBox {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn with_color(self, r: i32, g: i32, b: i32) -> TextBox {
+        self.with_rgba(r, g, b, 255)
+    }
+
+    /// Color of the text, `a` being its opacity
+    pub fn with_rgba(mut self, r: i32, g: i32, b: i32, a: i32) -> TextBox {
+        self.color = [r, g, b, a];
+        self
+    }
+
+    /// Fill the rectangle behind the text
+    pub fn with_background(mut self, r: i32, g: i32, b: i32, a: i32) -> TextBox {
+        self.background = Some([r, g, b, a]);
+        self
+    }
+
+    /// Keep the text `padding` pixels away from the edges of the rectangle
+    pub fn with_padding(mut self, padding: f32) -> TextBox {
+        self.padding = padding;
+        self
+    }
+
+    /// Draw on `layer` instead of `layer::TEXT_LAYER`
+    pub fn with_layer(mut self, layer: i32) -> TextBox {
+        self.layer = layer;
+        self
+    }
+
+    /// The lines drawn, once wrapped
+    pub fn lines(&self) -> Vec<String> {
+        let layout = self.layout(&self.face());
+        layout.lines.into_iter().map(|(line, _)| line).collect()
+    }
+
+    /// Width and height of the rectangle
+    pub fn size(&self) -> (f32, f32) {
+        self.rect_size(&self.layout(&self.face()))
+    }
+
+    /// Render the background and the text on the game window, on the layer of the box
+    pub fn render(&self) {
+        let face = self.face();
+        let layout = self.layout(&face);
+        let (width, height) = self.rect_size(&layout);
+
+        if let Some([r, g, b, a]) = self.background {
+            let background = Shape::rect(self.x, self.y, width, height)
+                .with_rgba(r, g, b, a)
+                .with_layer(self.layer);
+            camera::on_screen(|| background.render());
+        }
+
+        let (text_width, text_height) = layout.size();
+        let inner_width = self
+            .width
+            .map_or(text_width, |width| width - 2.0 * self.padding);
+        let inner_height = height - 2.0 * self.padding;
+        let top = self.y + self.padding + self.vertical_align.offset(inner_height - text_height);
+        for (i, (line, line_width)) in layout.lines.iter().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let x = self.x + self.padding + self.align.offset(inner_width - line_width);
+            let baseline = top + i as f32 * layout.line_height + layout.ascent;
+            face.draw(self.layer, line, x, baseline, self.color);
+        }
+        stats::count_text_draw();
+    }
+
+    fn face(&self) -> Face {
+        Face::new(self.font.clone(), self.scale)
+    }
+
+    /// Split the text into lines, wrapped at the width of the rectangle
+    fn layout(&self, face: &Face) -> Layout {
+        let wrap_width = self.width.map(|width| width - 2.0 * self.padding);
+        let mut lines = Vec::new();
+        for paragraph in self.text.split('\n') {
+            match wrap_width {
+                Some(width) => wrap(face, paragraph, width, &mut lines),
+                None => lines.push(paragraph.to_string()),
+            }
+        }
+
+        let metrics = face.measure("");
+        Layout {
+            lines: lines
+                .into_iter()
+                .map(|line| {
+                    let width = face.measure(&line).width;
+                    (line, width)
+                })
+                .collect(),
+            ascent: metrics.ascent,
+            line_height: metrics.line_height(),
+        }
+    }
+
+    fn rect_size(&self, layout: &Layout) -> (f32, f32) {
+        let (text_width, text_height) = layout.size();
+        (
+            self.width.unwrap_or(text_width + 2.0 * self.padding),
+            self.height.unwrap_or(text_height + 2.0 * self.padding),
+        )
+    }
+}
+
+/// Break a paragraph into lines no wider than `width`, between words when possible
+fn wrap(face: &Face, paragraph: &str, width: f32, lines: &mut Vec<String>) {
+    let mut line = String::new();
+    for word in paragraph.split(' ') {
+        if !line.is_empty() {
+            let candidate = format!("{line} {word}");
+            if face.measure(&candidate).width <= width {
+                line = candidate;
+                continue;
+            }
+            lines.push(std::mem::take(&mut line));
+        }
+        line = word.to_string();
+
+        // words longer than a line are broken anywhere
+        while face.measure(&line).width > width {
+            let split = fitting_prefix(face, &line, width);
+            if split == line.len() {
+                break;
+            }
+            lines.push(line[..split].to_string());
+            line = line[split..].to_string();
+        }
+    }
+    lines.push(line);
+}
+
+/// Length in bytes of the longest start of `line` no wider than `width`, at least a
+/// character
+fn fitting_prefix(face: &Face, line: &str, width: f32) -> usize {
+    let mut ends = line
+        .char_indices()
+        .map(|(i, _)| i)
+        .skip(1)
+        .chain([line.len()]);
+    let mut split = ends.next().unwrap_or(0);
+    for end in ends {
+        if face.measure(&line[..end]).width > width {
+            break;
+        }
+        split = end;
+    }
+    split
+}
+
+/// Width and height of `text` drawn with `TEXT_RENDER!` at `scale`: the width of its
+/// widest line and the height of all its lines
+pub fn measure(text: &str, scale: f32) -> (f32, f32) {
+    TextBox::new(text, 0.0, 0.0).with_scale(scale).size()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{self, Backend};
+
+    fn text_box(text: &str) -> TextBox {
+        renderer::select_backend(Backend::Headless);
+        TextBox::new(text, 0.0, 0.0)
+    }
+
+    /// Metrics of `text` in the built-in font, as drawn with a scale of `scale`
+    fn metrics(text: &str, scale: f32) -> TextMetrics {
+        font::built_in().metrics(text, font::DEFAULT_SIZE * scale)
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        let line_height = metrics("", 1.0).line_height();
+        let width = metrics("the quick", 1.0)
+            .width
+            .max(metrics("brown fox", 1.0).width)
+            .ceil();
+        let text = text_box("the quick brown fox\n\njumps").with_width(width);
+        assert_eq!(text.lines(), ["the quick", "brown fox", "", "jumps"]);
+        assert_eq!(text.size(), (width, 4.0 * line_height));
+
+        // the padding is taken from the width, leaving room for a word per line
+        let widest_word = metrics("quick", 1.0).width.max(metrics("brown", 1.0).width);
+        let padding = ((width - widest_word) / 2.0).floor();
+        let padded = text.with_padding(padding);
+        assert_eq!(
+            padded.lines(),
+            ["the", "quick", "brown", "fox", "", "jumps"]
+        );
+        assert_eq!(padded.size(), (width, 6.0 * line_height + 2.0 * padding));
+
+        let width = metrics("abcde", 1.0).width.ceil();
+        let long_word = text_box("abcdefghijkl mn").with_width(width);
+        let lines = long_word.lines();
+        assert_eq!(lines[0], "abcde");
+        assert_eq!(lines.concat().replace(' ', ""), "abcdefghijklmn");
+        assert!(lines.iter().all(|line| metrics(line, 1.0).width <= width));
+    }
+
+    #[test]
+    fn measures_text() {
+        renderer::select_backend(Backend::Headless);
+        let line_height = metrics("", 1.0).line_height();
+        assert_eq!(
+            measure("ab\nabcd", 1.0),
+            (metrics("abcd", 1.0).width, 2.0 * line_height)
+        );
+        let large = metrics("abc", 2.0);
+        assert_eq!(measure("abc", 2.0), (large.width, large.line_height()));
+        assert_eq!(measure("", 1.0), (0.0, line_height));
+
+        let fixed = text_box("ab").with_width(200.0).with_height(50.0);
+        assert_eq!(fixed.size(), (200.0, 50.0));
+    }
+}
@@ -0,0 +1,441 @@
+//! texture.rs
+//!
+//! Images loaded from PNG or BMP files and uploaded to the renderer as textures. A sprite
+//! can draw a texture, or a part of it, instead of its solid color.
+//!
+//! Textures need a renderer with an open window, so load them after `CREATE_GAME!`.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::renderer::{self, try_with_renderer, with_renderer};
+
+/// An RGBA image, row by row starting from the top-left corner
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    width: i32,
+    height: i32,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+    /// Create an image from its RGBA pixels, row by row starting from the top-left corner
+    pub fn new(width: i32, height: i32, pixels: Vec<[u8; 4]>) -> Image {
+        let expected = (width.max(0) as usize).checked_mul(height.max(0) as usize);
+        assert_eq!(
+            Some(pixels.len()),
+            expected,
+            "expected {width}x{height} RGBA pixels"
+        );
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Read a PNG or BMP file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Image> {
+        Image::decode(&fs::read(path)?)
+    }
+
+    /// Decode the content of a PNG or BMP file, telling them apart by their signature
+    pub fn decode(bytes: &[u8]) -> io::Result<Image> {
+        match bytes {
+            [0x89, b'P', b'N', b'G', ..] => decode_png(bytes),
+            [b'B', b'M', ..] => decode_bmp(bytes),
+            _ => Err(invalid_data("not a PNG or BMP image")),
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// All pixels, row by row starting from the top-left corner
+    pub fn pixels(&self) -> &[[u8; 4]] {
+        &self.pixels
+    }
+
+    /// Color of a pixel, `None` when outside of the image
+    pub fn pixel(&self, x: i32, y: i32) -> Option<[u8; 4]> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.pixels[(y * self.width + x) as usize])
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Decode a PNG of any color type, palettes and 16 bit channels included
+fn decode_png(bytes: &[u8]) -> io::Result<Image> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(io::Error::other)?;
+
+    let buffer = &buffer[..info.buffer_size()];
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]])
+            .collect(),
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => return Err(invalid_data("unexpanded PNG palette")),
+    };
+    Ok(Image::new(info.width as i32, info.height as i32, pixels))
+}
+
+/// Little endian integer at `offset`, `None` past the end of `bytes`
+fn read_le<const N: usize>(bytes: &[u8], offset: usize) -> Option<[u8; N]> {
+    bytes.get(offset..offset + N)?.try_into().ok()
+}
+
+/// Decode an uncompressed BMP: 8 bit with a palette, 24 bit, or 32 bit with or without
+/// bit field masks
+fn decode_bmp(bytes: &[u8]) -> io::Result<Image> {
+    let truncated = || invalid_data("truncated BMP");
+    let u16_at = |offset| {
+        read_le(bytes, offset)
+            .map(u16::from_le_bytes)
+            .ok_or_else(truncated)
+    };
+    let u32_at = |offset| {
+        read_le(bytes, offset)
+            .map(u32::from_le_bytes)
+            .ok_or_else(truncated)
+    };
+    let i32_at = |offset| {
+        read_le(bytes, offset)
+            .map(i32::from_le_bytes)
+            .ok_or_else(truncated)
+    };
+
+    let data_offset = u32_at(10)? as usize;
+    let header_size = u32_at(14)? as usize;
+    if header_size < 40 {
+        return Err(invalid_data("unsupported BMP header"));
+    }
+    let width = i32_at(18)?;
+    let raw_height = i32_at(22)?;
+    let bits_per_pixel = u16_at(28)?;
+    let compression = u32_at(30)?;
+    let palette_size = u32_at(46)? as usize;
+
+    // rows are stored bottom-up unless the height is negative
+    let top_down = raw_height < 0;
+    let height = raw_height.checked_abs().ok_or_else(truncated)?;
+    if width <= 0 || height == 0 {
+        return Err(invalid_data("empty BMP"));
+    }
+
+    // BI_RGB, or BI_BITFIELDS with the masks after the header
+    let masks = match (compression, bits_per_pixel) {
+        (0, 32) => [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0],
+        (0, _) => [0; 4],
+        // the color masks follow a 40 byte header, or are part of a bigger one which
+        // can also have an alpha mask
+        (3, 32) => {
+            let alpha = match header_size >= 56 {
+                true => u32_at(66)?,
+                false => 0,
+            };
+            [u32_at(54)?, u32_at(58)?, u32_at(62)?, alpha]
+        }
+        _ => return Err(invalid_data("compressed BMPs are not supported")),
+    };
+
+    let palette: Vec<[u8; 4]> = match bits_per_pixel {
+        8 => {
+            let entries = match palette_size {
+                0 => 256,
+                entries => entries,
+            };
+            (0..entries)
+                .map(|i| {
+                    let [b, g, r, _] =
+                        read_le::<4>(bytes, 14 + header_size + i * 4).ok_or_else(truncated)?;
+                    Ok([r, g, b, 255])
+                })
+                .collect::<io::Result<_>>()?
+        }
+        24 | 32 => vec![],
+        other => return Err(invalid_data(format!("unsupported {other} bit BMP"))),
+    };
+
+    let bytes_per_pixel = bits_per_pixel as usize / 8;
+    let row_size = (width as usize * bytes_per_pixel).div_ceil(4) * 4;
+    let pixel_count = (width as usize)
+        .checked_mul(height as usize)
+        .ok_or_else(|| invalid_data("BMP too large"))?;
+    // every pixel takes a byte at least, a truncated file doesn't get its full size reserved
+    let mut pixels = Vec::with_capacity(pixel_count.min(bytes.len()));
+    for y in 0..height as usize {
+        let stored_row = match top_down {
+            true => y,
+            false => height as usize - 1 - y,
+        };
+        let row_start = data_offset + stored_row * row_size;
+        let row = bytes
+            .get(row_start..row_start + width as usize * bytes_per_pixel)
+            .ok_or_else(truncated)?;
+
+        for pixel in row.chunks_exact(bytes_per_pixel) {
+            pixels.push(match pixel {
+                [index] => *palette
+                    .get(*index as usize)
+                    .ok_or_else(|| invalid_data("BMP palette index out of range"))?,
+                [b, g, r] => [*r, *g, *b, 255],
+                _ => {
+                    let value = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                    let [r, g, b, a] = masks.map(|mask| masked_channel(value, mask));
+                    match masks[3] {
+                        0 => [r, g, b, 255],
+                        _ => [r, g, b, a],
+                    }
+                }
+            });
+        }
+    }
+    Ok(Image::new(width, height, pixels))
+}
+
+/// Extract a channel of a BMP bit field pixel, scaled to 8 bits
+fn masked_channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let bits = (value & mask) >> mask.trailing_zeros();
+    let max = mask >> mask.trailing_zeros();
+    (bits as u64 * 255 / max as u64) as u8
+}
+
+/// A rectangle of a texture in pixels, from its top-left corner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl SourceRect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> SourceRect {
+        SourceRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// A texture owned by the renderer, released once the last clone is dropped
+struct TextureData {
+    id: u32,
+    width: i32,
+    height: i32,
+}
+
+impl Drop for TextureData {
+    fn drop(&mut self) {
+        // the renderer may be gone when a texture is released at thread exit
+        try_with_renderer(|renderer| renderer.destroy_texture(self.id));
+        renderer::texture_destroyed();
+    }
+}
+
+/// Shared handle to a texture. Clones are cheap and refer to the same texture, which
+/// stays alive as long as a clone or a sprite using it does.
+#[derive(Clone)]
+pub struct Texture {
+    data: Rc<TextureData>,
+}
+
+impl Texture {
+    /// Upload an image to the renderer
+    pub fn new(image: &Image) -> Texture {
+        let id = with_renderer(|renderer| renderer.create_texture(image));
+        renderer::texture_created();
+        Texture {
+            data: Rc::new(TextureData {
+                id,
+                width: image.width(),
+                height: image.height(),
+            }),
+        }
+    }
+
+    /// Read a PNG or BMP file and upload it to the renderer
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Texture> {
+        Ok(Texture::new(&Image::load(path)?))
+    }
+
+    /// Renderer id of the texture, never 0
+    pub fn id(&self) -> u32 {
+        self.data.id
+    }
+
+    pub fn width(&self) -> i32 {
+        self.data.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.data.height
+    }
+
+    /// The whole texture
+    pub fn full_rect(&self) -> SourceRect {
+        SourceRect::new(0, 0, self.width(), self.height())
+    }
+
+    /// Texture coordinates of `source`: left, top, right and bottom from 0 to 1
+    pub fn tex_coords(&self, source: SourceRect) -> [f32; 4] {
+        let width = self.width().max(1) as f32;
+        let height = self.height().max(1) as f32;
+        [
+            source.x as f32 / width,
+            source.y as f32 / height,
+            (source.x + source.width) as f32 / width,
+            (source.y + source.height) as f32 / height,
+        ]
+    }
+}
+
+impl std::fmt::Debug for Texture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Texture")
+            .field("id", &self.id())
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .finish()
+    }
+}
+
+/// Load a texture from a PNG or BMP file, returning an `io::Result<Texture>`
+#[macro_export]
+macro_rules! LOAD_TEXTURE {
+    ($path:expr) => {
+        $crate::texture::Texture::load($path)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [255, 255, 255, 0];
+
+    /// A BMP with a 40 byte header, a palette and pixel rows as stored in the file
+    fn bmp(bits_per_pixel: u16, height: i32, palette: &[[u8; 4]], rows: &[&[u8]]) -> Vec<u8> {
+        let width = rows[0].len() as i32 / (bits_per_pixel as i32 / 8);
+        let data_offset = 54 + palette.len() as u32 * 4;
+
+        let mut bytes = b"BM".to_vec();
+        bytes.extend(0u32.to_le_bytes()); // file size, ignored
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(data_offset.to_le_bytes());
+        bytes.extend(40u32.to_le_bytes());
+        bytes.extend(width.to_le_bytes());
+        bytes.extend(height.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(bits_per_pixel.to_le_bytes());
+        bytes.extend([0; 16]); // no compression, image size and resolution
+        bytes.extend((palette.len() as u32).to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(palette.iter().flatten());
+        for row in rows {
+            bytes.extend(*row);
+            bytes.extend(vec![0; (4 - row.len() % 4) % 4]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn decode_bmp_24_bits_bottom_up() {
+        // blue green red
+        let bytes = bmp(
+            24,
+            2,
+            &[],
+            &[&[0, 0, 255, 0, 255, 0], &[255, 0, 0, 0, 0, 255]],
+        );
+        let image = Image::decode(&bytes).unwrap();
+
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(image.pixels(), &[BLUE, RED, RED, GREEN]);
+    }
+
+    #[test]
+    fn decode_bmp_8_bits_top_down() {
+        let palette = [[0, 0, 255, 0], [255, 0, 0, 0]];
+        let bytes = bmp(8, -2, &palette, &[&[0, 1, 1], &[1, 0, 0]]);
+        let image = Image::decode(&bytes).unwrap();
+
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert_eq!(image.pixels(), &[RED, BLUE, BLUE, BLUE, RED, RED]);
+    }
+
+    #[test]
+    fn decode_png_with_alpha() {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[GREEN, CLEAR].concat()).unwrap();
+        writer.finish().unwrap();
+
+        let path = std::env::temp_dir().join(format!("texture_{}.png", std::process::id()));
+        fs::write(&path, &bytes).unwrap();
+        let image = Image::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(image, Image::new(2, 1, vec![GREEN, CLEAR]));
+    }
+
+    #[test]
+    fn decode_rejects_other_formats() {
+        assert!(Image::decode(b"GIF89a").is_err());
+        assert!(Image::decode(&bmp(24, 1, &[], &[&[0, 0, 255]])[..56]).is_err());
+    }
+
+    #[test]
+    fn decode_bmp_rejects_huge_sizes() {
+        let mut bytes = bmp(24, 1, &[], &[&[0, 0, 255]]);
+        bytes[18..22].copy_from_slice(&i32::MAX.to_le_bytes());
+        bytes[22..26].copy_from_slice(&i32::MIN.to_le_bytes());
+        assert!(Image::decode(&bytes).is_err());
+        bytes[22..26].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(Image::decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn masked_channels_scale_to_8_bits() {
+        assert_eq!(masked_channel(0xffff_ffff, 0xffff_ffff), 255);
+        assert_eq!(masked_channel(0x0000_0f00, 0x0000_0f00), 255);
+        assert_eq!(masked_channel(0x0000_0500, 0x0000_0f00), 85);
+        assert_eq!(masked_channel(0x1234_5678, 0), 0);
+    }
+}
@@ -0,0 +1,432 @@
+//! tilemap.rs
+//!
+//! Levels made of tiles: a grid of tile ids drawn with a tileset, whose tiles are solid
+//! colors or regions of a texture. Only the tiles in view are drawn, so a map can be much
+//! larger than the window when looked at through a camera.
+//!
+//! Tiles can be solid, e.g. walls. The map tells which tile is at a position and whether
+//! shapes run into solid tiles, in the same coordinates as the sprites.
+
+use crate::camera;
+use crate::collision::Rect;
+use crate::game_ffi::Sprite;
+use crate::layer;
+use crate::stats;
+use crate::texture::{SourceRect, Texture};
+
+/// Index of a tile in a `Tileset`
+pub type TileId = u16;
+
+/// Id of the cells without a tile, drawn as nothing and never solid
+pub const EMPTY: TileId = 0;
+
+/// How a tile is drawn, and whether it blocks the way
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    /// Color of the tile, or tint of its texture region
+    color: [i32; 4],
+    /// Region of the tileset texture drawn, `None` for a solid color
+    source: Option<SourceRect>,
+    solid: bool,
+}
+
+impl Tile {
+    /// A tile of an opaque color
+    pub fn color(r: i32, g: i32, b: i32) -> Tile {
+        Tile {
+            color: [r, g, b, 255],
+            source: None,
+            solid: false,
+        }
+    }
+
+    /// A tile drawing `source`, a region of the tileset texture
+    pub fn region(source: SourceRect) -> Tile {
+        Tile {
+            color: [255, 255, 255, 255],
+            source: Some(source),
+            solid: false,
+        }
+    }
+
+    /// Use a color with an alpha, or tint the texture region, white leaving it unchanged
+    pub fn with_rgba(mut self, r: i32, g: i32, b: i32, a: i32) -> Tile {
+        self.color = [r, g, b, a];
+        self
+    }
+
+    /// Block the way, see `TileMap::is_solid`
+    pub fn with_solid(mut self, solid: bool) -> Tile {
+        self.solid = solid;
+        self
+    }
+
+    pub fn rgba(&self) -> [i32; 4] {
+        self.color
+    }
+
+    pub fn source(&self) -> Option<SourceRect> {
+        self.source
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.solid
+    }
+}
+
+/// The tiles a map is drawn with, identified by their order, starting from 1
+#[derive(Clone, Debug, Default)]
+pub struct Tileset {
+    /// Texture of the tiles drawing a region of it
+    texture: Option<Texture>,
+    tiles: Vec<Tile>,
+}
+
+impl Tileset {
+    /// An empty tileset, for tiles of solid colors
+    pub fn new() -> Tileset {
+        Tileset::default()
+    }
+
+    /// A tileset cutting `texture` in `tile_width` by `tile_height` regions, one tile per
+    /// region row by row. Partial regions on the right and bottom edges are left out.
+    pub fn from_sheet(texture: &Texture, tile_width: i32, tile_height: i32) -> Tileset {
+        let mut tileset = Tileset::new().with_texture(texture);
+        for row in 0..texture.height() / tile_height.max(1) {
+            for column in 0..texture.width() / tile_width.max(1) {
+                let (x, y) = (column * tile_width, row * tile_height);
+                tileset.add(Tile::region(SourceRect::new(x, y, tile_width, tile_height)));
+            }
+        }
+        tileset
+    }
+
+    /// Draw the texture regions of the tiles from `texture`
+    pub fn with_texture(mut self, texture: &Texture) -> Tileset {
+        self.texture = Some(texture.clone());
+        self
+    }
+
+    /// Add a tile, returning its id
+    pub fn add(&mut self, tile: Tile) -> TileId {
+        self.tiles.push(tile);
+        self.tiles.len() as TileId
+    }
+
+    /// The tile of an id, `None` for `EMPTY` and unknown ids
+    pub fn tile(&self, id: TileId) -> Option<&Tile> {
+        self.tiles.get((id as usize).checked_sub(1)?)
+    }
+
+    /// Change a tile, e.g. to make a tile cut from a sheet solid. Does nothing for unknown
+    /// ids.
+    pub fn set_tile(&mut self, id: TileId, tile: Tile) {
+        if let Some(index) = (id as usize).checked_sub(1) {
+            if let Some(current) = self.tiles.get_mut(index) {
+                *current = tile;
+            }
+        }
+    }
+
+    pub fn texture(&self) -> Option<&Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Number of tiles, also the highest tile id
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+/// A grid of tiles, `columns` by `rows`, with its top-left corner at a position
+#[derive(Clone, Debug)]
+pub struct TileMap {
+    columns: i32,
+    rows: i32,
+    tile_width: i32,
+    tile_height: i32,
+    x: f32,
+    y: f32,
+    /// Tile ids row by row, starting from the top-left cell
+    tiles: Vec<TileId>,
+    tileset: Tileset,
+    layer: i32,
+}
+
+impl TileMap {
+    /// An empty map of `columns` by `rows` cells of `tile_width` by `tile_height` pixels,
+    /// at the origin
+    pub fn new(
+        columns: i32,
+        rows: i32,
+        tile_width: i32,
+        tile_height: i32,
+        tileset: Tileset,
+    ) -> TileMap {
+        let (columns, rows) = (columns.max(0), rows.max(0));
+        TileMap {
+            columns,
+            rows,
+            tile_width: tile_width.max(1),
+            tile_height: tile_height.max(1),
+            x: 0.0,
+            y: 0.0,
+            tiles: vec![EMPTY; (columns * rows) as usize],
+            tileset,
+            layer: layer::DEFAULT_LAYER,
+        }
+    }
+
+    /// A map drawn as text, one line per row and one character per cell. `legend` gives
+    /// the tile of each character, the others are empty, e.g. `.` for the floor.
+    pub fn from_text(
+        text: &str,
+        legend: &[(char, TileId)],
+        tile_width: i32,
+        tile_height: i32,
+        tileset: Tileset,
+    ) -> TileMap {
+        let lines: Vec<&str> = text.lines().collect();
+        let columns = lines.iter().map(|line| line.chars().count()).max();
+        let mut map = TileMap::new(
+            columns.unwrap_or(0) as i32,
+            lines.len() as i32,
+            tile_width,
+            tile_height,
+            tileset,
+        );
+        for (row, line) in lines.iter().enumerate() {
+            for (column, character) in line.chars().enumerate() {
+                if let Some((_, id)) = legend.iter().find(|(c, _)| *c == character) {
+                    map.set(column as i32, row as i32, *id);
+                }
+            }
+        }
+        map
+    }
+
+    /// Place the top-left corner of the map at `x`, `y`
+    pub fn with_position(mut self, x: f32, y: f32) -> TileMap {
+        self.x = x;
+        self.y = y;
+        self
+    }
+
+    /// Draw the map on another layer than the default one
+    pub fn with_layer(mut self, layer: i32) -> TileMap {
+        self.layer = layer;
+        self
+    }
+
+    pub fn columns(&self) -> i32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> i32 {
+        self.rows
+    }
+
+    pub fn tile_size(&self) -> (i32, i32) {
+        (self.tile_width, self.tile_height)
+    }
+
+    /// Area covered by the map
+    pub fn bounds(&self) -> Rect {
+        Rect::new(
+            self.x,
+            self.y,
+            (self.columns * self.tile_width) as f32,
+            (self.rows * self.tile_height) as f32,
+        )
+    }
+
+    pub fn tileset(&self) -> &Tileset {
+        &self.tileset
+    }
+
+    pub fn layer(&self) -> i32 {
+        self.layer
+    }
+
+    /// Tile id of a cell, `None` outside of the map
+    pub fn get(&self, column: i32, row: i32) -> Option<TileId> {
+        self.index(column, row).map(|index| self.tiles[index])
+    }
+
+    /// Change the tile of a cell. Does nothing outside of the map.
+    pub fn set(&mut self, column: i32, row: i32, id: TileId) {
+        if let Some(index) = self.index(column, row) {
+            self.tiles[index] = id;
+        }
+    }
+
+    /// Put the same tile in every cell
+    pub fn fill(&mut self, id: TileId) {
+        self.tiles.fill(id);
+    }
+
+    /// Column and row of the cell at position `x`, `y`, `None` outside of the map
+    pub fn cell_at(&self, x: f32, y: f32) -> Option<(i32, i32)> {
+        let column = ((x - self.x) / self.tile_width as f32).floor() as i32;
+        let row = ((y - self.y) / self.tile_height as f32).floor() as i32;
+        self.index(column, row).map(|_| (column, row))
+    }
+
+    /// Tile id at position `x`, `y`, `None` outside of the map
+    pub fn tile_at(&self, x: f32, y: f32) -> Option<TileId> {
+        let (column, row) = self.cell_at(x, y)?;
+        self.get(column, row)
+    }
+
+    /// Area covered by a cell, inside of the map or not
+    pub fn cell_bounds(&self, column: i32, row: i32) -> Rect {
+        Rect::new(
+            self.x + (column * self.tile_width) as f32,
+            self.y + (row * self.tile_height) as f32,
+            self.tile_width as f32,
+            self.tile_height as f32,
+        )
+    }
+
+    /// Whether the tile of a cell is solid. Cells outside of the map are not.
+    pub fn is_solid(&self, column: i32, row: i32) -> bool {
+        self.get(column, row)
+            .and_then(|id| self.tileset.tile(id))
+            .is_some_and(Tile::is_solid)
+    }
+
+    /// Whether the tile at position `x`, `y` is solid
+    pub fn is_solid_at(&self, x: f32, y: f32) -> bool {
+        self.cell_at(x, y)
+            .is_some_and(|(column, row)| self.is_solid(column, row))
+    }
+
+    /// Bounds of the solid tiles overlapping `area`, e.g. to push a sprite out of the walls
+    /// with `Rect::penetration`
+    pub fn solid_tiles_in(&self, area: &Rect) -> Vec<Rect> {
+        self.cells_in(area)
+            .filter(|&(column, row)| self.is_solid(column, row))
+            .map(|(column, row)| self.cell_bounds(column, row))
+            .filter(|bounds| bounds.intersects(area))
+            .collect()
+    }
+
+    /// Whether `area` overlaps a solid tile, e.g. the bounds of a sprite
+    pub fn overlaps_solid(&self, area: &Rect) -> bool {
+        !self.solid_tiles_in(area).is_empty()
+    }
+
+    /// Queue the tiles in view on the layer of the map. Tiles are drawn as sprites, one
+    /// per cell that isn't empty, with no sprite allocated.
+    pub fn render(&self) {
+        let (left, top, width, height) = camera::visible_area();
+        let texture = self.tileset.texture();
+        for (column, row) in self.cells_in(&Rect::new(left, top, width, height)) {
+            let Some(tile) = self.get(column, row).and_then(|id| self.tileset.tile(id)) else {
+                continue;
+            };
+            let bounds = self.cell_bounds(column, row);
+            let mut sprite = Sprite {
+                width: self.tile_width,
+                height: self.tile_height,
+                color: tile.color,
+                x: bounds.x,
+                y: bounds.y,
+                texture: 0,
+                tex_coords: [0.0, 0.0, 1.0, 1.0],
+                rotation: 0.0,
+                scale: [1.0, 1.0],
+                origin: [0.0, 0.0],
+            };
+            // texture regions without a texture are drawn with their color
+            let textured = tile.source.zip(texture);
+            if let Some((source, texture)) = textured {
+                sprite.texture = texture.id();
+                sprite.tex_coords = texture.tex_coords(source);
+            }
+            layer::draw_sprite(self.layer, &sprite, textured.map(|(_, texture)| texture));
+            stats::count_sprite_draw();
+        }
+    }
+
+    fn index(&self, column: i32, row: i32) -> Option<usize> {
+        let inside = (0..self.columns).contains(&column) && (0..self.rows).contains(&row);
+        inside.then(|| (row * self.columns + column) as usize)
+    }
+
+    /// Cells of the map covered by `area`, the right and bottom edges excluded, row by row
+    fn cells_in(&self, area: &Rect) -> impl Iterator<Item = (i32, i32)> {
+        let (tile_width, tile_height) = (self.tile_width as f32, self.tile_height as f32);
+        let first = |start: f32, size: f32| (start / size).floor().max(0.0) as i32;
+        let end = |end: f32, size: f32, count: i32| ((end / size).ceil() as i32).min(count);
+        let (left, top) = (area.x - self.x, area.y - self.y);
+        let columns = first(left, tile_width)..end(left + area.width, tile_width, self.columns);
+        let rows = first(top, tile_height)..end(top + area.height, tile_height, self.rows);
+        rows.flat_map(move |row| columns.clone().map(move |column| (column, row)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAZE: &str = "\
+#####
+#...#
+#.#..
+#####";
+
+    fn maze() -> TileMap {
+        let mut tileset = Tileset::new();
+        let wall = tileset.add(Tile::color(0, 0, 255).with_solid(true));
+        let water = tileset.add(Tile::color(0, 128, 255));
+        let mut map = TileMap::from_text(MAZE, &[('#', wall)], 10, 10, tileset);
+        map.set(1, 2, water);
+        map.with_position(100.0, 50.0)
+    }
+
+    #[test]
+    fn finds_tiles_at_positions() {
+        let map = maze();
+        assert_eq!((map.columns(), map.rows()), (5, 4));
+        assert_eq!(map.bounds(), Rect::new(100.0, 50.0, 50.0, 40.0));
+        assert_eq!(map.cell_at(100.0, 50.0), Some((0, 0)));
+        assert_eq!(map.cell_at(119.9, 70.0), Some((1, 2)));
+        assert_eq!(map.cell_at(99.9, 50.0), None);
+        assert_eq!(map.cell_at(150.0, 50.0), None);
+        assert_eq!(map.tile_at(115.0, 75.0), Some(2));
+        assert_eq!(map.tile_at(125.0, 75.0), Some(1));
+        assert_eq!(map.tile_at(145.0, 75.0), Some(EMPTY));
+
+        // water isn't solid, and neither is the outside of the map
+        assert!(map.is_solid(0, 0) && map.is_solid(2, 2));
+        assert!(!map.is_solid(1, 2) && !map.is_solid(4, 2) && !map.is_solid(-1, 0));
+        assert!(map.is_solid_at(149.0, 89.0) && !map.is_solid_at(150.0, 89.0));
+        assert_eq!(map.tileset().tile(EMPTY), None);
+    }
+
+    #[test]
+    fn collides_with_solid_tiles() {
+        let map = maze();
+        // in the corridor, touching the walls around it
+        assert!(!map.overlaps_solid(&Rect::new(110.0, 60.0, 30.0, 10.0)));
+        // one pixel into the wall below
+        assert!(map.overlaps_solid(&Rect::new(125.0, 65.0, 6.0, 6.0)));
+        assert_eq!(
+            map.solid_tiles_in(&Rect::new(138.0, 58.0, 4.0, 4.0)),
+            [
+                Rect::new(130.0, 50.0, 10.0, 10.0),
+                Rect::new(140.0, 50.0, 10.0, 10.0),
+                Rect::new(140.0, 60.0, 10.0, 10.0)
+            ]
+        );
+        assert!(!map.overlaps_solid(&Rect::new(0.0, 0.0, 100.0, 1000.0)));
+
+        // out through the gap on the right
+        assert!(!map.overlaps_solid(&Rect::new(140.0, 70.0, 20.0, 10.0)));
+    }
+}
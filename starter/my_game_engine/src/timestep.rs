@@ -0,0 +1,208 @@
+//! timestep.rs
+//!
+//! Fixed-timestep simulation. Frame time is accumulated and consumed in steps of a fixed
+//! duration, so games update at the same pace whatever the speed of the machine and the
+//! cost of rendering. What is left in the accumulator gives the interpolation alpha used
+//! to render between the last two simulation states.
+//!
+//! `START_FIXED_TIMESTEP_LOOP!` runs a game loop on top of it.
+
+use std::time::Duration;
+
+/// Accumulates frame time and turns it into fixed simulation steps
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    step: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+    last_time: Option<Duration>,
+    skipped_steps: u64,
+}
+
+impl FixedTimestep {
+    /// Steps simulated per frame before the remaining time is dropped
+    pub const DEFAULT_MAX_STEPS: u32 = 5;
+
+    /// Simulate in steps of `step`. Panics on a zero step.
+    pub fn new(step: Duration) -> FixedTimestep {
+        assert!(!step.is_zero(), "the simulation step can't be zero");
+        FixedTimestep {
+            step,
+            max_steps: FixedTimestep::DEFAULT_MAX_STEPS,
+            accumulator: Duration::ZERO,
+            last_time: None,
+            skipped_steps: 0,
+        }
+    }
+
+    /// Simulate `updates_per_second` steps every second
+    pub fn from_rate(updates_per_second: u32) -> FixedTimestep {
+        FixedTimestep::new(Duration::from_secs(1) / updates_per_second.max(1))
+    }
+
+    /// Simulate at most `max_steps` per frame. When a frame took longer, e.g. after the
+    /// window was dragged, the extra time is dropped instead of making the next frames
+    /// even slower trying to catch up.
+    pub fn with_max_steps(mut self, max_steps: u32) -> FixedTimestep {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// Duration of a simulation step
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Duration of a simulation step in seconds, the delta time given to updates
+    pub fn delta_time(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    /// Add the time a frame took and return how many steps to simulate
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            if steps == self.max_steps {
+                let skipped = self.accumulator.as_nanos() / self.step.as_nanos();
+                self.skipped_steps += skipped as u64;
+                self.accumulator = Duration::from_nanos(
+                    (self.accumulator.as_nanos() % self.step.as_nanos()) as u64,
+                );
+                break;
+            }
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Advance to `now`, a time read from a clock such as `Renderer::time`, and return how
+    /// many steps to simulate. The first call only starts the clock.
+    pub fn advance_to(&mut self, now: Duration) -> u32 {
+        let elapsed = self
+            .last_time
+            .map_or(Duration::ZERO, |last| now.saturating_sub(last));
+        self.last_time = Some(now);
+        self.advance(elapsed)
+    }
+
+    /// How far the current time is between the last simulated step and the next one,
+    /// from `0.0` to `1.0`
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+
+    /// Time left before the next step is due
+    pub fn time_until_next_step(&self) -> Duration {
+        self.step.saturating_sub(self.accumulator)
+    }
+
+    /// Steps dropped so far by the max steps guard
+    pub fn skipped_steps(&self) -> u64 {
+        self.skipped_steps
+    }
+}
+
+/// Run a game loop with a fixed-timestep simulation until the window closes.
+///
+/// The update block runs once per simulation step with the step duration in seconds bound
+/// to `dt`. The render block then runs once per frame with the interpolation alpha bound
+/// to `alpha`. `break` in either block ends the loop. Input events are delivered to the
+/// update block.
+///
+/// ```ignore
+/// START_FIXED_TIMESTEP_LOOP!(FixedTimestep::from_rate(100), |dt| {
+///     x += SPEED * dt;
+/// }, |alpha| {
+///     MOVE_SPRITE!(true, true, sprite, previous_x + (x - previous_x) * alpha, y);
+/// });
+/// ```
+#[macro_export]
+macro_rules! START_FIXED_TIMESTEP_LOOP {
+    ($timestep:expr, |$dt:ident| $update:block, |$alpha:ident| $render:block) => {{
+        let mut timestep: $crate::timestep::FixedTimestep = $timestep;
+        let mut steps: u32 = 0;
+        let mut started = false;
+
+        // A single loop runs both the updates and the frames so that `break` in the
+        // game's blocks ends the game loop
+        loop {
+            if steps > 0 {
+                steps -= 1;
+                // each step sees the events received since the previous one, so an event
+                // is handled once even when a frame runs several steps, and never lost
+                // on a frame without any
+                $crate::input::start_frame();
+                let $dt = timestep.delta_time();
+                $crate::stats::enter($crate::stats::Phase::Update);
+                $update
+                continue;
+            }
+
+            if started {
+                let $alpha = timestep.alpha();
+                $crate::stats::enter($crate::stats::Phase::Render);
+                $render
+                $crate::stats::render_overlay();
+                $crate::layer::flush();
+
+                $crate::stats::enter($crate::stats::Phase::Idle);
+                $crate::window::update();
+                std::thread::sleep(timestep.time_until_next_step());
+                $crate::stats::end_frame();
+            }
+            started = true;
+
+            let should_close =
+                $crate::renderer::with_renderer(|renderer| renderer.window_should_close());
+            if should_close {
+                break;
+            }
+
+            let now = $crate::renderer::with_renderer(|renderer| renderer.time());
+            steps = timestep.advance_to(now);
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn steps_accumulate() {
+        let mut timestep = FixedTimestep::new(STEP);
+
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 1);
+        assert_eq!(timestep.advance(Duration::from_millis(25)), 2);
+        assert!((timestep.alpha() - 0.7).abs() < 1e-4);
+        assert_eq!(timestep.time_until_next_step(), Duration::from_millis(3));
+        assert_eq!(timestep.delta_time(), 0.01);
+    }
+
+    #[test]
+    fn max_steps_guard() {
+        let mut timestep = FixedTimestep::new(STEP).with_max_steps(3);
+
+        assert_eq!(timestep.advance(Duration::from_millis(1005)), 3);
+        assert_eq!(timestep.skipped_steps(), 97);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(timestep.advance(Duration::from_millis(5)), 1);
+    }
+
+    #[test]
+    fn advance_to_clock() {
+        let mut timestep = FixedTimestep::from_rate(100);
+
+        assert_eq!(timestep.advance_to(Duration::from_secs(3)), 0);
+        assert_eq!(timestep.advance_to(Duration::from_millis(3020)), 2);
+        // a clock going backwards doesn't simulate anything
+        assert_eq!(timestep.advance_to(Duration::from_secs(1)), 0);
+    }
+}
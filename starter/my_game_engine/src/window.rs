@@ -0,0 +1,363 @@
+//! window.rs
+//!
+//! Game windows, their size and logical resolution. A game can open several windows, e.g.
+//! a game view and an inspector, and switches between them with `make_current`. Drawing,
+//! input queries and the game loops target the current window.
+//!
+//! Games draw in a logical resolution, by default the
+//! size the window was created with, which is scaled to the actual window size according
+//! to a `Scaling` mode. Resizes are reported by the backends, queued as `WindowEvent`s and
+//! applied to the renderer once the frame is presented.
+//!
+//! Sprite positions, mouse positions and `game_ffi::Window` all use logical coordinates,
+//! unless the window has a `camera`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::audio;
+use crate::camera;
+use crate::game_ffi::Window;
+use crate::input;
+use crate::layer;
+use crate::renderer::with_renderer;
+
+/// How the logical resolution is mapped to the window
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scaling {
+    /// Scale as much as possible while keeping the aspect ratio, with black bars on the sides
+    #[default]
+    Letterbox,
+    /// Fill the whole window, distorting the game when the aspect ratio changes
+    Stretch,
+    /// No scaling, the logical resolution follows the window size
+    Expand,
+}
+
+/// Area of the window the logical resolution is drawn in, in window pixels from the
+/// top-left corner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Viewport {
+    /// Viewport covering a whole window
+    pub fn full(width: i32, height: i32) -> Viewport {
+        Viewport {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    /// Viewport showing `logical` in a window of size `window` with the given scaling
+    pub fn fit(window: (i32, i32), logical: (i32, i32), scaling: Scaling) -> Viewport {
+        let (window_width, window_height) = window;
+        let (logical_width, logical_height) = logical;
+
+        match scaling {
+            Scaling::Stretch | Scaling::Expand => Viewport::full(window_width, window_height),
+            Scaling::Letterbox => {
+                let scale = f32::min(
+                    window_width as f32 / logical_width.max(1) as f32,
+                    window_height as f32 / logical_height.max(1) as f32,
+                );
+                let width = (logical_width as f32 * scale).round() as i32;
+                let height = (logical_height as f32 * scale).round() as i32;
+                Viewport {
+                    x: (window_width - width) / 2,
+                    y: (window_height - height) / 2,
+                    width,
+                    height,
+                }
+            }
+        }
+    }
+}
+
+/// Something that happened to the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowEvent {
+    /// The window was resized to `width` by `height` pixels
+    Resized { width: i32, height: i32 },
+}
+
+/// Identifies one of the windows opened by the game, returned by `create`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WindowId(u32);
+
+/// Sizes of a window
+struct Resolution {
+    window: (i32, i32),
+    logical: (i32, i32),
+    scaling: Scaling,
+    /// Whether the renderer must be told about a new viewport
+    changed: bool,
+}
+
+impl Resolution {
+    fn viewport(&self) -> Viewport {
+        Viewport::fit(self.window, self.logical, self.scaling)
+    }
+
+    fn to_logical(&self, x: f32, y: f32) -> (f32, f32) {
+        let viewport = self.viewport();
+        let (logical_width, logical_height) = self.logical;
+        (
+            (x - viewport.x as f32) * logical_width as f32 / viewport.width.max(1) as f32,
+            (y - viewport.y as f32) * logical_height as f32 / viewport.height.max(1) as f32,
+        )
+    }
+}
+
+/// The windows opened on the current thread
+#[derive(Default)]
+struct Windows {
+    resolutions: HashMap<WindowId, Resolution>,
+    current: Option<WindowId>,
+    next_id: u32,
+}
+
+thread_local! {
+    static WINDOWS: RefCell<Windows> = RefCell::new(Windows::default());
+}
+
+fn with_windows<T>(f: impl FnOnce(&mut Windows) -> T) -> T {
+    WINDOWS.with(|windows| f(&mut windows.borrow_mut()))
+}
+
+/// Run a closure against the sizes of the current window
+fn with_resolution<T>(f: impl FnOnce(&mut Resolution) -> T) -> T {
+    with_windows(|windows| {
+        let current = windows
+            .current
+            .expect("the game window must be created first");
+        f(windows
+            .resolutions
+            .get_mut(&current)
+            .expect("the current window was closed"))
+    })
+}
+
+/// Open a game window, which becomes the current window. Its size is also the logical
+/// resolution until changed with `set_logical_resolution`.
+pub fn create(title: &str, width: i32, height: i32) -> WindowId {
+    // what was rendered belongs to the previous window
+    layer::flush();
+    let id = with_windows(|windows| {
+        windows.next_id += 1;
+        WindowId(windows.next_id)
+    });
+
+    with_renderer(|renderer| renderer.create_window(id, title, width, height));
+    with_windows(|windows| {
+        windows.resolutions.insert(
+            id,
+            Resolution {
+                window: (width, height),
+                logical: (width, height),
+                scaling: Scaling::default(),
+                changed: true,
+            },
+        );
+        windows.current = Some(id);
+    });
+    apply_changes();
+    id
+}
+
+/// Close a window. When it was the current window, the oldest remaining window becomes
+/// current.
+pub fn close(id: WindowId) {
+    let open = with_windows(|windows| windows.resolutions.remove(&id).is_some());
+    if !open {
+        return;
+    }
+
+    if current() == Some(id) {
+        layer::discard();
+    }
+    camera::forget(id);
+    with_renderer(|renderer| renderer.destroy_window(id));
+    let next = with_windows(|windows| {
+        if windows.current == Some(id) {
+            windows.current = windows.resolutions.keys().min().copied();
+        }
+        windows.current
+    });
+    if let Some(next) = next {
+        with_renderer(|renderer| renderer.make_current(next));
+    }
+}
+
+/// Make `id` the current window: the target of drawing, input queries and the game loops
+pub fn make_current(id: WindowId) {
+    if current() != Some(id) {
+        layer::flush();
+    }
+    let open = with_windows(|windows| {
+        let open = windows.resolutions.contains_key(&id);
+        if open {
+            windows.current = Some(id);
+        }
+        open
+    });
+    assert!(open, "window {id:?} was closed");
+
+    with_renderer(|renderer| renderer.make_current(id));
+}
+
+/// The current window, `None` before the first window is created
+pub fn current() -> Option<WindowId> {
+    with_windows(|windows| windows.current)
+}
+
+/// All open windows, oldest first
+pub fn windows() -> Vec<WindowId> {
+    let mut ids: Vec<WindowId> =
+        with_windows(|windows| windows.resolutions.keys().copied().collect());
+    ids.sort();
+    ids
+}
+
+/// Draw the rendered layers and present the frame of the current window, process the
+/// events of all windows and apply any resize. Audio outputs without a device catch up
+/// with the frame.
+pub fn update() {
+    layer::flush();
+    with_renderer(|renderer| renderer.update_window());
+    apply_changes();
+    audio::update();
+}
+
+/// Tell the renderer about new viewports, once it is not busy anymore
+fn apply_changes() {
+    let changed: Vec<(WindowId, Viewport, (i32, i32))> = with_windows(|windows| {
+        windows
+            .resolutions
+            .iter_mut()
+            .filter_map(|(id, resolution)| {
+                std::mem::take(&mut resolution.changed)
+                    .then(|| (*id, resolution.viewport(), resolution.logical))
+            })
+            .collect()
+    });
+
+    for (id, viewport, logical) in changed {
+        with_renderer(|renderer| renderer.set_viewport(id, viewport, logical));
+    }
+}
+
+/// Record a new window size and queue a `Resized` event. Called by the backends, possibly
+/// while the renderer is busy, so the renderer is only updated by the next `update`.
+pub fn handle_resize(id: WindowId, width: i32, height: i32) {
+    with_windows(|windows| {
+        if let Some(resolution) = windows.resolutions.get_mut(&id) {
+            resolution.window = (width, height);
+            if resolution.scaling == Scaling::Expand {
+                resolution.logical = (width, height);
+            }
+            resolution.changed = true;
+        }
+    });
+    input::push_window_event(id, WindowEvent::Resized { width, height });
+}
+
+/// Draw the game in a `width` by `height` logical resolution, scaled to the current window
+pub fn set_logical_resolution(width: i32, height: i32) {
+    with_resolution(|resolution| {
+        resolution.logical = (width, height);
+        resolution.changed = true;
+    });
+    apply_changes();
+}
+
+/// Change how the logical resolution is scaled to the current window
+pub fn set_scaling(scaling: Scaling) {
+    with_resolution(|resolution| {
+        resolution.scaling = scaling;
+        if scaling == Scaling::Expand {
+            resolution.logical = resolution.window;
+        }
+        resolution.changed = true;
+    });
+    apply_changes();
+}
+
+pub fn scaling() -> Scaling {
+    with_resolution(|resolution| resolution.scaling)
+}
+
+/// Size of the current window in pixels
+pub fn window_size() -> (i32, i32) {
+    with_resolution(|resolution| resolution.window)
+}
+
+/// Size of the logical resolution games draw in
+pub fn logical_size() -> (i32, i32) {
+    with_resolution(|resolution| resolution.logical)
+}
+
+/// Area of the current window the game is drawn in
+pub fn viewport() -> Viewport {
+    with_resolution(|resolution| resolution.viewport())
+}
+
+/// Convert a position in pixels of a window, e.g. of the mouse cursor, to logical
+/// coordinates
+pub fn to_logical(id: WindowId, x: f32, y: f32) -> (f32, f32) {
+    with_windows(|windows| match windows.resolutions.get(&id) {
+        Some(resolution) => resolution.to_logical(x, y),
+        None => (x, y),
+    })
+}
+
+impl Window {
+    /// Update `width` and `height` to the current logical resolution, so wrapping around
+    /// with `GO_LEFT!` and friends follows the window when it is resized
+    pub fn sync_size(&mut self) {
+        (self.width, self.height) = logical_size();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox_keeps_aspect_ratio() {
+        assert_eq!(
+            Viewport::fit((400, 400), (800, 600), Scaling::Letterbox),
+            Viewport {
+                x: 0,
+                y: 50,
+                width: 400,
+                height: 300
+            }
+        );
+        assert_eq!(
+            Viewport::fit((1000, 600), (800, 600), Scaling::Letterbox),
+            Viewport {
+                x: 100,
+                y: 0,
+                width: 800,
+                height: 600
+            }
+        );
+    }
+
+    #[test]
+    fn stretch_and_expand_fill_the_window() {
+        for scaling in [Scaling::Stretch, Scaling::Expand] {
+            assert_eq!(
+                Viewport::fit((400, 400), (800, 600), scaling),
+                Viewport::full(400, 400)
+            );
+        }
+    }
+}
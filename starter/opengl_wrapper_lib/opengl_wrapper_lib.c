@@ -1,21 +1,149 @@
+// Vertex buffers are OpenGL 1.5, declared by glext.h
+#define GL_GLEXT_PROTOTYPES
 #include <GLFW/glfw3.h>
+#include <math.h>
+#include <stddef.h>
 #include <stdlib.h>
 #include "opengl_wrapper_lib.h"
-// Global variable for the GLFW window
-GLFWwindow* window;
+
+#define DEGREES_TO_RADIANS 0.017453292f
+// Callback forwarding key events to the caller
+static key_event_callback key_callback = NULL;
+// Callbacks forwarding mouse events to the caller
+static mouse_button_event_callback mouse_button_callback = NULL;
+static scroll_event_callback scroll_callback = NULL;
+// Callback forwarding window resizes to the caller
+static resize_event_callback resize_callback = NULL;
+
+// A corner of a triangle in the batch
+typedef struct {
+    float x, y;
+    float u, v;
+    unsigned char color[4];
+} Vertex;
+
+// Triangles of the sprites and polygons drawn since the last flush, all with the same
+// texture. They are sent to the GPU in one draw call when the frame is presented, or
+// earlier when the texture changes. The batch belongs to the current context: it is
+// flushed before another context becomes current.
+static struct {
+    Vertex *vertices;
+    int count;
+    int capacity;
+    unsigned int texture; // texture of the triangles, 0 for solid colors
+    GLuint buffer; // vertex buffer object the triangles are uploaded to
+} batch = {NULL, 0, 0, 0, 0};
+
+// Draw the batched triangles in the current context and empty the batch. Without a current
+// context there is nowhere to draw them, and they are dropped.
+static void flush_batch() {
+    if (batch.count == 0) {
+        return;
+    }
+    if (glfwGetCurrentContext() == NULL) {
+        batch.count = 0;
+        return;
+    }
+
+    // Buffers are shared by the windows, but not by the windows opened after all of them
+    // were closed
+    if (batch.buffer == 0 || !glIsBuffer(batch.buffer)) {
+        glGenBuffers(1, &batch.buffer);
+    }
+    glBindBuffer(GL_ARRAY_BUFFER, batch.buffer);
+    glBufferData(GL_ARRAY_BUFFER, batch.count * sizeof(Vertex), batch.vertices, GL_STREAM_DRAW);
+
+    glEnableClientState(GL_VERTEX_ARRAY);
+    glEnableClientState(GL_COLOR_ARRAY);
+    glVertexPointer(2, GL_FLOAT, sizeof(Vertex), (const void*)offsetof(Vertex, x));
+    glColorPointer(4, GL_UNSIGNED_BYTE, sizeof(Vertex), (const void*)offsetof(Vertex, color));
+
+    if (batch.texture != 0) {
+        // The texture is multiplied by the vertex colors, alpha included
+        glEnable(GL_TEXTURE_2D);
+        glBindTexture(GL_TEXTURE_2D, batch.texture);
+        glTexEnvi(GL_TEXTURE_ENV, GL_TEXTURE_ENV_MODE, GL_MODULATE);
+        glEnableClientState(GL_TEXTURE_COORD_ARRAY);
+        glTexCoordPointer(2, GL_FLOAT, sizeof(Vertex), (const void*)offsetof(Vertex, u));
+    }
+
+    glDrawArrays(GL_TRIANGLES, 0, batch.count);
+
+    if (batch.texture != 0) {
+        glDisableClientState(GL_TEXTURE_COORD_ARRAY);
+        glBindTexture(GL_TEXTURE_2D, 0);
+        glDisable(GL_TEXTURE_2D);
+    }
+    glDisableClientState(GL_COLOR_ARRAY);
+    glDisableClientState(GL_VERTEX_ARRAY);
+    glBindBuffer(GL_ARRAY_BUFFER, 0);
+
+    batch.count = 0;
+}
+
+// Make room for count more vertices with a texture in the batch, flushing it first when
+// it holds another texture
+static Vertex* reserve_vertices(unsigned int texture, int count) {
+    if (batch.texture != texture) {
+        flush_batch();
+        batch.texture = texture;
+    }
+    if (batch.count + count > batch.capacity) {
+        int capacity = batch.capacity > 0 ? batch.capacity : 1024;
+        while (capacity < batch.count + count) {
+            capacity *= 2;
+        }
+        Vertex *vertices = (Vertex*)realloc(batch.vertices, capacity * sizeof(Vertex));
+        if (!vertices) {
+            exit(EXIT_FAILURE);
+        }
+        batch.vertices = vertices;
+        batch.capacity = capacity;
+    }
+
+    Vertex *reserved = batch.vertices + batch.count;
+    batch.count += count;
+    return reserved;
+}
+
+// Fill a vertex of the batch
+static void set_vertex(Vertex *vertex, float x, float y, float u, float v, const int color[4]) {
+    vertex->x = x;
+    vertex->y = y;
+    vertex->u = u;
+    vertex->v = v;
+    for (int i = 0; i < 4; i++) {
+        vertex->color[i] = (unsigned char)color[i];
+    }
+}
+
+// Make the OpenGL context of a window current, so the next draw calls target it
+static void use_window(GLFWwindow* window) {
+    if (glfwGetCurrentContext() != window) {
+        flush_batch();
+        glfwMakeContextCurrent(window);
+    }
+}
 
 // Function to create a game window
-void create_game_window(const char *title, int width, int height) {
+GLFWwindow* create_game_window(const char *title, int width, int height) {
+    static int initialized = 0;
     int argc = 0;
     char *argv = "test_game";
 
-    glutInit(&argc, &argv);
+    // GLUT and GLFW are only initialized for the first window
+    if (!initialized) {
+        glutInit(&argc, &argv);
 
-    if (!glfwInit()) {
-        exit(EXIT_FAILURE);
+        if (!glfwInit()) {
+            exit(EXIT_FAILURE);
+        }
+        initialized = 1;
     }
 
-    window = glfwCreateWindow(width, height, title, NULL, NULL);
+    // Share textures and buffers with the windows already open
+    flush_batch();
+    GLFWwindow* window = glfwCreateWindow(width, height, title, NULL, glfwGetCurrentContext());
 
     if (!window) {
         glfwTerminate();
@@ -30,6 +158,28 @@ void create_game_window(const char *title, int width, int height) {
     glOrtho(0, width, height, 0, -1, 1); // Origin at top-left
     glMatrixMode(GL_MODELVIEW);
     glLoadIdentity();
+
+    // Blend translucent sprites and texels with what was drawn behind them
+    glEnable(GL_BLEND);
+    glBlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA);
+
+    return window;
+}
+
+// Function to close a game window
+void destroy_game_window(GLFWwindow* window) {
+    // The batch of the window would never be presented, and no context is current until
+    // another window is made current
+    if (glfwGetCurrentContext() == window) {
+        batch.count = 0;
+        glfwMakeContextCurrent(NULL);
+    }
+    glfwDestroyWindow(window);
+}
+
+// Function to make a window the target of the next draw calls
+void make_window_current(GLFWwindow* window) {
+    use_window(window);
 }
 
 // Function to create a sprite
@@ -40,51 +190,137 @@ Sprite* create_sprite(float x, float y, int width, int height, int r, int g, int
     sprite->color[0] = r;
     sprite->color[1] = g;
     sprite->color[2] = b;
+    sprite->color[3] = 255;
     sprite->x = x;
     sprite->y = y;
+    sprite->texture = 0;
+    sprite->tex_coords[0] = 0.0f;
+    sprite->tex_coords[1] = 0.0f;
+    sprite->tex_coords[2] = 1.0f;
+    sprite->tex_coords[3] = 1.0f;
+    sprite->rotation = 0.0f;
+    sprite->scale[0] = 1.0f;
+    sprite->scale[1] = 1.0f;
+    sprite->origin[0] = 0.5f;
+    sprite->origin[1] = 0.5f;
     return sprite;
 }
 
+// Function to destroy a sprite created with create_sprite
+void destroy_sprite(Sprite *sprite) {
+    free(sprite);
+}
+
 int test() {
     return 1;
 }
 
-// Function to render a sprite
-void render_sprite(Sprite *sprite) {
-    // Convert sprite position and size to window coordinates
-    float x1 = sprite->x;
-    float y1 = sprite->y;
-    float x2 = sprite->x + sprite->width;
-    float y2 = sprite->y + sprite->height;
+// Function to render a sprite, batched with the next sprites and polygons
+void render_sprite(GLFWwindow* window, Sprite *sprite) {
+    use_window(window);
+
+    // Rotate and scale the corners around the origin. With y pointing down, positive
+    // angles turn clockwise
+    float pivot_x = sprite->x + sprite->origin[0] * sprite->width;
+    float pivot_y = sprite->y + sprite->origin[1] * sprite->height;
+    float radians = sprite->rotation * DEGREES_TO_RADIANS;
+    float cos_angle = cosf(radians);
+    float sin_angle = sinf(radians);
+    float corners[4][2] = {
+        {0.0f, 0.0f},
+        {1.0f, 0.0f},
+        {1.0f, 1.0f},
+        {0.0f, 1.0f},
+    };
+    float x[4], y[4], u[4], v[4];
+    for (int i = 0; i < 4; i++) {
+        float dx = (corners[i][0] - sprite->origin[0]) * sprite->width * sprite->scale[0];
+        float dy = (corners[i][1] - sprite->origin[1]) * sprite->height * sprite->scale[1];
+        x[i] = pivot_x + dx * cos_angle - dy * sin_angle;
+        y[i] = pivot_y + dx * sin_angle + dy * cos_angle;
+        // left, top, right and bottom of the part of the texture drawn
+        u[i] = sprite->tex_coords[corners[i][0] == 0.0f ? 0 : 2];
+        v[i] = sprite->tex_coords[corners[i][1] == 0.0f ? 1 : 3];
+    }
 
-    glColor3ub(sprite->color[0], sprite->color[1], sprite->color[2]);
-    glBegin(GL_QUADS);
-    glVertex2f(x1, y1);
-    glVertex2f(x2, y1);
-    glVertex2f(x2, y2);
-    glVertex2f(x1, y2);
-    glEnd();
+    // Two triangles, top-right and bottom-left
+    static const int triangles[6] = {0, 1, 2, 0, 2, 3};
+    Vertex *vertices = reserve_vertices(sprite->texture, 6);
+    for (int i = 0; i < 6; i++) {
+        int corner = triangles[i];
+        set_vertex(&vertices[i], x[corner], y[corner], u[corner], v[corner], sprite->color);
+    }
 }
 
-// Function to update a sprite position
-void update_sprite_position(Sprite *sprite, float x, float y) {
-    sprite->x = x;
-    sprite->y = y;
+// Function to fill a convex polygon given as count x, y pairs with an RGBA color, batched
+// with the next sprites and polygons
+void render_polygon(GLFWwindow* window, const float *points, int count, int r, int g, int b, int a) {
+    if (count < 3) {
+        return;
+    }
+    use_window(window);
+    int color[4] = {r, g, b, a};
+
+    // A convex polygon is a fan of triangles around its first point
+    Vertex *vertices = reserve_vertices(0, 3 * (count - 2));
+    for (int i = 1; i < count - 1; i++) {
+        set_vertex(vertices++, points[0], points[1], 0.0f, 0.0f, color);
+        set_vertex(vertices++, points[2 * i], points[2 * i + 1], 0.0f, 0.0f, color);
+        set_vertex(vertices++, points[2 * i + 2], points[2 * i + 3], 0.0f, 0.0f, color);
+    }
+}
+
+// Function to create a texture from RGBA pixels, top row first
+unsigned int create_texture(const unsigned char *pixels, int width, int height) {
+    GLuint texture;
+    glGenTextures(1, &texture);
+    glBindTexture(GL_TEXTURE_2D, texture);
+
+    // Keep the pixel art sharp when scaled
+    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_NEAREST);
+    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_NEAREST);
+    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE);
+    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE);
+
+    glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
+    glTexImage2D(GL_TEXTURE_2D, 0, GL_RGBA, width, height, 0, GL_RGBA, GL_UNSIGNED_BYTE, pixels);
+    glBindTexture(GL_TEXTURE_2D, 0);
+    return texture;
 }
 
-// Function to update the game window
-void update_game_window() {
+// Function to destroy a texture created with create_texture
+void destroy_texture(unsigned int texture) {
+    // Draw the sprites still using the texture
+    flush_batch();
+    glDeleteTextures(1, &texture);
+}
+
+// Function to get the largest width and height of a texture, a window must be created first
+int get_max_texture_size() {
+    GLint size = 0;
+    glGetIntegerv(GL_MAX_TEXTURE_SIZE, &size);
+    return size;
+}
+
+// Function to update the game window. Events of every window are processed
+void update_game_window(GLFWwindow* window) {
+    // Draw the batch into the context of the window that is presented
+    use_window(window);
+    flush_batch();
     glfwSwapBuffers(window);
     glfwPollEvents();
 }
 
 // Function to clear the screen
-void clear_screen() {
+void clear_screen(GLFWwindow* window) {
+    use_window(window);
+    // The batched triangles would be cleared anyway
+    batch.count = 0;
     glClear(GL_COLOR_BUFFER_BIT);
 }
 
 // Function to check if the window should close
-int window_should_close() {
+int window_should_close(GLFWwindow* window) {
     return glfwWindowShouldClose(window);
 }
 
@@ -93,13 +329,108 @@ int get_key(GLFWwindow* window, int key) {
     return glfwGetKey(window, key);
 }
 
-// Function to get the window pointer
-GLFWwindow* get_window() {
-    return window;
+// GLFW key callback
+static void on_glfw_key(GLFWwindow* source, int key, int scancode, int action, int mods) {
+    if (key_callback != NULL) {
+        key_callback(source, key, scancode, action, mods);
+    }
+}
+
+// Function to receive the key events of a window, delivered while windows are updated
+void set_key_callback(GLFWwindow* window, key_event_callback callback) {
+    key_callback = callback;
+    glfwSetKeyCallback(window, on_glfw_key);
+}
+
+// GLFW mouse button callback, adds the cursor position to the event
+static void on_glfw_mouse_button(GLFWwindow* source, int button, int action, int mods) {
+    double x, y;
+    if (mouse_button_callback != NULL) {
+        glfwGetCursorPos(source, &x, &y);
+        mouse_button_callback(source, button, action, mods, x, y);
+    }
+}
+
+// GLFW scroll callback
+static void on_glfw_scroll(GLFWwindow* source, double x_offset, double y_offset) {
+    if (scroll_callback != NULL) {
+        scroll_callback(source, x_offset, y_offset);
+    }
+}
+
+// Function to receive the mouse button and scroll events of a window, delivered while
+// windows are updated
+void set_mouse_callbacks(GLFWwindow* window, mouse_button_event_callback button_callback, scroll_event_callback wheel_callback) {
+    mouse_button_callback = button_callback;
+    scroll_callback = wheel_callback;
+    glfwSetMouseButtonCallback(window, on_glfw_mouse_button);
+    glfwSetScrollCallback(window, on_glfw_scroll);
+}
+
+// Function to get the cursor position in window coordinates
+void get_cursor_position(GLFWwindow* window, double *x, double *y) {
+    glfwGetCursorPos(window, x, y);
+}
+
+// Function to get mouse button state
+int get_mouse_button(GLFWwindow* window, int button) {
+    return glfwGetMouseButton(window, button);
+}
+
+// GLFW framebuffer size callback
+static void on_glfw_framebuffer_size(GLFWwindow* source, int width, int height) {
+    if (resize_callback != NULL) {
+        resize_callback(source, width, height);
+    }
+}
+
+// Function to receive the resizes of a window, delivered while windows are updated
+void set_resize_callback(GLFWwindow* window, resize_event_callback callback) {
+    resize_callback = callback;
+    glfwSetFramebufferSizeCallback(window, on_glfw_framebuffer_size);
+}
+
+// Function to draw a logical_width x logical_height area into a part of the window
+void set_viewport(GLFWwindow* window, int x, int y, int width, int height, int logical_width, int logical_height) {
+    int window_width, window_height;
+    glfwGetFramebufferSize(window, &window_width, &window_height);
+
+    use_window(window);
+    // Triangles batched so far are drawn with the previous projection
+    flush_batch();
+    // OpenGL viewports start at the bottom-left corner
+    glViewport(x, window_height - y - height, width, height);
+
+    glMatrixMode(GL_PROJECTION);
+    glLoadIdentity();
+    glOrtho(0, logical_width, logical_height, 0, -1, 1); // Origin at top-left
+    glMatrixMode(GL_MODELVIEW);
+    glLoadIdentity();
+}
+
+// Function to get the time in seconds since the first window was created
+double get_time() {
+    return glfwGetTime();
+}
+
+// Function to get the size of the window in pixels
+void get_window_size(GLFWwindow* window, int *width, int *height) {
+    glfwGetFramebufferSize(window, width, height);
+}
+
+// Function to read back the RGB pixels drawn so far, bottom row first
+void read_pixels(GLFWwindow* window, unsigned char *pixels, int width, int height) {
+    use_window(window);
+    flush_batch();
+    glPixelStorei(GL_PACK_ALIGNMENT, 1);
+    glReadPixels(0, 0, width, height, GL_RGB, GL_UNSIGNED_BYTE, pixels);
 }
 
 // Function to draw a text using GLUT. glutinit must be called before hand (from claud ai)
-void renderText(const char* text, float x, float y, float scale, float r, float g, float b) {
+void renderText(GLFWwindow* window, const char* text, float x, float y, float scale, float r, float g, float b) {
+    use_window(window);
+    // Text is drawn over the sprites and polygons rendered before it
+    flush_batch();
     glPushMatrix();
     glLoadIdentity();
     
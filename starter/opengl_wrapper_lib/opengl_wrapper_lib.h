@@ -8,37 +8,99 @@
 typedef struct {
     int width;
     int height;
-    int color[3]; // RGB color
+    int color[4]; // RGBA color, tinting the texture of textured sprites. Alpha 255 is opaque
     float x, y; // Position
+    unsigned int texture; // Texture from create_texture, 0 for a solid color sprite
+    float tex_coords[4]; // Part of the texture drawn: left, top, right and bottom from 0 to 1
+    float rotation; // Rotation around the origin, in degrees clockwise
+    float scale[2]; // Horizontal and vertical scale around the origin
+    float origin[2]; // Pivot of rotation and scale, from 0 to 1 across the sprite
 } Sprite;
 
-// Function to create a game window
-void create_game_window(const char *title, int width, int height);
+// Callback receiving the GLFW key events of a game window
+typedef void (*key_event_callback)(GLFWwindow* window, int key, int scancode, int action, int mods);
+
+// Callback receiving the GLFW mouse button events of a game window with the cursor position
+typedef void (*mouse_button_event_callback)(GLFWwindow* window, int button, int action, int mods, double x, double y);
+
+// Callback receiving the GLFW scroll offsets of a game window
+typedef void (*scroll_event_callback)(GLFWwindow* window, double x_offset, double y_offset);
+
+// Callback receiving the new size of a game window in pixels
+typedef void (*resize_event_callback)(GLFWwindow* window, int width, int height);
+
+// Function to create a game window, which becomes the target of the next draw calls
+GLFWwindow* create_game_window(const char *title, int width, int height);
+
+// Function to close a game window
+void destroy_game_window(GLFWwindow* window);
+
+// Function to make a window the target of the next draw calls
+void make_window_current(GLFWwindow* window);
 
 // Function to create a sprite
 Sprite* create_sprite(float x, float y, int width, int height, int r, int g, int b);
 
-// Function to render a sprite
-void render_sprite(Sprite *sprite);
+// Function to destroy a sprite
+void destroy_sprite(Sprite *sprite);
+
+// Function to render a sprite. Sprites and polygons are batched, and drawn together when
+// the window is updated or read back
+void render_sprite(GLFWwindow* window, Sprite *sprite);
+
+// Function to fill a convex polygon given as count x, y pairs with an RGBA color
+void render_polygon(GLFWwindow* window, const float *points, int count, int r, int g, int b, int a);
+
+// Function to create a texture from RGBA pixels, top row first. Textures are shared by
+// all game windows
+unsigned int create_texture(const unsigned char *pixels, int width, int height);
 
-// Function to update a sprite position
-void update_sprite_position(Sprite *sprite, float x, float y);
+// Function to destroy a texture created with create_texture
+void destroy_texture(unsigned int texture);
 
-// Function to update the game window
-void update_game_window();
+// Function to get the largest width and height of a texture, a window must be created first
+int get_max_texture_size();
+
+// Function to update the game window. Events of every window are processed
+void update_game_window(GLFWwindow* window);
 
 // Function to clear the screen
-void clear_screen();
+void clear_screen(GLFWwindow* window);
 
 // Function to check if the window should close
-int window_should_close();
+int window_should_close(GLFWwindow* window);
 
 // Function to get key state
 int get_key(GLFWwindow* window, int key);
 
-// Function to get the window pointer
-GLFWwindow* get_window();
+// Function to receive the key events of a window, delivered while windows are updated
+void set_key_callback(GLFWwindow* window, key_event_callback callback);
+
+// Function to receive the mouse button and scroll events of a window, delivered while
+// windows are updated
+void set_mouse_callbacks(GLFWwindow* window, mouse_button_event_callback button_callback, scroll_event_callback wheel_callback);
+
+// Function to get the cursor position in window coordinates
+void get_cursor_position(GLFWwindow* window, double *x, double *y);
+
+// Function to get mouse button state
+int get_mouse_button(GLFWwindow* window, int button);
+
+// Function to receive the resizes of a window, delivered while windows are updated
+void set_resize_callback(GLFWwindow* window, resize_event_callback callback);
+
+// Function to draw a logical_width x logical_height area into a part of the window
+void set_viewport(GLFWwindow* window, int x, int y, int width, int height, int logical_width, int logical_height);
+
+// Function to get the time in seconds since the first window was created
+double get_time();
+
+// Function to get the size of the window in pixels
+void get_window_size(GLFWwindow* window, int *width, int *height);
+
+// Function to read back the RGB pixels drawn so far, bottom row first
+void read_pixels(GLFWwindow* window, unsigned char *pixels, int width, int height);
 
-void renderText(const char* text, float x, float y, float scale, float r, float g, float b);
+void renderText(GLFWwindow* window, const char* text, float x, float y, float scale, float r, float g, float b);
 
 #endif // OPENGL_WRAPPER_LIB_H
@@ -0,0 +1,45 @@
+//! arena.rs
+//!
+//! Walls of the arena the snakes crawl in, from one of the layouts of the `assets/arenas`
+//! directory picked at random for each game. Layouts are drawn as text, `#` being a wall,
+//! on cells the size of a snake body part. Gaps in the outer walls let the snakes wrap
+//! around the window.
+
+use rand::prelude::*;
+
+use my_game_engine::collision::Rect;
+use my_game_engine::tilemap::{Tile, TileMap, Tileset};
+
+/// Walls are drawn over the grid, food never lies on them
+pub const WALL_LAYER: i32 = 0;
+/// Snakes start here when asked to start in a wall, every layout keeps it clear
+const SPAWN_POSITION: (f32, f32) = (100.0, 100.0);
+/// Room the snakes need to start, around the user's snake and its buddy
+const SPAWN_ROOM: f32 = 50.0;
+
+const LAYOUTS: [&str; 3] = [
+    include_str!("../assets/arenas/box.txt"),
+    include_str!("../assets/arenas/pillars.txt"),
+    include_str!("../assets/arenas/maze.txt"),
+];
+
+/// The walls of one of the layouts, with `cell_side` pixel cells
+pub fn random(cell_side: i32) -> TileMap {
+    let layout = LAYOUTS.choose(&mut rand::rng()).expect("no arena layouts");
+    load(layout, cell_side)
+}
+
+/// Walls of a layout drawn as text
+pub fn load(layout: &str, cell_side: i32) -> TileMap {
+    let mut tileset = Tileset::new();
+    let wall = tileset.add(Tile::color(70, 80, 110).with_solid(true));
+    TileMap::from_text(layout, &[('#', wall)], cell_side, cell_side, tileset).with_layer(WALL_LAYER)
+}
+
+/// Where the snakes start: `x`, `y` when there is room, and a clear spot otherwise
+pub fn spawn_position(walls: &TileMap, x: f32, y: f32) -> (f32, f32) {
+    match walls.overlaps_solid(&Rect::new(x, y, SPAWN_ROOM, SPAWN_ROOM)) {
+        true => SPAWN_POSITION,
+        false => (x, y),
+    }
+}
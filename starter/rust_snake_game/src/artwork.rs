@@ -0,0 +1,87 @@
+//! artwork.rs
+//!
+//! Textures of the snakes and the food, loaded from the `assets` directory. They are
+//! drawn tinted with the color of each snake or food item, so they are mostly white. An
+//! image that can't be loaded falls back to a solid color sprite.
+//!
+//! The head and the food are sprite sheets of 16x16 frames: the head blinks its eyes, and
+//! the food pulses, then blinks before it expires.
+
+use std::path::Path;
+use std::time::Duration;
+
+use my_game_engine::animation::{Animation, PlayMode, SpriteSheet};
+use my_game_engine::texture::Texture;
+use my_game_engine::LOAD_TEXTURE;
+
+const ASSETS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets");
+/// Side of a frame in the sprite sheets
+const FRAME_SIDE: i32 = 16;
+
+/// Head frames: eyes open, half closed and closed
+const HEAD_BLINK: [(usize, Duration); 4] = [
+    (0, Duration::from_millis(2500)),
+    (1, Duration::from_millis(60)),
+    (2, Duration::from_millis(100)),
+    (1, Duration::from_millis(60)),
+];
+/// Food frames from the largest to the smallest
+const FOOD_PULSE: [usize; 3] = [0, 1, 2];
+const FOOD_PULSE_FRAME: Duration = Duration::from_millis(150);
+/// The last food frame is empty, alternating with it makes the food blink
+const FOOD_BLINK: [usize; 2] = [0, 3];
+const FOOD_BLINK_FRAME: Duration = Duration::from_millis(120);
+
+/// All the textures of the game, `None` when missing
+#[derive(Clone, Default)]
+pub struct Artwork {
+    pub snake_head: Option<SpriteSheet>,
+    pub snake_body: Option<Texture>,
+    pub food: Option<SpriteSheet>,
+}
+
+impl Artwork {
+    /// Load the textures. Must be called once the game window is created
+    pub fn load() -> Artwork {
+        Artwork {
+            snake_head: load_texture("snake_head.png").map(sprite_sheet),
+            snake_body: load_texture("snake_body.png"),
+            food: load_texture("food.png").map(sprite_sheet),
+        }
+    }
+
+    /// Eyes of the snake head, blinking every few seconds
+    pub fn head_animation(&self) -> Option<Animation> {
+        self.snake_head
+            .as_ref()
+            .map(|sheet| sheet.timed_animation(HEAD_BLINK, PlayMode::Loop))
+    }
+
+    /// Food growing and shrinking while it can be eaten
+    pub fn food_animation(&self) -> Option<Animation> {
+        self.food
+            .as_ref()
+            .map(|sheet| sheet.animation(FOOD_PULSE, FOOD_PULSE_FRAME, PlayMode::PingPong))
+    }
+
+    /// Food blinking when it is about to expire
+    pub fn expiring_food_animation(&self) -> Option<Animation> {
+        self.food
+            .as_ref()
+            .map(|sheet| sheet.animation(FOOD_BLINK, FOOD_BLINK_FRAME, PlayMode::Loop))
+    }
+}
+
+fn sprite_sheet(texture: Texture) -> SpriteSheet {
+    SpriteSheet::new(&texture, FRAME_SIDE, FRAME_SIDE)
+}
+
+fn load_texture(name: &str) -> Option<Texture> {
+    match LOAD_TEXTURE!(Path::new(ASSETS_DIR).join(name)) {
+        Ok(texture) => Some(texture),
+        Err(e) => {
+            println!("Can't load {name}: {e}, using a solid color instead");
+            None
+        }
+    }
+}
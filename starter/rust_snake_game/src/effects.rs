@@ -0,0 +1,41 @@
+//! effects.rs
+//!
+//! Particle effects of the game: a burst of the color of the food when a snake eats it,
+//! and an explosion when the user's snake dies. Effects are bursts, finished once their
+//! particles faded out.
+
+use my_game_engine::particles::Emitter;
+
+use crate::snake::SNAKE_LAYER;
+
+/// Effects are drawn over the snakes and the head of a dead one
+pub const EFFECTS_LAYER: i32 = SNAKE_LAYER + 2;
+
+const FOOD_BURST_PARTICLES: usize = 24;
+const EXPLOSION_PARTICLES: usize = 250;
+
+/// Crumbs of the color of food eaten at `x`, `y`, scattering around
+pub fn food_burst(x: f32, y: f32, color: [i32; 4]) -> Emitter {
+    let [r, g, b, _] = color;
+    let mut burst = Emitter::new(x, y)
+        .with_speed(40.0, 140.0)
+        .with_lifetime(0.25, 0.5)
+        .with_colors([r, g, b, 255], [r, g, b, 0])
+        .with_sizes(5.0, 1.0)
+        .with_layer(EFFECTS_LAYER);
+    burst.burst(FOOD_BURST_PARTICLES);
+    burst
+}
+
+/// Sparks flying from `x`, `y` and falling, from white hot to dark red
+pub fn explosion(x: f32, y: f32) -> Emitter {
+    let mut explosion = Emitter::new(x, y)
+        .with_speed(60.0, 320.0)
+        .with_lifetime(0.5, 1.2)
+        .with_gravity(0.0, 250.0)
+        .with_colors([255, 255, 200, 255], [200, 20, 0, 0])
+        .with_sizes(7.0, 2.0)
+        .with_layer(EFFECTS_LAYER);
+    explosion.burst(EXPLOSION_PARTICLES);
+    explosion
+}
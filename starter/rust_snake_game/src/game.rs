@@ -17,14 +17,23 @@ use std::time;
 use rand::prelude::*;
 const SPRIDE_SIDE: i32 = 25; // TODO: merge with the one in main.rs
 
+use crate::artwork::Artwork;
+use crate::effects::{self, EFFECTS_LAYER};
 use crate::remote;
 use crate::remote::SpriteData;
-use crate::snake::{Snake, SnakeKind, SnakeMovement};
-
-use my_game_engine::game_ffi;
+use crate::snake::{Snake, SnakeKind, SnakeMovement, SNAKE_LAYER};
+use crate::sounds::{self, Sounds};
+
+use my_game_engine::animation::Animation;
+use my_game_engine::particles::Emitter;
+use my_game_engine::shape::Shape;
+use my_game_engine::spatial::SpatialGrid;
+use my_game_engine::sprite::SpriteHandle;
+use my_game_engine::tilemap::TileMap;
+use my_game_engine::window;
 use my_game_engine::{
-    SPAWN_SPRITE, SPRITE_ATTR, SPRITE_HEIGHT, SPRITE_WIDTH, SPRITE_X, SPRITE_Y, TEXT_RENDER,
-    CLEAR_SCREEN
+    ANIMATE_SPRITE, CLEAR_SCREEN, DUPE_SPRITE, ROTATE_SPRITE, SPAWN_SPRITE, SPRITE_ROTATION,
+    SPRITE_WIDTH, SPRITE_X, SPRITE_Y, TEXT_RENDER,
 };
 
 use core::cmp::PartialEq;
@@ -33,55 +42,49 @@ use core::cmp::PartialEq;
 const FOOD_UPDATE_EVERY: time::Duration = time::Duration::from_millis(500);
 /// Food will remain visible on the screen for this duration
 const FOOD_EXPIRES_IN: time::Duration = time::Duration::from_secs(100);
+/// Food starts blinking when it expires in less than this duration
+const FOOD_BLINKS_BEFORE_EXPIRY: time::Duration = time::Duration::from_secs(3);
+/// The grid is drawn under everything
+const GRID_LAYER: i32 = -1;
+/// Distance between the grid lines
+const GRID_SPACING: i32 = 50;
+/// Food is drawn under the snakes
+const FOOD_LAYER: i32 = 0;
+/// The head of a dead snake is drawn over everything but the score
+const DEAD_HEAD_LAYER: i32 = SNAKE_LAYER + 1;
+/// Debug outlines are drawn over the sprites and the effects
+const DEBUG_LAYER: i32 = EFFECTS_LAYER + 1;
+/// Size of the cells indexing the food, a few food items wide
+const FOOD_GRID_CELL: f32 = 2.0 * SPRIDE_SIDE as f32;
+/// Power-up food spins at this speed, in degrees per second
+const POWER_UP_SPIN_SPEED: f32 = 180.0;
+/// Points scored by eating power-up food, instead of one
+const POWER_UP_SCORE: i32 = 5;
+/// whether to outline the boxes used to detect collisions
+const DEBUG_OUTLINES: bool = false;
 /// whether to use simulated sprite generation or actually go over the network
 const REMOTE_SPRITE_FETCH_DEBUG: bool = true;
 
-/// Bad food kill a snake
-#[derive(PartialEq, Clone)]
+/// Bad food kill a snake, power-ups are worth more points
+#[derive(PartialEq)]
 enum FoodType {
     Good,
     Bad,
+    PowerUp,
 }
 
 /// Each food item has an expiry time, after which it will 
 /// disappear from the screen.
-#[derive(PartialEq, Clone)]
 pub struct Food {
-    sprite: *mut game_ffi::Sprite,
+    /// Key of the food in the grid of food bounds
+    id: u64,
+    sprite: SpriteHandle,
     expires: time::Instant,
     food_type: FoodType,
-}
-
-/// This macro check whether two sprite overlap
-macro_rules! CHECK_SPRITE_OVERLAP {
-    ($s1:expr, $s2:expr) => {{
-        let mut inside: bool = false;
-        let x1 = SPRITE_X!($s1);
-        let y1 = SPRITE_Y!($s1);
-        let x2 = SPRITE_X!($s2);
-        let y2 = SPRITE_Y!($s2);
-        // gather all corners in sprite number 2
-        let corners = vec![
-            (x2, y2),
-            (x2 + SPRITE_WIDTH!($s2) as f32, y2),
-            (x2, y2 + SPRITE_HEIGHT!($s2) as f32),
-            (
-                x2 + SPRITE_WIDTH!($s2) as f32,
-                y2 + SPRITE_HEIGHT!($s2) as f32,
-            ),
-        ];
-
-        // check whether each corner in sprite 2 is contained in sprite 1
-        for (x, y) in corners {
-            if (x >= x1 && x <= x1 + SPRITE_WIDTH!($s1) as f32)
-                && (y >= y1 && y < y1 + SPRITE_HEIGHT!($s1) as f32)
-            {
-                inside = true;
-                break;
-            }
-        }
-        inside
-    }};
+    /// Pulsing, then blinking before expiry. `None` for solid color food
+    animation: Option<Animation>,
+    /// Whether the food is about to expire and blinks
+    blinking: bool,
 }
 
 /// The main game structure
@@ -90,6 +93,10 @@ pub struct Game {
     snakes: Vec<Snake>,
     /// Tracks all food items fetched and visible on the game window
     food: Vec<Food>,
+    /// Bounds of the food, to only check the food around the snake heads
+    food_grid: SpatialGrid<u64>,
+    /// Id of the next food item
+    next_food_id: u64,
     /// Game will emit new food request based on how long it has been since the last fetch
     last_food_fetched: time::Instant,
     /// Whether the game is runnin
@@ -101,6 +108,14 @@ pub struct Game {
     ),
     /// Current user score
     score: i32,
+    /// Textures of the food items
+    artwork: Artwork,
+    /// Sound effects of the food eaten and the game over
+    sounds: Sounds,
+    /// Walls of the arena, `None` in an open arena
+    walls: Option<TileMap>,
+    /// Particle effects of the food eaten and the death of the user's snake
+    effects: Vec<Emitter>,
 }
 
 /// background spawned thread function to invoke remote sprite request
@@ -191,13 +206,25 @@ impl Game {
         let (sender_main, receiver_remote) = unbounded(); // one way from main to background
         let (sender_remote, receiver_main) = unbounded(); // one way from backgroun to main
 
+        let mut food_grid = SpatialGrid::new(FOOD_GRID_CELL);
+        for item in food.iter() {
+            food_grid.insert(item.id, item.sprite.bounds());
+        }
+        let next_food_id = food.iter().map(|item| item.id + 1).max().unwrap_or(0);
+
         let game = Game {
-            snakes: snakes,
-            food: food,
+            snakes,
+            food,
+            food_grid,
+            next_food_id,
             last_food_fetched: time::Instant::now(),
             running: Arc::new(Mutex::new(true)),
             channels: (sender_main, receiver_main),
             score: 0,
+            artwork: Artwork::default(),
+            sounds: Sounds::default(),
+            walls: None,
+            effects: Vec::new(),
         };
 
         let thread_sender = sender_remote.clone();
@@ -226,25 +253,114 @@ impl Game {
         game
     }
 
-    /// Render the next step of the game. This will update internal state related to snake
-    /// and food and render the window. It should be called once at every step in the game loop
-    pub fn render(&mut self) {
-        CLEAR_SCREEN!();
+    /// Draw the food with the animated food sprite sheet of `artwork` instead of solid squares
+    pub fn with_artwork(mut self, artwork: &Artwork) -> Game {
+        self.artwork = artwork.clone();
+        self
+    }
 
-        match self.render_snakes() {
-            Err(_e) => self.die(),
-            Ok(_) => {}
+    /// Play the sound effects of `sounds` when food is eaten and the game is over
+    pub fn with_sounds(mut self, sounds: &Sounds) -> Game {
+        self.sounds = sounds.clone();
+        self
+    }
+
+    /// Draw the walls of the arena, and keep food out of them. The snakes are given the
+    /// walls they run into with `Snake::with_walls`
+    pub fn with_walls(mut self, walls: &TileMap) -> Game {
+        self.walls = Some(walls.clone());
+        self
+    }
+
+    /// Simulate the next `dt` seconds of the game. This will update internal state related
+    /// to snakes and food. It should be called at every step of a fixed-timestep loop, and
+    /// keeps playing the effects once the game is over
+    pub fn update(&mut self, dt: f32) {
+        self.update_effects(dt);
+
+        if !self.running() {
+            return;
+        }
+
+        if self.update_snakes(dt).is_err() {
+            self.die();
         }
 
-        self.render_food();
+        self.update_food(dt);
+    }
+
+    /// Render the current state of the game on the window. It should be called once per frame.
+    /// Everything is drawn on its own layer, whatever the order of the calls
+    pub fn render(&self) {
+        CLEAR_SCREEN!();
 
         self.render_score();
+
+        if !self.running() {
+            self.render_dead_user_snake();
+        }
+
+        for snake in self.snakes.iter() {
+            snake.render();
+        }
+
+        self.food.iter().for_each(render_food);
+
+        if let Some(walls) = &self.walls {
+            walls.render();
+        }
+
+        for effect in self.effects.iter() {
+            effect.render();
+        }
+
+        self.render_grid();
+
+        if DEBUG_OUTLINES {
+            self.render_debug_outlines();
+        }
+    }
+
+    /// Faint lines across the window
+    fn render_grid(&self) {
+        let (width, height) = window::logical_size();
+        let vertical = (GRID_SPACING..width)
+            .step_by(GRID_SPACING as usize)
+            .map(|x| Shape::line(x as f32, 0.0, x as f32, height as f32, 1.0));
+        let horizontal = (GRID_SPACING..height)
+            .step_by(GRID_SPACING as usize)
+            .map(|y| Shape::line(0.0, y as f32, width as f32, y as f32, 1.0));
+
+        for line in vertical.chain(horizontal) {
+            line.with_rgba(255, 255, 255, 24)
+                .with_layer(GRID_LAYER)
+                .render();
+        }
+    }
+
+    /// Outline the bounds checked for collisions: the snake heads and the food
+    fn render_debug_outlines(&self) {
+        let heads = self
+            .snakes
+            .iter()
+            .filter_map(|snake| snake.head())
+            .map(|head| &head.sprite);
+        let food = self.food.iter().map(|food| &food.sprite);
+
+        for sprite in heads.chain(food) {
+            let bounds = sprite.bounds();
+            Shape::rect(bounds.x, bounds.y, bounds.width, bounds.height)
+                .with_color(255, 0, 255)
+                .with_outline(1.0)
+                .with_layer(DEBUG_LAYER)
+                .render();
+        }
     }
 
     fn render_score(&self) {
         let score = self.score.to_string();
         let score_text = C_STRING!(format!("score={score}"));
-        TEXT_RENDER!(score_text, 0.0, 20.0, 100.0, 255.0, 0.0, 0.0);
+        TEXT_RENDER!(score_text, 0.0, 20.0, 1.0, 255.0, 0.0, 0.0);
     }
 
     pub fn stop(&mut self) {
@@ -253,9 +369,26 @@ impl Game {
         *running = false;
     }
 
+    /// Whether the effects are over, e.g. the explosion of the user's snake
+    pub fn effects_finished(&self) -> bool {
+        self.effects.is_empty()
+    }
+
     pub fn die(&mut self) {
+        sounds::play(&self.sounds.game_over);
+        let user_snake_head = self
+            .snakes
+            .iter()
+            .find(|snake| snake.kind == SnakeKind::USER)
+            .and_then(|snake| snake.head());
+        if let Some(head) = user_snake_head {
+            let (x, y) = head.sprite.bounds().center();
+            self.effects.push(effects::explosion(x, y));
+        }
         self.stop();
+    }
 
+    fn render_dead_user_snake(&self) {
         // draw the user's head in white
         let user_snake_head = self
             .snakes
@@ -267,35 +400,43 @@ impl Game {
             .head()
             .expect("Can't find snake head!");
 
-        SPAWN_SPRITE!(
-            true,
+        let mut dead_head = DUPE_SPRITE!(
+            user_snake_head.sprite,
             SPRITE_X!(user_snake_head.sprite),
-            SPRITE_Y!(user_snake_head.sprite),
-            SPRITE_WIDTH!(user_snake_head.sprite),
-            SPRITE_HEIGHT!(user_snake_head.sprite),
-            250,
-            255,
-            255
+            SPRITE_Y!(user_snake_head.sprite)
         );
+        dead_head.set_color(250, 255, 255);
+        dead_head.set_layer(DEAD_HEAD_LAYER);
+        dead_head.render();
     }
 
     pub fn running(&self) -> bool {
         *self.running.lock().unwrap()
     }
 
-    /// Render Snake and return whether game should continue or not
-    fn render_snakes(&mut self) -> Result<(), String> {
+    /// Move the snakes and return whether game should continue or not
+    fn update_snakes(&mut self, dt: f32) -> Result<(), String> {
         for snake in self.snakes.iter_mut() {
-            snake.crawl();
+            snake.crawl(dt);
 
-            // check if snake has encountered food
+            if snake.dies_from_walls() && snake.hits_wall() {
+                return Err(String::from("Snake hit a wall!"));
+            }
+
+            // check if snake has encountered food, and take it out of the game
             let food_consumed: Vec<Food> = match snake.head() {
-                Some(head) => self
-                    .food
-                    .iter()
-                    .cloned()
-                    .filter(|food| CHECK_SPRITE_OVERLAP!(food.sprite, head.sprite))
-                    .collect::<Vec<Food>>(),
+                Some(head) => {
+                    let eaten_ids = self.food_grid.query(&head.sprite.bounds());
+                    for id in eaten_ids.iter() {
+                        self.food_grid.remove(*id);
+                    }
+                    let (eaten, remaining): (Vec<Food>, Vec<Food>) = self
+                        .food
+                        .drain(..)
+                        .partition(|food| eaten_ids.contains(&food.id));
+                    self.food = remaining;
+                    eaten
+                }
                 None => {
                     vec![]
                 }
@@ -311,34 +452,55 @@ impl Game {
                         != 0
                 {
                     // bad food eaten, die!
+                    sounds::play(&self.sounds.bad_food);
                     return Err(String::from("Snake At bad food!"));
                 }
 
                 if snake.is_owned_by_user() {
-                    self.score += food_consumed.len() as i32;
+                    self.score += food_consumed
+                        .iter()
+                        .map(|food| match food.food_type {
+                            FoodType::PowerUp => POWER_UP_SCORE,
+                            _ => 1,
+                        })
+                        .sum::<i32>();
+                }
+
+                for food in food_consumed.iter() {
+                    let (x, y) = food.sprite.bounds().center();
+                    let color = food.sprite.color();
+                    self.effects.push(effects::food_burst(x, y, color));
                 }
 
-                snake.grow();
+                snake.grow(dt);
+                sounds::play(&self.sounds.eat);
 
-                // remove food items
-                self.food.retain(|food| !food_consumed.contains(&food));
                 println!("food eaten! remaining food {}", self.food.len());
             }
-
-            // render the snake on the screen
-            snake.render();
         }
 
         Ok(())
     }
 
-    fn render_food(&mut self) {
+    /// Play the effects, and drop the finished ones
+    fn update_effects(&mut self, dt: f32) {
+        for effect in self.effects.iter_mut() {
+            effect.update(dt);
+        }
+        self.effects.retain(|effect| !effect.is_finished());
+    }
+
+    fn update_food(&mut self, dt: f32) {
         // cleanup
         self.remove_expired_food();
+        self.animate_food(dt);
 
         // is there new food
         let mut new_food: Vec<Food> = Vec::new();
         self.check_new_food_downloaded(&mut new_food);
+        for food in new_food.iter() {
+            self.food_grid.insert(food.id, food.sprite.bounds());
+        }
         self.food.append(&mut new_food);
 
         // request new food
@@ -346,22 +508,48 @@ impl Game {
             self.request_new_food();
             self.last_food_fetched = time::Instant::now();
         }
-
-        //render
-        unsafe {
-            self.food.iter().for_each(move |food| {
-                game_ffi::render_sprite(food.sprite);
-            });
-        }
     }
 
     // Check whether any food has expired and remove it
     fn remove_expired_food(&mut self) {
-        self.food.retain(|x| x.expires.elapsed() < FOOD_EXPIRES_IN);
+        let food_grid = &mut self.food_grid;
+        self.food.retain(|x| {
+            let fresh = x.expires.elapsed() < FOOD_EXPIRES_IN;
+            if !fresh {
+                food_grid.remove(x.id);
+            }
+            fresh
+        });
+    }
+
+    // Play the food animations, switching to blinking when the food is about to expire, and
+    // spin the power-ups
+    fn animate_food(&mut self, dt: f32) {
+        for food in self.food.iter_mut() {
+            if food.food_type == FoodType::PowerUp {
+                let rotation = SPRITE_ROTATION!(food.sprite) + POWER_UP_SPIN_SPEED * dt;
+                ROTATE_SPRITE!(food.sprite, rotation % 360.0);
+                // spinning changes the bounds
+                self.food_grid.update(food.id, food.sprite.bounds());
+            }
+
+            let expires_in = FOOD_EXPIRES_IN.saturating_sub(food.expires.elapsed());
+            if !food.blinking && expires_in < FOOD_BLINKS_BEFORE_EXPIRY {
+                food.blinking = true;
+                food.animation = self.artwork.expiring_food_animation();
+            }
+
+            if let Some(animation) = &mut food.animation {
+                ANIMATE_SPRITE!(food.sprite, animation, dt);
+            }
+        }
     }
 
-    fn check_new_food_downloaded(&self, new_food: &mut Vec<Food>) {
+    fn check_new_food_downloaded(&mut self, new_food: &mut Vec<Food>) {
+        let artwork = &self.artwork;
         let receiver = &self.channels.1;
+        let next_food_id = &mut self.next_food_id;
+        let walls = &self.walls;
 
         if !receiver.is_empty() {
             receiver.try_iter().for_each(move |mut sprite_data| {
@@ -371,22 +559,45 @@ impl Game {
                     sprite_data.g = 0;
                     sprite_data.b = 0;
                     food_type = FoodType::Bad;
+                } else if sprite_data.g > 230 {
+                    // a few lucky items are golden power-ups
+                    sprite_data.r = 255;
+                    sprite_data.g = 215;
+                    sprite_data.b = 0;
+                    food_type = FoodType::PowerUp;
+                }
+
+                let mut sprite = SPAWN_SPRITE!(
+                    false,
+                    sprite_data.x,
+                    sprite_data.y,
+                    SPRIDE_SIDE,
+                    SPRIDE_SIDE,
+                    sprite_data.r,
+                    sprite_data.g,
+                    sprite_data.b
+                );
+                sprite.set_layer(FOOD_LAYER);
+                // out of reach
+                if let Some(walls) = walls {
+                    if walls.overlaps_solid(&sprite.bounds()) {
+                        return;
+                    }
+                }
+                let animation = artwork.food_animation();
+                if let Some(animation) = &animation {
+                    animation.apply(&mut sprite);
                 }
 
                 new_food.push(Food {
-                    sprite: SPAWN_SPRITE!(
-                        false,
-                        sprite_data.x,
-                        sprite_data.y,
-                        SPRIDE_SIDE,
-                        SPRIDE_SIDE,
-                        sprite_data.r,
-                        sprite_data.g,
-                        sprite_data.b
-                    ),
+                    id: *next_food_id,
+                    sprite,
                     food_type,
                     expires: time::Instant::now(),
+                    animation,
+                    blinking: false,
                 });
+                *next_food_id += 1;
             });
         }
     }
@@ -398,6 +609,26 @@ impl Game {
     }
 }
 
+/// Draw a food item with its artwork, or as a round pellet of its color without artwork
+fn render_food(food: &Food) {
+    if food.animation.is_some() {
+        food.sprite.render();
+        return;
+    }
+
+    let sprite = &food.sprite;
+    let [r, g, b, _] = sprite.color();
+    let radius = SPRITE_WIDTH!(sprite) as f32 / 2.0;
+    Shape::circle(
+        SPRITE_X!(sprite) + radius,
+        SPRITE_Y!(sprite) + radius,
+        radius,
+    )
+    .with_color(r, g, b)
+    .with_layer(FOOD_LAYER)
+    .render();
+}
+
 impl Drop for Game {
     fn drop(&mut self) {
         println!("drop called");
@@ -9,16 +9,38 @@
 //! food and can thus be used as a helper. A third kind of snake is deployed that moves autonomously
 //! across the window, creating a bit of distraction to the user. This snake also doesn't die.
 //! 
+//! The user's snake is steered with the arrow keys or WASD, and `escape` quits the game.
+//! `F3` shows or hides frame statistics at the bottom of the window.
+//! Eating food and losing play sound effects, heard when built with the `audio-device` feature.
+//!
+//! Each game takes place in an arena with walls, picked at random. The user's snake dies when
+//! it runs into a wall, while the other snakes turn away from them. Food bursts into crumbs
+//! when eaten, and the user's snake explodes when it dies.
+//!
 //! Games can be restarted when a snake dies by pressing the `space` bar. A top left scrore box
 //! shows the current score from eating food, counted those eaten by both the user and the buddy
 //! snake
 
 use my_game_engine::game_ffi;
-use my_game_engine::{C_STRING, ON_KEY_PRESS, START_WINDOW_AND_GAME_LOOP, TEXT_RENDER, TICK, CREATE_GAME};
+use my_game_engine::key::Key;
+use my_game_engine::stats;
+use my_game_engine::text::{Align, TextBox, VerticalAlign};
+use my_game_engine::timestep::FixedTimestep;
+use my_game_engine::window::{self, Scaling};
+use my_game_engine::{
+    CREATE_GAME, C_STRING, ON_KEY_DOWN, START_FIXED_TIMESTEP_LOOP, START_WINDOW_AND_GAME_LOOP,
+};
 use remote::SpriteData;
 
 use std::ffi::CString;
-use std::{thread, time};
+use std::time;
+
+mod arena;
+
+mod artwork;
+use artwork::Artwork;
+
+mod effects;
 
 mod game;
 use game::Game;
@@ -27,46 +49,83 @@ mod snake;
 use snake::Snake;
 use snake::SnakeKind;
 
+mod sounds;
+use sounds::Sounds;
+
 mod remote;
 use reqwest::Error;
 
 use game_ffi::Window;
+use my_game_engine::tilemap::TileMap;
 
 const WINDOW_WIDTH: i32 = 800;
 const WINDOW_HEIGHT: i32 = 600;
 const SPRIDE_SIDE: i32 = 25;
 const LOOP_SLEEP_MS: time::Duration = time::Duration::from_millis(10);
+/// The game is simulated in steps of this duration, whatever the frame rate
+const SIMULATION_STEP: time::Duration = time::Duration::from_millis(10);
 const GAME_OVER_FLASH_EVERY_MS: time::Duration = time::Duration::from_millis(1000);
 
 #[doc(hidden)]
 fn render_game_over_message() {
     static mut RED: bool = true;
-    let score_text = C_STRING!("!! GAME OVER !! (space to restart)");
+    let score_text = "!! GAME OVER !! (space to restart)";
+
+    // centered in the window, over the snakes
+    let (width, height) = window::logical_size();
+    let message = TextBox::new(score_text, 0.0, 0.0)
+        .with_width(width as f32)
+        .with_height(height as f32)
+        .with_align(Align::Center)
+        .with_vertical_align(VerticalAlign::Middle)
+        .with_scale(1.25);
 
     unsafe {
         match RED {
             true => {
-                TEXT_RENDER!(score_text, 250.0, 300.0, 500.0, 255.0, 0.0, 0.0);
+                message.with_color(255, 0, 0).render();
                 RED = false;
             }
             false => {
-                TEXT_RENDER!(score_text, 250.0, 300.0, 500.0, 0.0, 255.0, 0.0);
+                message.with_color(0, 255, 0).render();
                 RED = true;
             }
         }
     }
 }
 
-/// Main loop where all game events are handled
-fn game_main_loop(game: &mut Game) {
-    START_WINDOW_AND_GAME_LOOP!(LOOP_SLEEP_MS, {
-        if game.running() {
-            game.render();
-        } else {
-            // break to game over loop
+/// Main loop where all game events are handled. Returns whether the user quit the game
+fn game_main_loop(game: &mut Game) -> bool {
+    let mut quit = false;
+
+    // snakes move a whole body part at a time, there is nothing to interpolate
+    START_FIXED_TIMESTEP_LOOP!(FixedTimestep::new(SIMULATION_STEP), |dt| {
+        ON_KEY_DOWN!(Key::Escape, {
+            quit = true;
+            break;
+        });
+
+        ON_KEY_DOWN!(Key::F3, {
+            match stats::overlay_shown() {
+                true => stats::hide_overlay(),
+                false => {
+                    let (_, height) = window::logical_size();
+                    stats::show_overlay(0.0, (height - 10) as f32)
+                }
+            }
+        });
+
+        game.update(dt);
+    }, |_alpha| {
+        game.render();
+
+        if !game.running() && game.effects_finished() {
+            // break to game over loop, once the snake exploded
             break;
         }
     });
+
+    quit
 }
 
 /// Handle the game over loop to allow restart
@@ -82,18 +141,29 @@ fn game_over_loop() -> bool {
             time = time::Instant::now();
         }
 
-        ON_KEY_PRESS!(game_ffi::GLFW_KEY_SPACE, {
+        ON_KEY_DOWN!(Key::Space, {
             // restart the game
             restart = true;
             break;
         });
+
+        ON_KEY_DOWN!(Key::Escape, {
+            break;
+        });
     });
 
-    return restart;
+    restart
 }
 
 /// Create several snakes that will be used in the game
-fn create_snakes(snakes: &mut Vec<Snake>, initial_sprite: &SpriteData) {
+fn create_snakes(
+    snakes: &mut Vec<Snake>,
+    initial_sprite: &SpriteData,
+    artwork: &Artwork,
+    walls: &TileMap,
+) {
+    let (x, y) = arena::spawn_position(walls, initial_sprite.x, initial_sprite.y);
+
     // Create soome snakes
     let user_snake = Snake::new(
         SnakeKind::USER,
@@ -102,8 +172,8 @@ fn create_snakes(snakes: &mut Vec<Snake>, initial_sprite: &SpriteData) {
             height: WINDOW_HEIGHT,
             sprite_side: SPRIDE_SIDE,
         },
-        initial_sprite.x,
-        initial_sprite.y,
+        x,
+        y,
         SPRIDE_SIDE,
         SPRIDE_SIDE,
         0,
@@ -118,8 +188,8 @@ fn create_snakes(snakes: &mut Vec<Snake>, initial_sprite: &SpriteData) {
             height: WINDOW_HEIGHT,
             sprite_side: SPRIDE_SIDE,
         },
-        initial_sprite.x + 25.0,
-        initial_sprite.y + 25.0,
+        x + 25.0,
+        y + 25.0,
         SPRIDE_SIDE,
         SPRIDE_SIDE,
         25,
@@ -134,8 +204,8 @@ fn create_snakes(snakes: &mut Vec<Snake>, initial_sprite: &SpriteData) {
             height: WINDOW_HEIGHT,
             sprite_side: SPRIDE_SIDE,
         },
-        initial_sprite.x + 25.0,
-        initial_sprite.y + 25.0,
+        x + 25.0,
+        y + 25.0,
         SPRIDE_SIDE,
         SPRIDE_SIDE,
         50,
@@ -143,9 +213,9 @@ fn create_snakes(snakes: &mut Vec<Snake>, initial_sprite: &SpriteData) {
         128,
     );
 
-    snakes.push(user_snake);
-    snakes.push(buddy_snake);
-    snakes.push(autonomous_snake);
+    snakes.push(user_snake.with_artwork(artwork).with_walls(walls));
+    snakes.push(buddy_snake.with_artwork(artwork).with_walls(walls));
+    snakes.push(autonomous_snake.with_artwork(artwork).with_walls(walls));
 }
 
 /// Main entrypoint for the program.
@@ -157,17 +227,27 @@ async fn main() -> Result<(), Error> {
     let initial_sprite = remote::request_sprite().await;
 
     CREATE_GAME!(C_STRING!("Snake Game"), WINDOW_WIDTH, WINDOW_HEIGHT);
+    // a bigger window gives the snakes more room
+    window::set_scaling(Scaling::Expand);
+    let artwork = Artwork::load();
+    let sounds = Sounds::load();
 
     loop {
         println!("NEW GAME!");
         let mut snakes: Vec<Snake> = vec![];
-        create_snakes(&mut snakes, &initial_sprite);
+        let walls = arena::random(SPRIDE_SIDE);
+        create_snakes(&mut snakes, &initial_sprite, &artwork, &walls);
 
         // Create the game
-        let mut game = game::Game::new(snakes, vec![]);
+        let mut game = game::Game::new(snakes, vec![])
+            .with_artwork(&artwork)
+            .with_sounds(&sounds)
+            .with_walls(&walls);
 
         // Main Game loop. returns when the games ends
-        game_main_loop(&mut game);
+        if game_main_loop(&mut game) {
+            break;
+        }
 
         // Game Over Loop
         if !game_over_loop() {
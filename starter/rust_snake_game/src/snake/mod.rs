@@ -4,21 +4,44 @@
 //! Expects a game to have already been instantiated.
 //!
 use core::cmp::PartialEq;
+use rand::seq::SliceRandom;
 use rand::Rng;
 use std::option::Option;
 use std::{collections::VecDeque, u8};
 
+use crate::artwork::Artwork;
 use crate::game_ffi;
 use game_ffi::Window;
+use my_game_engine::animation::Animation;
+use my_game_engine::collision::Rect;
+use my_game_engine::key::Key;
+use my_game_engine::sprite::SpriteHandle;
+use my_game_engine::tilemap::TileMap;
 use my_game_engine::{
-    DUPE_SPRITE, GO_DOWN, GO_LEFT, GO_RIGHT, GO_UP, ON_KEY_PRESS, SPAWN_SPRITE, SPRITE_ATTR,
-    SPRITE_X, SPRITE_Y,
+    ANIMATE_SPRITE, DUPE_SPRITE, GO_DOWN, GO_LEFT, GO_RIGHT, GO_UP, ON_KEY_DOWN, ROTATE_SPRITE,
+    SPAWN_SPRITE, SPRITE_X, SPRITE_Y,
 };
 
-const SNAKE_BODY_DISPLACEMENT_SPEED_PER_ITERATION: i32 = 3;
+/// Body parts added at the head of the snake every second
+const SNAKE_BODY_DISPLACEMENT_SPEED_PER_SECOND: f32 = 300.0;
 const INITIAL_SNAKE_GROWTH_SPEED: f32 = 1.0;
 const SNAKE_GROWTH_RATE: f32 = 0.02;
+/// Snakes crawl over the food
+pub const SNAKE_LAYER: i32 = 1;
 
+/// Keys steering the snakes: arrows, or WASD
+const DIRECTION_KEYS: [(Key, Direction); 8] = [
+    (Key::Left, Direction::LEFT),
+    (Key::A, Direction::LEFT),
+    (Key::Right, Direction::RIGHT),
+    (Key::D, Direction::RIGHT),
+    (Key::Up, Direction::UP),
+    (Key::W, Direction::UP),
+    (Key::Down, Direction::DOWN),
+    (Key::S, Direction::DOWN),
+];
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum Direction {
     UP,
     DOWN,
@@ -26,6 +49,35 @@ pub enum Direction {
     RIGHT,
 }
 
+impl Direction {
+    /// Rotation of a head facing this direction, the head artwork faces right
+    fn head_rotation(self) -> f32 {
+        match self {
+            Direction::RIGHT => 0.0,
+            Direction::DOWN => 90.0,
+            Direction::LEFT => 180.0,
+            Direction::UP => 270.0,
+        }
+    }
+
+    /// The directions to turn to from this one
+    fn turns(self) -> [Direction; 2] {
+        match self {
+            Direction::LEFT | Direction::RIGHT => [Direction::UP, Direction::DOWN],
+            Direction::UP | Direction::DOWN => [Direction::LEFT, Direction::RIGHT],
+        }
+    }
+
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::RIGHT => Direction::LEFT,
+            Direction::LEFT => Direction::RIGHT,
+            Direction::UP => Direction::DOWN,
+            Direction::DOWN => Direction::UP,
+        }
+    }
+}
+
 #[derive(PartialEq)]
 pub enum SnakeKind {
     /// User controlls this snake with keyboard
@@ -37,26 +89,26 @@ pub enum SnakeKind {
 }
 
 pub struct GameSprite {
-    pub sprite: *mut game_ffi::Sprite,
+    pub sprite: SpriteHandle,
 }
 
 impl GameSprite {
-    fn from_sprite(sprite: *mut game_ffi::Sprite) -> GameSprite {
-        GameSprite { sprite: sprite }
+    fn from_sprite(sprite: SpriteHandle) -> GameSprite {
+        GameSprite { sprite }
     }
 
     fn render(&self) {
-        unsafe {
-            game_ffi::render_sprite(self.sprite);
-        }
+        self.sprite.render();
     }
 }
 
 pub struct Snake {
     /// the snake's body
     body: VecDeque<GameSprite>,
-    /// the number of body parts to move at each step
-    speed: i32,
+    /// the number of body parts to move every second
+    speed: f32,
+    /// body parts owed from previous steps, when a step lasts less than a body part
+    progress: f32,
     /// by how many pixels to move the head of the snake
     stride: f32,
     /// the direction of the snake's head
@@ -67,14 +119,20 @@ pub struct Snake {
     pub kind: SnakeKind,
     /// Random generator helps with deciding the direction of the autonomous snakes
     rng: rand::rngs::ThreadRng,
+    /// Textures of the head and the body, tinted with the snake's color
+    artwork: Artwork,
+    /// Blinking eyes of the head, `None` without head artwork
+    head_animation: Option<Animation>,
+    /// Walls of the arena, `None` in an open arena
+    walls: Option<TileMap>,
 }
 
 pub trait SnakeMovement {
-    /// Make the snake crawl without growth
-    fn crawl(&mut self);
+    /// Make the snake crawl without growth for `dt` seconds
+    fn crawl(&mut self, dt: f32);
 
-    /// Grow the snake
-    fn grow(&mut self);
+    /// Grow the snake for `dt` seconds
+    fn grow(&mut self, dt: f32);
 }
 
 impl Snake {
@@ -90,19 +148,42 @@ impl Snake {
         g: i32,
         b: i32,
     ) -> Snake {
-        let snake_body_item =
-            GameSprite::from_sprite(SPAWN_SPRITE!(false, x, y, width, height, r, g, b));
+        let mut sprite = SPAWN_SPRITE!(false, x, y, width, height, r, g, b);
+        // new heads are duplicated from this one and stay on its layer
+        sprite.set_layer(SNAKE_LAYER);
+        let snake_body_item = GameSprite::from_sprite(sprite);
         Snake {
-            kind: kind,
+            kind,
             direction: Direction::RIGHT,
-            speed: SNAKE_BODY_DISPLACEMENT_SPEED_PER_ITERATION,
+            speed: SNAKE_BODY_DISPLACEMENT_SPEED_PER_SECOND,
+            progress: 0.0,
             stride: INITIAL_SNAKE_GROWTH_SPEED,
             body: VecDeque::from([snake_body_item]),
-            window: window,
+            window,
             rng: rand::rng(),
+            artwork: Artwork::default(),
+            head_animation: None,
+            walls: None,
         }
     }
 
+    /// Draw the snake with the head and body textures of `artwork` instead of solid squares
+    pub fn with_artwork(mut self, artwork: &Artwork) -> Snake {
+        self.artwork = artwork.clone();
+        self.head_animation = artwork.head_animation();
+        if let (Some(head), Some(animation)) = (self.body.front_mut(), &self.head_animation) {
+            animation.apply(&mut head.sprite);
+        }
+        self
+    }
+
+    /// Crawl in an arena with `walls`. The user's snake runs into them, the others turn
+    /// away from them
+    pub fn with_walls(mut self, walls: &TileMap) -> Snake {
+        self.walls = Some(walls.clone());
+        self
+    }
+
     pub fn render(&self) {
         for snake_body_item in self.body.iter() {
             snake_body_item.render();
@@ -120,10 +201,23 @@ impl Snake {
     pub fn dies_from_bad_food(&self) -> bool {
         self.kind == SnakeKind::USER
     }
+
+    pub fn dies_from_walls(&self) -> bool {
+        self.kind == SnakeKind::USER
+    }
+
+    /// Whether the head of the snake is in a wall
+    pub fn hits_wall(&self) -> bool {
+        match (&self.walls, self.head()) {
+            (Some(walls), Some(head)) => walls.overlaps_solid(&head.sprite.bounds()),
+            _ => false,
+        }
+    }
     // Private Methods
 
     /// USER and BUDDY snakes are controlled manually through the keyboard, while autonomous snakes
-    /// roam around the screen in random but smooth way
+    /// roam around the screen in random but smooth way. Only key presses change the direction,
+    /// so even a tap shorter than a frame is taken into account
     fn update_direction(&mut self) {
         if self.kind == SnakeKind::AUTONOMOUS {
             let distr = rand::distr::Uniform::new_inclusive(0, 50).unwrap();
@@ -147,67 +241,112 @@ impl Snake {
             return;
         }
 
-        ON_KEY_PRESS!(game_ffi::GLFW_KEY_LEFT, {
-            self.direction = Direction::LEFT;
-        });
-
-        ON_KEY_PRESS!(game_ffi::GLFW_KEY_RIGHT, {
-            self.direction = Direction::RIGHT;
-        });
-
-        ON_KEY_PRESS!(game_ffi::GLFW_KEY_UP, {
-            self.direction = Direction::UP;
-        });
-
-        ON_KEY_PRESS!(game_ffi::GLFW_KEY_DOWN, {
-            self.direction = Direction::DOWN;
-        });
+        for (key, direction) in DIRECTION_KEYS {
+            ON_KEY_DOWN!(key, {
+                self.direction = direction;
+            });
+        }
     }
 
-    /// Move the snake forward, delete the back of the snake if no growth is expected
-    fn move_forward(&mut self, grow: bool) {
-        for _ in 0..self.speed {
-            let sprite: *mut game_ffi::Sprite = self.body.front().expect("Empty head").sprite;
-            let new_head = match self.direction {
-                Direction::LEFT => {
-                    let new_x = GO_LEFT!(sprite, self.window, self.stride);
-                    DUPE_SPRITE!(sprite, new_x, SPRITE_Y!(sprite))
-                }
-                Direction::RIGHT => {
-                    let new_x = GO_RIGHT!(sprite, self.window, self.stride);
-                    DUPE_SPRITE!(sprite, new_x, SPRITE_Y!(sprite))
-                }
-                Direction::UP => {
-                    let new_y = GO_UP!(sprite, self.window, self.stride);
-                    DUPE_SPRITE!(sprite, SPRITE_X!(sprite), new_y)
-                }
-                Direction::DOWN => {
-                    let new_y = GO_DOWN!(sprite, self.window, self.stride);
-                    DUPE_SPRITE!(sprite, SPRITE_X!(sprite), new_y)
-                }
-            };
+    /// Move the snake forward for `dt` seconds, delete the back of the snake if no growth
+    /// is expected. The distance only depends on time, not on how often it is called
+    fn move_forward(&mut self, grow: bool, dt: f32) {
+        self.progress += self.speed * dt;
+        let body_parts = self.progress.floor();
+        self.progress -= body_parts;
+        // wrap around the current window, it may have been resized
+        self.window.sync_size();
+
+        for _ in 0..body_parts as u32 {
+            self.avoid_walls();
+            let sprite: &SpriteHandle = &self.body.front().expect("Empty head").sprite;
+            let (new_x, new_y) = self.next_position(self.direction);
+            let mut new_head = DUPE_SPRITE!(sprite, new_x, new_y);
+            ROTATE_SPRITE!(new_head, self.direction.head_rotation());
             self.body.push_front(GameSprite::from_sprite(new_head));
+            // the previous head is now part of the body
+            if let (Some(neck), Some(texture)) = (self.body.get_mut(1), &self.artwork.snake_body) {
+                neck.sprite.set_texture(texture, None);
+                ROTATE_SPRITE!(neck.sprite, 0.0);
+            }
 
             if !grow {
                 self.body.pop_back();
             }
         }
     }
+
+    /// Position of the next head going in `direction`, wrapping around the window
+    fn next_position(&self, direction: Direction) -> (f32, f32) {
+        let sprite: &SpriteHandle = &self.body.front().expect("Empty head").sprite;
+        match direction {
+            Direction::LEFT => (
+                GO_LEFT!(sprite, self.window, self.stride),
+                SPRITE_Y!(sprite),
+            ),
+            Direction::RIGHT => (
+                GO_RIGHT!(sprite, self.window, self.stride),
+                SPRITE_Y!(sprite),
+            ),
+            Direction::UP => (SPRITE_X!(sprite), GO_UP!(sprite, self.window, self.stride)),
+            Direction::DOWN => (
+                SPRITE_X!(sprite),
+                GO_DOWN!(sprite, self.window, self.stride),
+            ),
+        }
+    }
+
+    /// Whether the next head going in `direction` would be in a wall
+    fn wall_ahead(&self, direction: Direction) -> bool {
+        let Some(walls) = &self.walls else {
+            return false;
+        };
+        let sprite: &SpriteHandle = &self.body.front().expect("Empty head").sprite;
+        let (x, y) = self.next_position(direction);
+        walls.overlaps_solid(&Rect::new(
+            x,
+            y,
+            sprite.width() as f32,
+            sprite.height() as f32,
+        ))
+    }
+
+    /// Turn away from a wall ahead, unless the snake dies from running into it. Turn back
+    /// when stuck in a dead end
+    fn avoid_walls(&mut self) {
+        if self.dies_from_walls() || !self.wall_ahead(self.direction) {
+            return;
+        }
+        let mut turns = self.direction.turns();
+        turns.shuffle(&mut self.rng);
+        self.direction = turns
+            .into_iter()
+            .find(|turn| !self.wall_ahead(*turn))
+            .unwrap_or(self.direction.opposite());
+    }
+
+    /// Play the head animation for `dt` seconds on the current head
+    fn animate_head(&mut self, dt: f32) {
+        if let (Some(head), Some(animation)) = (self.body.front_mut(), &mut self.head_animation) {
+            ANIMATE_SPRITE!(head.sprite, animation, dt);
+        }
+    }
 }
 
 impl SnakeMovement for Snake {
     /// move head and delete the tail without rendering
-    fn crawl(&mut self) {
+    fn crawl(&mut self, dt: f32) {
         self.update_direction();
 
-        self.move_forward(false);
+        self.move_forward(false, dt);
+        self.animate_head(dt);
     }
 
     /// expand the snake with a new head in the same direction
-    fn grow(&mut self) {
+    fn grow(&mut self, dt: f32) {
         self.stride += SNAKE_GROWTH_RATE;
 
-        self.move_forward(true);
+        self.move_forward(true, dt);
         println!(
             "Snake size: {} - speed:{} - stride: {}",
             self.body.len(),
@@ -0,0 +1,47 @@
+//! sounds.rs
+//!
+//! Sound effects of the game, loaded from the `assets/sounds` directory: a blip when a
+//! snake eats food, a buzz when the user's snake eats bad food and a jingle when the game
+//! is over. A sound that can't be loaded is not played.
+
+use std::path::Path;
+
+use my_game_engine::audio::Sound;
+use my_game_engine::{LOAD_SOUND, PLAY_SOUND};
+
+const SOUNDS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sounds");
+
+/// All the sounds of the game, `None` when missing
+#[derive(Clone, Default)]
+pub struct Sounds {
+    pub eat: Option<Sound>,
+    pub bad_food: Option<Sound>,
+    pub game_over: Option<Sound>,
+}
+
+impl Sounds {
+    pub fn load() -> Sounds {
+        Sounds {
+            eat: load_sound("eat.wav"),
+            bad_food: load_sound("bad_food.wav"),
+            game_over: load_sound("game_over.wav"),
+        }
+    }
+}
+
+/// Play a sound effect, if it was loaded
+pub fn play(sound: &Option<Sound>) {
+    if let Some(sound) = sound {
+        PLAY_SOUND!(sound);
+    }
+}
+
+fn load_sound(name: &str) -> Option<Sound> {
+    match LOAD_SOUND!(Path::new(SOUNDS_DIR).join(name)) {
+        Ok(sound) => Some(sound),
+        Err(e) => {
+            println!("Can't load {name}: {e}, it won't be played");
+            None
+        }
+    }
+}